@@ -1,13 +1,18 @@
 // Audio Engine Module
 // Handles audio playback, decoding, and state management using rodio
 
+use crate::chapters::{self, Chapter};
+use crate::cue;
 use crate::errors::AudioEngineError;
+use crate::hls;
+use crate::http_stream::{self, HttpStream, StreamEvent, StreamEventHandler, StreamStatus};
 use rodio::{Decoder, OutputStream, OutputStreamHandle, Sink, Source};
 use serde::{Deserialize, Serialize};
 use std::fs::File;
 use std::io::BufReader;
-use std::path::Path;
-use std::sync::{Arc, Mutex};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, Weak};
 use std::time::Duration;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -17,6 +22,31 @@ pub struct PlaybackState {
     pub duration: f64,
     pub volume: f32,
     pub current_track: Option<String>,
+    pub is_buffering: bool,
+    pub buffer_fill: f32,
+    pub stream_title: Option<String>,
+    /// Index into the track's chapters, and that chapter's title
+    pub current_chapter: Option<usize>,
+    pub chapter_title: Option<String>,
+    /// Where the UI started this track from, e.g. `playlist:3`; kept in the listening history
+    pub source: Option<String>,
+}
+
+/// How far past the next CUE track's start the sink may be for that track to
+/// pick up without reopening the file, e.g. when it was loaded a little late
+const SEGMENT_HANDOFF_SLACK: f64 = 2.0;
+
+/// How often the segment watcher checks a playing CUE track against its end
+const SEGMENT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// "Previous chapter" restarts the current one when this far into it
+const CHAPTER_RESTART_SECONDS: f64 = 3.0;
+
+/// Part of a file played as a track of its own
+struct Segment {
+    audio_path: PathBuf,
+    start: f64,
+    end: Option<f64>,
 }
 
 impl Default for PlaybackState {
@@ -27,6 +57,12 @@ impl Default for PlaybackState {
             duration: 0.0,
             volume: 1.0,
             current_track: None,
+            is_buffering: false,
+            buffer_fill: 0.0,
+            stream_title: None,
+            current_chapter: None,
+            chapter_title: None,
+            source: None,
         }
     }
 }
@@ -35,6 +71,16 @@ pub struct AudioEngine {
     state: Arc<Mutex<PlaybackState>>,
     sink: Arc<Mutex<Option<Sink>>>,
     _stream: Arc<Mutex<Option<(OutputStream, OutputStreamHandle)>>>,
+    stream_status: Arc<Mutex<Option<StreamStatus>>>,
+    max_stream_bandwidth: Mutex<Option<u64>>,
+    /// Incremented on every load so observers can tell tracks apart
+    track_generation: AtomicU64,
+    /// Multiplier applied on top of the user volume while fading out
+    fade_level: Mutex<f32>,
+    /// Span of the file being played when the track is from a CUE sheet
+    segment: Arc<Mutex<Option<Segment>>>,
+    /// Chapters of the loaded file
+    chapters: Mutex<Vec<Chapter>>,
 }
 
 impl AudioEngine {
@@ -43,19 +89,61 @@ impl AudioEngine {
         let (stream, stream_handle) = OutputStream::try_default()
             .map_err(|e| AudioEngineError::DeviceError(e.to_string()))?;
         
-        Ok(Self {
+        let engine = Self {
             state: Arc::new(Mutex::new(PlaybackState::default())),
             sink: Arc::new(Mutex::new(None)),
             _stream: Arc::new(Mutex::new(Some((stream, stream_handle)))),
-        })
+            stream_status: Arc::new(Mutex::new(None)),
+            max_stream_bandwidth: Mutex::new(None),
+            track_generation: AtomicU64::new(0),
+            fade_level: Mutex::new(1.0),
+            segment: Arc::new(Mutex::new(None)),
+            chapters: Mutex::new(Vec::new()),
+        };
+        
+        let (state, sink, segment) = (
+            Arc::downgrade(&engine.state),
+            Arc::downgrade(&engine.sink),
+            Arc::downgrade(&engine.segment),
+        );
+        std::thread::spawn(move || watch_segment_end(state, sink, segment));
+        
+        Ok(engine)
     }
     
     /// Get current playback state
     pub fn get_state(&self) -> Result<PlaybackState, AudioEngineError> {
-        self.state
+        let mut state = self.state
             .lock()
             .map(|state| state.clone())
-            .map_err(|e| AudioEngineError::PlaybackError(format!("Failed to lock state: {}", e)))
+            .map_err(|e| AudioEngineError::PlaybackError(format!("Failed to lock state: {}", e)))?;
+        
+        // Refresh position from the sink while something is queued
+        let (start, end) = self.segment_bounds()?;
+        if let Ok(sink_guard) = self.sink.lock() {
+            if let Some(sink) = sink_guard.as_ref().filter(|s| !s.empty()) {
+                state.current_time = (sink.get_pos().as_secs_f64() - start).max(0.0);
+                // A CUE track held at its end has run a little into the next one
+                if end.is_some() {
+                    state.current_time = state.current_time.min(state.duration);
+                }
+            }
+        }
+        
+        if let Ok(chapters) = self.chapters.lock() {
+            state.current_chapter = chapters::index_at(&chapters, state.current_time);
+            state.chapter_title = state.current_chapter.map(|index| chapters[index].title.clone());
+        }
+        
+        // Refresh buffering info for remote streams
+        if let Ok(status_guard) = self.stream_status.lock() {
+            if let Some(status) = status_guard.as_ref() {
+                state.is_buffering = status.is_buffering();
+                state.buffer_fill = status.buffer_fill();
+            }
+        }
+        
+        Ok(state)
     }
     
     /// Update playback state
@@ -69,8 +157,88 @@ impl AudioEngine {
             .map_err(|e| AudioEngineError::PlaybackError(format!("Failed to update state: {}", e)))
     }
     
-    /// Load an audio file
+    /// Start and end of the current CUE track within its file, `(0.0, None)` otherwise
+    fn segment_bounds(&self) -> Result<(f64, Option<f64>), AudioEngineError> {
+        self.segment
+            .lock()
+            .map(|segment| segment.as_ref().map(|s| (s.start, s.end)).unwrap_or((0.0, None)))
+            .map_err(|e| AudioEngineError::PlaybackError(format!("Failed to lock segment: {}", e)))
+    }
+    
+    fn set_chapters(&self, chapters: Vec<Chapter>) -> Result<(), AudioEngineError> {
+        self.chapters
+            .lock()
+            .map(|mut current| *current = chapters)
+            .map_err(|e| AudioEngineError::PlaybackError(format!("Failed to lock chapters: {}", e)))
+    }
+    
+    fn set_segment(&self, segment: Option<Segment>) -> Result<(), AudioEngineError> {
+        self.segment
+            .lock()
+            .map(|mut current| *current = segment)
+            .map_err(|e| AudioEngineError::PlaybackError(format!("Failed to lock segment: {}", e)))
+    }
+    
+    /// Whether the sink is already playing `audio_path` at `start`, i.e. the
+    /// previous CUE track of the same file just ended
+    fn continues_into(&self, audio_path: &Path, start: f64) -> Result<bool, AudioEngineError> {
+        let same_file = self.segment
+            .lock()
+            .map_err(|e| AudioEngineError::PlaybackError(format!("Failed to lock segment: {}", e)))?
+            .as_ref()
+            .is_some_and(|segment| segment.audio_path == audio_path);
+        if !same_file {
+            return Ok(false);
+        }
+        let sink_guard = self.sink.lock()
+            .map_err(|e| AudioEngineError::PlaybackError(format!("Failed to lock sink: {}", e)))?;
+        Ok(sink_guard.as_ref().filter(|s| !s.empty()).is_some_and(|sink| {
+            let position = sink.get_pos().as_secs_f64();
+            position >= start - 0.5 && position <= start + SEGMENT_HANDOFF_SLACK
+        }))
+    }
+    
+    /// Load an audio file, or one track of a file split by a CUE sheet
     pub fn load_track(&self, file_path: &Path) -> Result<Duration, AudioEngineError> {
+        let (audio_path, cue_number) = cue::split_virtual_path(&file_path.to_string_lossy());
+        let segment = match cue_number {
+            Some(number) => Some(cue::find_segment(&audio_path, number).ok_or_else(|| {
+                AudioEngineError::LoadError(format!("No track {} in the CUE sheet of {}", number, audio_path.display()))
+            })?),
+            None => None,
+        };
+        
+        // Gapless: keep the sink running into the next track of the same file
+        if let Some(segment) = segment.as_ref().filter(|s| s.end.is_some()) {
+            if self.continues_into(&audio_path, segment.track.start)? {
+                let duration = Duration::from_secs_f64(segment.end.unwrap_or_default() - segment.track.start);
+                self.set_segment(Some(Segment {
+                    audio_path: audio_path.clone(),
+                    start: segment.track.start,
+                    end: segment.end,
+                }))?;
+                self.set_chapters(Vec::new())?;
+                // It may have been held at the end of the previous track
+                if let Some(sink) = self.sink.lock()
+                    .map_err(|e| AudioEngineError::PlaybackError(format!("Failed to lock sink: {}", e)))?
+                    .as_ref()
+                {
+                    sink.play();
+                }
+                self.track_generation.fetch_add(1, Ordering::SeqCst);
+                self.update_state(|state| {
+                    state.is_playing = true;
+                    state.duration = duration.as_secs_f64();
+                    state.current_time = 0.0;
+                    state.current_track = Some(file_path.to_string_lossy().to_string());
+                })?;
+                return Ok(duration);
+            }
+        }
+        
+        let track_path = file_path.to_string_lossy().to_string();
+        let file_path = audio_path.as_path();
+        
         // Open the file
         let file = File::open(file_path)
             .map_err(|e| AudioEngineError::LoadError(format!("Failed to open file: {}", e)))?;
@@ -82,22 +250,17 @@ impl AudioEngine {
             .map_err(|e| AudioEngineError::DecodeError(format!("Failed to decode audio: {}", e)))?;
         
         // Get duration
-        let duration = source.total_duration()
-            .ok_or_else(|| AudioEngineError::LoadError("Could not determine duration".to_string()))?;
+        let start = segment.as_ref().map(|s| s.track.start).unwrap_or(0.0);
+        let end = match segment.as_ref().and_then(|s| s.end) {
+            Some(end) => end,
+            None => source.total_duration()
+                .ok_or_else(|| AudioEngineError::LoadError("Could not determine duration".to_string()))?
+                .as_secs_f64(),
+        };
+        let duration = Duration::from_secs_f64((end - start).max(0.0));
         
         // Create new sink
-        let stream_guard = self._stream.lock()
-            .map_err(|e| AudioEngineError::DeviceError(format!("Failed to lock stream: {}", e)))?;
-        
-        let (_stream, stream_handle) = stream_guard.as_ref()
-            .ok_or_else(|| AudioEngineError::DeviceError("Audio stream not initialized".to_string()))?;
-        
-        let sink = Sink::try_new(stream_handle)
-            .map_err(|e| AudioEngineError::DeviceError(format!("Failed to create sink: {}", e)))?;
-        
-        // Set volume from state
-        let current_volume = self.get_state()?.volume;
-        sink.set_volume(current_volume);
+        let sink = self.create_sink()?;
         
         // Reload the file for the sink
         let file2 = File::open(file_path)
@@ -108,23 +271,116 @@ impl AudioEngine {
         
         sink.append(source2);
         sink.pause(); // Start paused
+        if start > 0.0 {
+            sink.try_seek(Duration::from_secs_f64(start))
+                .map_err(|e| AudioEngineError::LoadError(format!("Failed to seek to track start: {}", e)))?;
+        }
         
         // Store the sink
-        let mut sink_guard = self.sink.lock()
-            .map_err(|e| AudioEngineError::PlaybackError(format!("Failed to lock sink: {}", e)))?;
-        *sink_guard = Some(sink);
+        self.replace_sink(sink, None)?;
+        // A CUE track is already a part of its file; chapters would cover the rest too
+        self.set_chapters(match segment {
+            Some(_) => Vec::new(),
+            None => chapters::read(file_path, duration.as_secs_f64()),
+        })?;
+        self.set_segment(segment.map(|segment| Segment {
+            audio_path: audio_path.clone(),
+            start,
+            end: segment.end,
+        }))?;
         
         // Update state
         self.update_state(|state| {
             state.duration = duration.as_secs_f64();
             state.current_time = 0.0;
             state.is_playing = false;
-            state.current_track = Some(file_path.to_string_lossy().to_string());
+            state.current_track = Some(track_path);
+            state.is_buffering = false;
+            state.buffer_fill = 0.0;
+            state.stream_title = None;
+        })?;
+        
+        Ok(duration)
+    }
+    
+    /// Take what opening a remote stream needs, so the connection can be made
+    /// with the engine unlocked; the previous stream title is cleared
+    pub fn stream_opener(&self) -> Result<StreamOpener, AudioEngineError> {
+        let max_bandwidth = *self.max_stream_bandwidth.lock()
+            .map_err(|e| AudioEngineError::PlaybackError(format!("Failed to lock settings: {}", e)))?;
+        self.update_state(|state| state.stream_title = None)?;
+        Ok(StreamOpener {
+            state: self.state.clone(),
+            max_bandwidth,
+        })
+    }
+    
+    /// Play a stream opened by `StreamOpener::open`; returns `None` for live
+    /// streams without a known duration
+    pub fn install_stream(&self, opened: OpenedStream) -> Result<Option<Duration>, AudioEngineError> {
+        let OpenedStream { url, source, status, duration } = opened;
+        self.set_segment(None)?;
+        self.set_chapters(Vec::new())?;
+        
+        let sink = self.create_sink()?;
+        sink.append(source);
+        sink.pause(); // Start paused
+        
+        self.replace_sink(sink, Some(status))?;
+        
+        self.update_state(|state| {
+            state.duration = duration.map(|d| d.as_secs_f64()).unwrap_or(0.0);
+            state.current_time = 0.0;
+            state.is_playing = false;
+            state.current_track = Some(url);
+            state.is_buffering = true;
+            state.buffer_fill = 0.0;
         })?;
         
         Ok(duration)
     }
     
+    /// Create a sink on the output stream using the current volume
+    fn create_sink(&self) -> Result<Sink, AudioEngineError> {
+        let stream_guard = self._stream.lock()
+            .map_err(|e| AudioEngineError::DeviceError(format!("Failed to lock stream: {}", e)))?;
+        
+        let (_stream, stream_handle) = stream_guard.as_ref()
+            .ok_or_else(|| AudioEngineError::DeviceError("Audio stream not initialized".to_string()))?;
+        
+        let sink = Sink::try_new(stream_handle)
+            .map_err(|e| AudioEngineError::DeviceError(format!("Failed to create sink: {}", e)))?;
+        
+        // Set volume from state
+        let current_volume = self.get_state()?.volume;
+        sink.set_volume(current_volume * self.fade_level()?);
+        
+        Ok(sink)
+    }
+    
+    /// Swap in a new sink, stopping whatever was playing before
+    fn replace_sink(&self, sink: Sink, status: Option<StreamStatus>) -> Result<(), AudioEngineError> {
+        let mut sink_guard = self.sink.lock()
+            .map_err(|e| AudioEngineError::PlaybackError(format!("Failed to lock sink: {}", e)))?;
+        if let Some(previous) = sink_guard.replace(sink) {
+            previous.stop();
+        }
+        drop(sink_guard);
+        
+        let mut status_guard = self.stream_status.lock()
+            .map_err(|e| AudioEngineError::PlaybackError(format!("Failed to lock stream status: {}", e)))?;
+        *status_guard = status;
+        
+        self.track_generation.fetch_add(1, Ordering::SeqCst);
+        
+        Ok(())
+    }
+    
+    /// Number of tracks loaded so far
+    pub fn track_generation(&self) -> u64 {
+        self.track_generation.load(Ordering::SeqCst)
+    }
+    
     /// Start or resume playback
     pub fn play(&self) -> Result<(), AudioEngineError> {
         let sink_guard = self.sink.lock()
@@ -174,15 +430,87 @@ impl AudioEngine {
         
         drop(sink_guard);
         
+        if let Ok(mut status_guard) = self.stream_status.lock() {
+            *status_guard = None;
+        }
+        self.set_segment(None)?;
+        self.set_chapters(Vec::new())?;
+        
         self.update_state(|state| {
             state.is_playing = false;
             state.current_time = 0.0;
             state.current_track = None;
+            state.is_buffering = false;
+            state.buffer_fill = 0.0;
+            state.stream_title = None;
         })?;
         
         Ok(())
     }
     
+    /// Seek to a position in seconds
+    pub fn seek(&self, position: f64) -> Result<(), AudioEngineError> {
+        let position = position.max(0.0);
+        let (start, _) = self.segment_bounds()?;
+        
+        let sink_guard = self.sink.lock()
+            .map_err(|e| AudioEngineError::PlaybackError(format!("Failed to lock sink: {}", e)))?;
+        
+        let sink = sink_guard.as_ref()
+            .ok_or_else(|| AudioEngineError::PlaybackError("No track loaded".to_string()))?;
+        
+        sink.try_seek(Duration::from_secs_f64(start + position))
+            .map_err(|e| AudioEngineError::PlaybackError(format!("Failed to seek: {}", e)))?;
+        
+        drop(sink_guard);
+        
+        self.update_state(|state| {
+            state.current_time = position;
+        })
+    }
+    
+    /// Jump to the start of chapter `index`
+    pub fn seek_chapter(&self, index: usize) -> Result<(), AudioEngineError> {
+        let start = self.chapters
+            .lock()
+            .map_err(|e| AudioEngineError::PlaybackError(format!("Failed to lock chapters: {}", e)))?
+            .get(index)
+            .map(|chapter| chapter.start)
+            .ok_or_else(|| AudioEngineError::PlaybackError(format!("No chapter {}", index)))?;
+        self.seek(start)
+    }
+    
+    pub fn next_chapter(&self) -> Result<(), AudioEngineError> {
+        let next = self.get_state()?.current_chapter.map_or(0, |index| index + 1);
+        self.seek_chapter(next)
+    }
+    
+    /// Back to the start of the current chapter, or to the previous one when
+    /// the current chapter has only just started
+    pub fn previous_chapter(&self) -> Result<(), AudioEngineError> {
+        let state = self.get_state()?;
+        let Some(index) = state.current_chapter else {
+            return self.seek_chapter(0);
+        };
+        let chapter_start = self.chapters
+            .lock()
+            .map_err(|e| AudioEngineError::PlaybackError(format!("Failed to lock chapters: {}", e)))?[index]
+            .start;
+        if index == 0 || state.current_time - chapter_start > CHAPTER_RESTART_SECONDS {
+            self.seek_chapter(index)
+        } else {
+            self.seek_chapter(index - 1)
+        }
+    }
+    
+    /// Limit the bandwidth of HLS variants chosen for future streams (`None` picks the best)
+    pub fn set_max_stream_bandwidth(&self, bandwidth: Option<u64>) -> Result<(), AudioEngineError> {
+        let mut max_bandwidth = self.max_stream_bandwidth.lock()
+            .map_err(|e| AudioEngineError::PlaybackError(format!("Failed to lock settings: {}", e)))?;
+        *max_bandwidth = bandwidth;
+        Ok(())
+    }
+    
     /// Set volume (0.0 to 1.0)
     pub fn set_volume(&self, volume: f32) -> Result<(), AudioEngineError> {
         let clamped_volume = volume.clamp(0.0, 1.0);
@@ -191,7 +519,7 @@ impl AudioEngine {
             .map_err(|e| AudioEngineError::PlaybackError(format!("Failed to lock sink: {}", e)))?;
         
         if let Some(sink) = sink_guard.as_ref() {
-            sink.set_volume(clamped_volume);
+            sink.set_volume(clamped_volume * self.fade_level()?);
         }
         
         drop(sink_guard);
@@ -203,12 +531,122 @@ impl AudioEngine {
         Ok(())
     }
     
-    /// Check if playback is finished
+    fn fade_level(&self) -> Result<f32, AudioEngineError> {
+        self.fade_level
+            .lock()
+            .map(|level| *level)
+            .map_err(|e| AudioEngineError::PlaybackError(format!("Failed to lock fade level: {}", e)))
+    }
+    
+    /// Attenuate output (0.0 to 1.0) without touching the user's volume setting
+    pub fn set_fade_level(&self, level: f32) -> Result<(), AudioEngineError> {
+        let level = level.clamp(0.0, 1.0);
+        
+        let mut fade_guard = self.fade_level.lock()
+            .map_err(|e| AudioEngineError::PlaybackError(format!("Failed to lock fade level: {}", e)))?;
+        *fade_guard = level;
+        drop(fade_guard);
+        
+        let volume = self.get_state()?.volume;
+        let sink_guard = self.sink.lock()
+            .map_err(|e| AudioEngineError::PlaybackError(format!("Failed to lock sink: {}", e)))?;
+        
+        if let Some(sink) = sink_guard.as_ref() {
+            sink.set_volume(volume * level);
+        }
+        
+        Ok(())
+    }
+    
+    /// Check if playback is finished; a CUE track finishes at its end mark
     pub fn is_finished(&self) -> Result<bool, AudioEngineError> {
+        let (_, end) = self.segment_bounds()?;
         let sink_guard = self.sink.lock()
             .map_err(|e| AudioEngineError::PlaybackError(format!("Failed to lock sink: {}", e)))?;
         
-        Ok(sink_guard.as_ref().map(|s| s.empty()).unwrap_or(true))
+        let Some(sink) = sink_guard.as_ref().filter(|s| !s.empty()) else {
+            return Ok(true);
+        };
+        Ok(end.is_some_and(|end| sink.get_pos().as_secs_f64() >= end))
+    }
+}
+
+/// What `StreamOpener::open` needs from the engine, taken under its lock
+pub struct StreamOpener {
+    state: Arc<Mutex<PlaybackState>>,
+    max_bandwidth: Option<u64>,
+}
+
+/// A remote stream that is connected and probed, ready for `AudioEngine::install_stream`
+pub struct OpenedStream {
+    url: String,
+    source: Decoder<HttpStream>,
+    status: StreamStatus,
+    duration: Option<Duration>,
+}
+
+impl StreamOpener {
+    /// Connect to a remote http(s) stream or HLS playlist and start decoding it.
+    /// Radio metadata and reconnects are forwarded to `on_event`; a slow or dead
+    /// server only holds up the caller, not the engine.
+    pub fn open(self, url: &str, on_event: StreamEventHandler) -> Result<OpenedStream, AudioEngineError> {
+        // Keep the current song title in the playback state without needing the engine lock
+        let state = self.state;
+        let handler: StreamEventHandler = Arc::new(move |event: StreamEvent| {
+            if let StreamEvent::Metadata(metadata) = &event {
+                if let Ok(mut state) = state.lock() {
+                    state.stream_title = metadata.stream_title.clone();
+                }
+            }
+            on_event(event);
+        });
+        
+        let (stream, playlist_duration) = if hls::is_hls_url(url) {
+            hls::open(url, self.max_bandwidth)?
+        } else {
+            (HttpStream::open(url, Some(handler))?, None)
+        };
+        let status = stream.status();
+        
+        let source = Decoder::new(stream)
+            .map_err(|e| AudioEngineError::DecodeError(format!("Failed to decode stream: {}", e)))?;
+        let duration = source.total_duration()
+            .or_else(|| playlist_duration.map(Duration::from_secs_f64));
+        
+        Ok(OpenedStream {
+            url: url.to_string(),
+            source,
+            status,
+            duration,
+        })
+    }
+}
+
+/// Hold a CUE track at its end when nothing was queued after it, so it does not
+/// play on into the next track of the file; runs until the engine is dropped
+fn watch_segment_end(
+    state: Weak<Mutex<PlaybackState>>,
+    sink: Weak<Mutex<Option<Sink>>>,
+    segment: Weak<Mutex<Option<Segment>>>,
+) {
+    loop {
+        std::thread::sleep(SEGMENT_POLL_INTERVAL);
+        let (Some(state), Some(sink), Some(segment)) = (state.upgrade(), sink.upgrade(), segment.upgrade()) else {
+            return;
+        };
+        
+        // Holding the segment keeps a gapless load from switching tracks mid-check
+        let Ok(segment_guard) = segment.lock() else { return };
+        let Some(end) = segment_guard.as_ref().and_then(|segment| segment.end) else { continue };
+        let Ok(sink_guard) = sink.lock() else { return };
+        let Some(sink) = sink_guard.as_ref().filter(|s| !s.empty() && !s.is_paused()) else { continue };
+        
+        if sink.get_pos().as_secs_f64() >= end {
+            sink.pause();
+            if let Ok(mut state) = state.lock() {
+                state.is_playing = false;
+            }
+        }
     }
 }
 
@@ -220,7 +658,8 @@ impl Default for AudioEngine {
 
 // Tauri Commands
 
-use tauri::{AppHandle, Emitter, State};
+use crate::session::SessionState;
+use tauri::{AppHandle, Emitter, Manager, State};
 use std::sync::Mutex as StdMutex;
 
 // Wrapper to make AudioEngine Send + Sync
@@ -232,18 +671,71 @@ pub struct AudioEngineState {
 unsafe impl Send for AudioEngineState {}
 unsafe impl Sync for AudioEngineState {}
 
+#[derive(Debug, Clone, Serialize)]
+pub struct StreamTitleEvent {
+    pub url: String,
+    pub title: Option<String>,
+    pub stream_url: Option<String>,
+}
+
+/// Forward stream events from the fetcher thread to the frontend
+fn stream_event_emitter(app: AppHandle, url: String) -> StreamEventHandler {
+    Arc::new(move |event| {
+        let result = match event {
+            StreamEvent::Metadata(metadata) => app.emit("audio:stream_title", StreamTitleEvent {
+                url: url.clone(),
+                title: metadata.stream_title,
+                stream_url: metadata.stream_url,
+            }),
+            StreamEvent::Reconnecting { .. } => app.emit("audio:stream_reconnecting", &event),
+            StreamEvent::Reconnected => app.emit("audio:stream_reconnected", &url),
+        };
+        if let Err(e) = result {
+            log::warn!("Failed to emit stream event: {}", e);
+        }
+    })
+}
+
 #[tauri::command]
 pub fn audio_load_track(
     file_path: String,
+    source: Option<String>,
     engine: State<'_, AudioEngineState>,
     app: AppHandle,
 ) -> Result<PlaybackState, String> {
+    // Connecting to a stream can take a while, so the engine is only locked
+    // to take its settings and then to install the opened stream
+    let opened = if http_stream::is_remote_url(&file_path) {
+        let opener = engine.engine.lock()
+            .map_err(|e| format!("Failed to lock engine: {}", e))?
+            .stream_opener()
+            .map_err(|e| e.to_string())?;
+        let opened = opener.open(&file_path, stream_event_emitter(app.clone(), file_path.clone()))
+            .map_err(|e| e.to_string())?;
+        Some(opened)
+    } else {
+        None
+    };
+    
     let engine_guard = engine.engine.lock()
         .map_err(|e| format!("Failed to lock engine: {}", e))?;
     
-    let path = Path::new(&file_path);
-    
-    engine_guard.load_track(path)
+    if let Some(opened) = opened {
+        engine_guard.install_stream(opened)
+            .map_err(|e| e.to_string())?;
+    } else {
+        engine_guard.load_track(Path::new(&file_path))
+            .map_err(|e| e.to_string())?;
+
+        // Audiobooks and other auto-resume files reopen where they were left
+        let resume_position = app.try_state::<SessionState>()
+            .and_then(|session| session.resume_position(&file_path));
+        if let Some(position) = resume_position {
+            engine_guard.seek(position)
+                .map_err(|e| e.to_string())?;
+        }
+    }
+    engine_guard.update_state(|state| state.source = source)
         .map_err(|e| e.to_string())?;
     
     let state = engine_guard.get_state()
@@ -319,6 +811,73 @@ pub fn audio_stop(
     Ok(state)
 }
 
+#[tauri::command]
+pub fn audio_seek(
+    position: f64,
+    engine: State<'_, AudioEngineState>,
+    app: AppHandle,
+) -> Result<PlaybackState, String> {
+    let engine_guard = engine.engine.lock()
+        .map_err(|e| format!("Failed to lock engine: {}", e))?;
+    
+    engine_guard.seek(position)
+        .map_err(|e| e.to_string())?;
+    
+    let state = engine_guard.get_state()
+        .map_err(|e| e.to_string())?;
+    
+    // Emit state changed event
+    app.emit("audio:state_changed", &state)
+        .map_err(|e| format!("Failed to emit event: {}", e))?;
+    
+    Ok(state)
+}
+
+/// Apply a chapter jump and report the new state
+fn chapter_command<F>(engine: &AudioEngineState, app: &AppHandle, jump: F) -> Result<PlaybackState, String>
+where
+    F: FnOnce(&AudioEngine) -> Result<(), AudioEngineError>,
+{
+    let engine_guard = engine.engine.lock()
+        .map_err(|e| format!("Failed to lock engine: {}", e))?;
+    
+    jump(&engine_guard)
+        .map_err(|e| e.to_string())?;
+    
+    let state = engine_guard.get_state()
+        .map_err(|e| e.to_string())?;
+    
+    app.emit("audio:state_changed", &state)
+        .map_err(|e| format!("Failed to emit event: {}", e))?;
+    
+    Ok(state)
+}
+
+#[tauri::command]
+pub fn audio_next_chapter(
+    engine: State<'_, AudioEngineState>,
+    app: AppHandle,
+) -> Result<PlaybackState, String> {
+    chapter_command(&engine, &app, AudioEngine::next_chapter)
+}
+
+#[tauri::command]
+pub fn audio_previous_chapter(
+    engine: State<'_, AudioEngineState>,
+    app: AppHandle,
+) -> Result<PlaybackState, String> {
+    chapter_command(&engine, &app, AudioEngine::previous_chapter)
+}
+
+#[tauri::command]
+pub fn audio_seek_chapter(
+    index: usize,
+    engine: State<'_, AudioEngineState>,
+    app: AppHandle,
+) -> Result<PlaybackState, String> {
+    chapter_command(&engine, &app, |engine| engine.seek_chapter(index))
+}
+
 #[tauri::command]
 pub fn audio_get_state(
     engine: State<'_, AudioEngineState>,
@@ -352,6 +911,17 @@ pub fn audio_set_volume(
     Ok(state)
 }
 
+#[tauri::command]
+pub fn audio_set_max_stream_bandwidth(
+    bandwidth: Option<u64>,
+    engine: State<'_, AudioEngineState>,
+) -> Result<(), String> {
+    let engine_guard = engine.engine.lock()
+        .map_err(|e| format!("Failed to lock engine: {}", e))?;
+    
+    engine_guard.set_max_stream_bandwidth(bandwidth)
+        .map_err(|e| e.to_string())
+}
 
 #[tauri::command]
 pub fn audio_check_finished(
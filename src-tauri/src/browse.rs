@@ -0,0 +1,520 @@
+// Browse Module
+// Aggregated views of the library store (artists, albums, genres, decades and the
+// folder tree) with sorting and paging, so the UI never has to walk flat track lists
+
+use crate::errors::LibraryError;
+use crate::file_manager::TrackMetadata;
+use crate::library::{self, escape_like, Library};
+use rusqlite::{Row, ToSql};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, MAIN_SEPARATOR_STR};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BrowseSort {
+    #[default]
+    Name,
+    TrackCount,
+    AlbumCount,
+    Duration,
+    Year,
+    DateAdded,
+}
+
+/// Sorting, paging and filters shared by the browse views. A sort a view has
+/// no column for falls back to the name; filters narrow the tracks counted.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BrowseQuery {
+    pub sort: BrowseSort,
+    pub descending: bool,
+    pub limit: Option<u32>,
+    pub offset: Option<u32>,
+    pub artist_id: Option<i64>,
+    pub genre: Option<String>,
+    /// First year of the decade, e.g. 1980
+    pub decade: Option<i32>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Page<T> {
+    /// Items across all pages
+    pub total: u32,
+    pub items: Vec<T>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ArtistSummary {
+    pub id: i64,
+    pub name: String,
+    pub album_count: u32,
+    pub track_count: u32,
+    pub duration: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AlbumSummary {
+    pub id: i64,
+    pub title: String,
+    pub artist_id: i64,
+    pub artist: String,
+    pub year: Option<i32>,
+    pub track_count: u32,
+    pub duration: f64,
+    /// Track to ask `get_cover_art` for: the first one of the album
+    pub cover_track_id: String,
+    pub date_added: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GenreSummary {
+    pub name: String,
+    pub artist_count: u32,
+    pub album_count: u32,
+    pub track_count: u32,
+    pub duration: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DecadeSummary {
+    pub decade: i32,
+    pub album_count: u32,
+    pub track_count: u32,
+    pub duration: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FolderNode {
+    pub name: String,
+    pub path: String,
+    /// Tracks anywhere below the folder
+    pub track_count: u32,
+    pub duration: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum FolderEntry {
+    Folder(FolderNode),
+    Track(Box<TrackMetadata>),
+}
+
+/// Filter on `t`, with the parameters in `filter_params` order
+const TRACK_FILTER: &str = "(?1 IS NULL OR t.artist_id = ?1)
+    AND (?2 IS NULL OR t.genre = ?2 COLLATE NOCASE)
+    AND (?3 IS NULL OR (t.year >= ?3 AND t.year < ?3 + 10))";
+
+fn filter_params(query: &BrowseQuery) -> [&dyn ToSql; 3] {
+    [&query.artist_id, &query.genre, &query.decade]
+}
+
+fn paging(query: &BrowseQuery) -> (usize, usize) {
+    (
+        query.offset.unwrap_or(0) as usize,
+        query.limit.map_or(usize::MAX, |limit| limit as usize),
+    )
+}
+
+impl Library {
+    /// One page of `grouped`, a query with a `name` column, ordered by the
+    /// column `columns` gives for the requested sort
+    fn browse_page<T, F>(
+        &self,
+        grouped: &str,
+        params: &[&dyn ToSql],
+        columns: &[(BrowseSort, &str)],
+        query: &BrowseQuery,
+        map: F,
+    ) -> Result<Page<T>, LibraryError>
+    where
+        F: FnMut(&Row) -> rusqlite::Result<T>,
+    {
+        let total = self.conn.query_row(&format!("SELECT COUNT(*) FROM ({})", grouped), params, |row| row.get(0))?;
+        let column = columns.iter()
+            .find(|(sort, _)| *sort == query.sort)
+            .map_or("name COLLATE NOCASE", |(_, column)| *column);
+        let sql = format!(
+            "SELECT * FROM ({}) ORDER BY {} {}, name COLLATE NOCASE LIMIT {} OFFSET {}",
+            grouped,
+            column,
+            if query.descending { "DESC" } else { "ASC" },
+            query.limit.map(i64::from).unwrap_or(-1),
+            query.offset.unwrap_or(0),
+        );
+        let mut stmt = self.conn.prepare(&sql)?;
+        let items = stmt
+            .query_map(params, map)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Page { total, items })
+    }
+
+    pub fn browse_artists(&self, query: &BrowseQuery) -> Result<Page<ArtistSummary>, LibraryError> {
+        let grouped = format!(
+            "SELECT ar.id, ar.name AS name, COUNT(DISTINCT t.album_id) AS album_count, COUNT(t.id) AS track_count,
+                COALESCE(SUM(t.duration), 0) AS duration, MAX(t.date_added) AS date_added
+             FROM artists ar JOIN tracks t ON t.artist_id = ar.id
+             WHERE {}
+             GROUP BY ar.id",
+            TRACK_FILTER
+        );
+        let columns = [
+            (BrowseSort::AlbumCount, "album_count"),
+            (BrowseSort::TrackCount, "track_count"),
+            (BrowseSort::Duration, "duration"),
+            (BrowseSort::DateAdded, "date_added"),
+        ];
+        self.browse_page(&grouped, &filter_params(query), &columns, query, |row| Ok(ArtistSummary {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            album_count: row.get(2)?,
+            track_count: row.get(3)?,
+            duration: row.get(4)?,
+        }))
+    }
+
+    /// Albums with their first track for cover art; `artist_id` matches the
+    /// album artist, so compilations show up under it rather than per track
+    pub fn browse_albums(&self, query: &BrowseQuery) -> Result<Page<AlbumSummary>, LibraryError> {
+        let grouped = format!(
+            "SELECT al.id, al.title AS name, ar.id, ar.name, MAX(t.year) AS year, COUNT(t.id) AS track_count,
+                COALESCE(SUM(t.duration), 0) AS duration,
+                (SELECT c.id FROM tracks c WHERE c.album_id = al.id
+                    ORDER BY c.disc_number, c.track_number, c.file_path LIMIT 1),
+                MAX(t.date_added) AS date_added
+             FROM albums al
+             JOIN artists ar ON ar.id = al.artist_id
+             JOIN tracks t ON t.album_id = al.id
+             WHERE {}
+             GROUP BY al.id",
+            TRACK_FILTER.replace("t.artist_id", "al.artist_id")
+        );
+        let columns = [
+            (BrowseSort::Year, "year"),
+            (BrowseSort::TrackCount, "track_count"),
+            (BrowseSort::Duration, "duration"),
+            (BrowseSort::DateAdded, "date_added"),
+        ];
+        self.browse_page(&grouped, &filter_params(query), &columns, query, |row| Ok(AlbumSummary {
+            id: row.get(0)?,
+            title: row.get(1)?,
+            artist_id: row.get(2)?,
+            artist: row.get(3)?,
+            year: row.get(4)?,
+            track_count: row.get(5)?,
+            duration: row.get(6)?,
+            cover_track_id: row.get(7)?,
+            date_added: row.get(8)?,
+        }))
+    }
+
+    /// Genres as tagged; spellings that differ only in case are one genre
+    pub fn browse_genres(&self, query: &BrowseQuery) -> Result<Page<GenreSummary>, LibraryError> {
+        let grouped = format!(
+            "SELECT MIN(t.genre) AS name, COUNT(DISTINCT t.artist_id) AS artist_count,
+                COUNT(DISTINCT t.album_id) AS album_count, COUNT(t.id) AS track_count,
+                COALESCE(SUM(t.duration), 0) AS duration
+             FROM tracks t
+             WHERE t.genre IS NOT NULL AND t.genre != '' AND {}
+             GROUP BY t.genre COLLATE NOCASE",
+            TRACK_FILTER
+        );
+        let columns = [
+            (BrowseSort::AlbumCount, "album_count"),
+            (BrowseSort::TrackCount, "track_count"),
+            (BrowseSort::Duration, "duration"),
+        ];
+        self.browse_page(&grouped, &filter_params(query), &columns, query, |row| Ok(GenreSummary {
+            name: row.get(0)?,
+            artist_count: row.get(1)?,
+            album_count: row.get(2)?,
+            track_count: row.get(3)?,
+            duration: row.get(4)?,
+        }))
+    }
+
+    pub fn browse_decades(&self, query: &BrowseQuery) -> Result<Page<DecadeSummary>, LibraryError> {
+        let grouped = format!(
+            "SELECT (t.year / 10) * 10 AS name, COUNT(DISTINCT t.album_id) AS album_count,
+                COUNT(t.id) AS track_count, COALESCE(SUM(t.duration), 0) AS duration
+             FROM tracks t
+             WHERE t.year > 0 AND {}
+             GROUP BY t.year / 10",
+            TRACK_FILTER
+        );
+        let columns = [
+            (BrowseSort::Year, "name"),
+            (BrowseSort::AlbumCount, "album_count"),
+            (BrowseSort::TrackCount, "track_count"),
+            (BrowseSort::Duration, "duration"),
+        ];
+        self.browse_page(&grouped, &filter_params(query), &columns, query, |row| Ok(DecadeSummary {
+            decade: row.get(0)?,
+            album_count: row.get(1)?,
+            track_count: row.get(2)?,
+            duration: row.get(3)?,
+        }))
+    }
+
+    /// Subfolders of `path`, then the tracks directly in it, as stored in the
+    /// library. Without a path, the library roots are listed.
+    pub fn browse_folder(&self, path: Option<&str>, query: &BrowseQuery) -> Result<Page<FolderEntry>, LibraryError> {
+        let Some(path) = path else {
+            let mut roots: Vec<FolderNode> = Vec::new();
+            let mut stmt = self.conn.prepare(
+                "SELECT f.path, COUNT(t.id), COALESCE(SUM(t.duration), 0)
+                 FROM folders f LEFT JOIN tracks t ON t.folder_id = f.id
+                 GROUP BY f.id",
+            )?;
+            for row in stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get(1)?, row.get(2)?)))? {
+                let (path, track_count, duration) = row?;
+                let name = Path::new(&path).file_name().map_or(path.clone(), |n| n.to_string_lossy().to_string());
+                roots.push(FolderNode { name, path, track_count, duration });
+            }
+            sort_folders(&mut roots, query);
+            let (offset, limit) = paging(query);
+            return Ok(Page {
+                total: roots.len() as u32,
+                items: roots.into_iter().skip(offset).take(limit).map(FolderEntry::Folder).collect(),
+            });
+        };
+        if library::folder_for_path(&self.conn, path)?.is_none() {
+            return Err(LibraryError::FolderNotFound(path.to_string()));
+        }
+
+        let base = path.trim_end_matches(MAIN_SEPARATOR_STR);
+        let prefix = format!("{}{}", base, MAIN_SEPARATOR_STR);
+        let mut stmt = self.conn.prepare(
+            "SELECT t.id, t.file_path, t.duration FROM tracks t WHERE t.file_path LIKE ?1 ESCAPE '\\'",
+        )?;
+        let rows = stmt.query_map([format!("{}%", escape_like(&prefix))], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, f64>(2)?))
+        })?;
+
+        let mut folders: HashMap<String, FolderNode> = HashMap::new();
+        let mut tracks: Vec<(String, String, f64)> = Vec::new();
+        for row in rows {
+            let (id, file_path, duration) = row?;
+            let relative = &file_path[prefix.len()..];
+            match relative.split_once(MAIN_SEPARATOR_STR) {
+                Some((name, _)) => {
+                    let folder = folders.entry(name.to_string()).or_insert_with(|| FolderNode {
+                        name: name.to_string(),
+                        path: format!("{}{}", prefix, name),
+                        track_count: 0,
+                        duration: 0.0,
+                    });
+                    folder.track_count += 1;
+                    folder.duration += duration;
+                }
+                None => tracks.push((id, relative.to_string(), duration)),
+            }
+        }
+
+        let mut folders: Vec<FolderNode> = folders.into_values().collect();
+        sort_folders(&mut folders, query);
+        tracks.sort_by(|a, b| match query.sort {
+            BrowseSort::Duration => a.2.total_cmp(&b.2),
+            _ => natural_key(&a.1).cmp(&natural_key(&b.1)),
+        });
+        if query.descending {
+            tracks.reverse();
+        }
+
+        let total = (folders.len() + tracks.len()) as u32;
+        let (offset, limit) = paging(query);
+        let mut items: Vec<FolderEntry> = folders.into_iter().map(FolderEntry::Folder).skip(offset).take(limit).collect();
+        let offset = offset.saturating_sub(total as usize - tracks.len());
+        for (id, _, _) in tracks.into_iter().skip(offset).take(limit - items.len()) {
+            if let Some(track) = self.track(&id)? {
+                items.push(FolderEntry::Track(Box::new(track)));
+            }
+        }
+        Ok(Page { total, items })
+    }
+}
+
+/// Lowercase name with digit runs zero-padded, so "Track 10" sorts after "Track 9"
+fn natural_key(name: &str) -> String {
+    let mut key = String::with_capacity(name.len() + 8);
+    let mut digits = String::new();
+    for c in name.chars().flat_map(char::to_lowercase).chain(std::iter::once('\0')) {
+        if c.is_ascii_digit() {
+            digits.push(c);
+            continue;
+        }
+        if !digits.is_empty() {
+            key.push_str(&format!("{:0>10}", digits));
+            digits.clear();
+        }
+        if c != '\0' {
+            key.push(c);
+        }
+    }
+    key
+}
+
+fn sort_folders(folders: &mut [FolderNode], query: &BrowseQuery) {
+    folders.sort_by(|a, b| {
+        let order = match query.sort {
+            BrowseSort::TrackCount => a.track_count.cmp(&b.track_count),
+            BrowseSort::Duration => a.duration.total_cmp(&b.duration),
+            _ => std::cmp::Ordering::Equal,
+        };
+        order.then_with(|| natural_key(&a.name).cmp(&natural_key(&b.name)))
+    });
+    if query.descending {
+        folders.reverse();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track(path: &str, artist: &str, album: &str, genre: &str, year: i32) -> TrackMetadata {
+        TrackMetadata {
+            id: format!("{:x}", md5::compute(path.as_bytes())),
+            title: Path::new(path).file_stem().unwrap().to_string_lossy().to_string(),
+            artist: artist.to_string(),
+            album: Some(album.to_string()),
+            genre: Some(genre.to_string()),
+            year: Some(year),
+            duration: 100.0,
+            file_path: path.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn library() -> Library {
+        let library = Library::open_in_memory().unwrap();
+        let root = Path::new(MAIN_SEPARATOR_STR).join("music");
+        library.add_folder(&root).unwrap();
+        for (path, artist, album, genre, year) in [
+            ("Кино/Группа крови/Track 9.mp3", "Кино", "Группа крови", "Rock", 1988),
+            ("Кино/Группа крови/Track 10.mp3", "Кино", "Группа крови", "rock", 1988),
+            ("Кино/Звезда/01.mp3", "Кино", "Звезда по имени Солнце", "Rock", 1989),
+            ("ABBA/Arrival/01.mp3", "ABBA", "Arrival", "Pop", 1976),
+            ("loose.mp3", "ABBA", "Singles", "Pop", 1975),
+        ] {
+            let path = root.join(path).to_string_lossy().to_string();
+            library.upsert_track(&track(&path, artist, album, genre, year)).unwrap();
+        }
+        library
+    }
+
+    #[test]
+    fn test_aggregate_views_sort_and_page() {
+        let library = library();
+
+        let artists = library.browse_artists(&BrowseQuery { sort: BrowseSort::TrackCount, descending: true, ..Default::default() }).unwrap();
+        let names: Vec<(&str, u32, u32)> = artists.items.iter().map(|a| (a.name.as_str(), a.album_count, a.track_count)).collect();
+        assert_eq!(names, vec![("Кино", 2, 3), ("ABBA", 2, 2)]);
+
+        let albums = library.browse_albums(&BrowseQuery { sort: BrowseSort::Year, limit: Some(2), offset: Some(1), ..Default::default() }).unwrap();
+        assert_eq!(albums.total, 4);
+        let titles: Vec<&str> = albums.items.iter().map(|a| a.title.as_str()).collect();
+        assert_eq!(titles, vec!["Arrival", "Группа крови"]);
+        assert_eq!((albums.items[1].track_count, albums.items[1].duration), (2, 200.0));
+        let first = library.track(&albums.items[1].cover_track_id).unwrap().unwrap();
+        assert!(first.file_path.ends_with("Track 10.mp3") || first.file_path.ends_with("Track 9.mp3"));
+
+        let genres = library.browse_genres(&BrowseQuery::default()).unwrap();
+        let genres: Vec<(&str, u32)> = genres.items.iter().map(|g| (g.name.as_str(), g.track_count)).collect();
+        assert_eq!(genres, vec![("Pop", 2), ("Rock", 3)]);
+
+        let decades = library.browse_decades(&BrowseQuery { genre: Some("ROCK".into()), ..Default::default() }).unwrap();
+        let decades: Vec<(i32, u32)> = decades.items.iter().map(|d| (d.decade, d.album_count)).collect();
+        assert_eq!(decades, vec![(1980, 2)]);
+
+        let seventies = library.browse_albums(&BrowseQuery { decade: Some(1970), ..Default::default() }).unwrap();
+        assert_eq!(seventies.total, 2);
+    }
+
+    #[test]
+    fn test_folder_tree() {
+        let library = library();
+        let root = Path::new(MAIN_SEPARATOR_STR).join("music").to_string_lossy().to_string();
+
+        let roots = library.browse_folder(None, &BrowseQuery::default()).unwrap();
+        assert!(matches!(&roots.items[..], [FolderEntry::Folder(f)] if f.track_count == 5 && f.name == "music"));
+
+        let listing = library.browse_folder(Some(&root), &BrowseQuery::default()).unwrap();
+        let names: Vec<String> = listing.items.iter().map(|entry| match entry {
+            FolderEntry::Folder(folder) => format!("{}/{}", folder.name, folder.track_count),
+            FolderEntry::Track(track) => track.title.clone(),
+        }).collect();
+        assert_eq!(names, vec!["ABBA/1", "Кино/3", "loose"]);
+
+        // Tracks sort naturally and page after the folders
+        let album = Path::new(&root).join("Кино").join("Группа крови").to_string_lossy().to_string();
+        let tracks = library.browse_folder(Some(&album), &BrowseQuery::default()).unwrap();
+        let titles: Vec<&str> = tracks.items.iter().filter_map(|e| match e {
+            FolderEntry::Track(t) => Some(t.title.as_str()),
+            _ => None,
+        }).collect();
+        assert_eq!(titles, vec!["Track 9", "Track 10"]);
+        let page = library.browse_folder(Some(&root), &BrowseQuery { offset: Some(2), ..Default::default() }).unwrap();
+        assert!(matches!(&page.items[..], [FolderEntry::Track(t)] if t.title == "loose"));
+
+        assert!(matches!(
+            library.browse_folder(Some("/elsewhere"), &BrowseQuery::default()),
+            Err(LibraryError::FolderNotFound(_))
+        ));
+    }
+}
+
+// Tauri Commands
+
+use crate::library::LibraryState;
+use tauri::State;
+
+#[tauri::command]
+pub fn library_browse_artists(query: Option<BrowseQuery>, library: State<'_, LibraryState>) -> Result<Page<ArtistSummary>, String> {
+    let library = library.library.lock()
+        .map_err(|e| format!("Failed to lock library: {}", e))?;
+
+    library.browse_artists(&query.unwrap_or_default())
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn library_browse_albums(query: Option<BrowseQuery>, library: State<'_, LibraryState>) -> Result<Page<AlbumSummary>, String> {
+    let library = library.library.lock()
+        .map_err(|e| format!("Failed to lock library: {}", e))?;
+
+    library.browse_albums(&query.unwrap_or_default())
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn library_browse_genres(query: Option<BrowseQuery>, library: State<'_, LibraryState>) -> Result<Page<GenreSummary>, String> {
+    let library = library.library.lock()
+        .map_err(|e| format!("Failed to lock library: {}", e))?;
+
+    library.browse_genres(&query.unwrap_or_default())
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn library_browse_decades(query: Option<BrowseQuery>, library: State<'_, LibraryState>) -> Result<Page<DecadeSummary>, String> {
+    let library = library.library.lock()
+        .map_err(|e| format!("Failed to lock library: {}", e))?;
+
+    library.browse_decades(&query.unwrap_or_default())
+        .map_err(|e| e.to_string())
+}
+
+/// List a folder of the library; `path` omitted lists the library roots
+#[tauri::command]
+pub fn library_browse_folder(
+    path: Option<String>,
+    query: Option<BrowseQuery>,
+    library: State<'_, LibraryState>,
+) -> Result<Page<FolderEntry>, String> {
+    let library = library.library.lock()
+        .map_err(|e| format!("Failed to lock library: {}", e))?;
+
+    library.browse_folder(path.as_deref(), &query.unwrap_or_default())
+        .map_err(|e| e.to_string())
+}
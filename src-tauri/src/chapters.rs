@@ -0,0 +1,411 @@
+// Chapters Module
+// Reads chapter marks for audiobooks and podcasts: ID3v2 CHAP/CTOC frames, MP4
+// chapters (QuickTime chapter tracks and Nero `chpl`) and Vorbis CHAPTERxxx comments
+
+use crate::file_manager;
+use crate::tag_writer::{parse_atoms, top_level_atoms, Atom};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// Nero chapter start times are in 100 ns units
+const NERO_TIMESCALE: f64 = 10_000_000.0;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Chapter {
+    pub title: String,
+    /// Seconds from the start of the file
+    pub start: f64,
+    pub end: f64,
+}
+
+/// Fill in missing titles and ends (the next chapter's start, or the file's end)
+fn finish(mut marks: Vec<(Option<String>, f64, Option<f64>)>, duration: f64) -> Vec<Chapter> {
+    marks.sort_by(|a, b| a.1.total_cmp(&b.1));
+    let starts: Vec<f64> = marks.iter().map(|(_, start, _)| *start).collect();
+    marks.into_iter()
+        .enumerate()
+        .map(|(index, (title, start, end))| {
+            let next = starts.get(index + 1).copied().unwrap_or(duration);
+            Chapter {
+                title: title.filter(|t| !t.trim().is_empty()).unwrap_or_else(|| format!("Chapter {}", index + 1)),
+                start,
+                end: end.filter(|&end| end > start).unwrap_or(next.max(start)),
+            }
+        })
+        .collect()
+}
+
+/// `HH:MM:SS.mmm` as used by CHAPTERxxx comments
+fn parse_timestamp(value: &str) -> Option<f64> {
+    let mut seconds = 0.0;
+    for part in value.trim().split(':') {
+        let part: f64 = part.parse().ok()?;
+        seconds = seconds * 60.0 + part;
+    }
+    Some(seconds)
+}
+
+/// Chapters from Vorbis comments (`CHAPTER001=00:00:00.000`, `CHAPTER001NAME=Intro`)
+pub fn from_comments<'a>(comments: impl IntoIterator<Item = (&'a str, &'a str)>, duration: f64) -> Vec<Chapter> {
+    let mut marks: Vec<(String, Option<String>, Option<f64>)> = Vec::new();
+    for (key, value) in comments {
+        let key = key.to_ascii_uppercase();
+        let Some(rest) = key.strip_prefix("CHAPTER") else { continue };
+        let digits = rest.chars().take_while(char::is_ascii_digit).count();
+        if digits == 0 {
+            continue;
+        }
+        let (number, field) = rest.split_at(digits);
+        let index = match marks.iter().position(|(n, _, _)| n == number) {
+            Some(index) => index,
+            None => {
+                marks.push((number.to_string(), None, None));
+                marks.len() - 1
+            }
+        };
+        match field {
+            "" => marks[index].2 = parse_timestamp(value),
+            "NAME" => marks[index].1 = Some(value.trim().to_string()),
+            _ => {}
+        }
+    }
+    finish(
+        marks.into_iter()
+            .filter_map(|(_, title, start)| Some((title, start?, None)))
+            .collect(),
+        duration,
+    )
+}
+
+/// CHAP frames, in the order of the top-level CTOC when there is one
+fn id3_chapters(path: &Path, duration: f64) -> Option<Vec<Chapter>> {
+    let tag = id3::Tag::read_from_path(path).ok()?;
+    let mut chapters: Vec<&id3::frame::Chapter> = tag.chapters().collect();
+    if chapters.is_empty() {
+        return None;
+    }
+    if let Some(toc) = tag.tables_of_contents().find(|toc| toc.top_level) {
+        let ordered: Vec<_> = toc.elements.iter()
+            .filter_map(|id| chapters.iter().find(|c| &c.element_id == id).copied())
+            .collect();
+        if !ordered.is_empty() {
+            chapters = ordered;
+        }
+    }
+
+    let marks = chapters.iter()
+        .map(|chapter| {
+            let title = chapter.frames.iter()
+                .find(|frame| frame.id() == "TIT2")
+                .and_then(|frame| frame.content().text())
+                .map(str::to_string);
+            let end = (chapter.end_time != u32::MAX).then(|| chapter.end_time as f64 / 1000.0);
+            (title, chapter.start_time as f64 / 1000.0, end)
+        })
+        .collect();
+    Some(finish(marks, duration))
+}
+
+fn be_u32(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn be_u64(data: &[u8], at: usize) -> Option<u64> {
+    Some(u64::from_be_bytes(data.get(at..at + 8)?.try_into().ok()?))
+}
+
+/// Body of the atom at `path` below `atoms`
+fn find_atom(atoms: &[Atom], path: &[&[u8; 4]]) -> Option<Atom> {
+    let (first, rest) = path.split_first()?;
+    let atom = atoms.iter().find(|a| &a.kind == *first)?;
+    if rest.is_empty() {
+        return Some(atom.clone());
+    }
+    find_atom(&parse_atoms(&atom.body).ok()?, rest)
+}
+
+/// Track ID from a `tkhd` full box; the field sits after 32- or 64-bit times
+fn track_id(trak: &[Atom]) -> Option<u32> {
+    let tkhd = find_atom(trak, &[b"tkhd"])?;
+    be_u32(&tkhd.body, if tkhd.body.first() == Some(&1) { 20 } else { 12 })
+}
+
+/// Nero chapters: `moov > udta > chpl`
+fn nero_chapters(moov: &[Atom], duration: f64) -> Option<Vec<Chapter>> {
+    let chpl = find_atom(moov, &[b"udta", b"chpl"])?.body;
+    let mut at = if chpl.first() == Some(&1) { 8 } else { 4 };
+    let count = *chpl.get(at)?;
+    at += 1;
+    let mut marks = Vec::new();
+    for _ in 0..count {
+        let start = be_u64(&chpl, at)? as f64 / NERO_TIMESCALE;
+        let len = *chpl.get(at + 8)? as usize;
+        let title = String::from_utf8_lossy(chpl.get(at + 9..at + 9 + len)?).to_string();
+        marks.push((Some(title), start, None));
+        at += 9 + len;
+    }
+    (!marks.is_empty()).then(|| finish(marks, duration))
+}
+
+/// QuickTime chapters: a text track referenced from another track's `tref > chap`,
+/// one sample per chapter holding a length-prefixed title
+fn quicktime_chapters(file: &mut File, moov: &[Atom], duration: f64) -> Option<Vec<Chapter>> {
+    let traks: Vec<Vec<Atom>> = moov.iter()
+        .filter(|a| &a.kind == b"trak")
+        .filter_map(|a| parse_atoms(&a.body).ok())
+        .collect();
+    let chapter_ids: Vec<u32> = traks.iter()
+        .filter_map(|trak| find_atom(trak, &[b"tref", b"chap"]))
+        .flat_map(|chap| chap.body.chunks_exact(4).map(|id| u32::from_be_bytes(id.try_into().unwrap())).collect::<Vec<_>>())
+        .collect();
+    let trak = traks.iter().find(|trak| track_id(trak).is_some_and(|id| chapter_ids.contains(&id)))?;
+
+    let mdhd = find_atom(trak, &[b"mdia", b"mdhd"])?.body;
+    let timescale = be_u32(&mdhd, if mdhd.first() == Some(&1) { 20 } else { 12 })?;
+    if timescale == 0 {
+        return None;
+    }
+    let stbl = parse_atoms(&find_atom(trak, &[b"mdia", b"minf", b"stbl"])?.body).ok()?;
+
+    // Sample sizes. Counts come from the file, so they are capped by what the
+    // atom (or, for a fixed size, the file) can actually hold.
+    let file_len = file.metadata().ok()?.len();
+    let stsz = find_atom(&stbl, &[b"stsz"])?.body;
+    let fixed_size = be_u32(&stsz, 4)?;
+    let capacity = match fixed_size {
+        0 => stsz.len().saturating_sub(12) / 4,
+        size => usize::try_from(file_len / u64::from(size)).unwrap_or(usize::MAX),
+    };
+    let sizes: Vec<u32> = (0..(be_u32(&stsz, 8)? as usize).min(capacity))
+        .map(|index| if fixed_size != 0 { Some(fixed_size) } else { be_u32(&stsz, 12 + index * 4) })
+        .collect::<Option<_>>()?;
+
+    // Sample start times, for no more samples than there are sizes
+    let stts = find_atom(&stbl, &[b"stts"])?.body;
+    let entries = (be_u32(&stts, 4)? as usize).min(stts.len().saturating_sub(8) / 8);
+    let mut times = Vec::with_capacity(sizes.len());
+    let mut time = 0u64;
+    for entry in 0..entries {
+        let count = (be_u32(&stts, 8 + entry * 8)? as usize).min(sizes.len() - times.len());
+        let delta = be_u32(&stts, 12 + entry * 8)?;
+        for _ in 0..count {
+            times.push(time as f64 / timescale as f64);
+            time += u64::from(delta);
+        }
+    }
+
+    // Sample offsets: chunks hold runs of samples as described by stsc
+    let chunk_offsets: Vec<u64> = if let Some(stco) = find_atom(&stbl, &[b"stco"]) {
+        (0..be_u32(&stco.body, 4)? as usize).map(|i| be_u32(&stco.body, 8 + i * 4).map(u64::from)).collect::<Option<_>>()?
+    } else {
+        let co64 = find_atom(&stbl, &[b"co64"])?.body;
+        (0..be_u32(&co64, 4)? as usize).map(|i| be_u64(&co64, 8 + i * 8)).collect::<Option<_>>()?
+    };
+    let stsc = find_atom(&stbl, &[b"stsc"])?.body;
+    let runs: Vec<(u32, u32)> = (0..be_u32(&stsc, 4)? as usize)
+        .map(|i| Some((be_u32(&stsc, 8 + i * 12)?, be_u32(&stsc, 12 + i * 12)?)))
+        .collect::<Option<_>>()?;
+    let mut offsets = Vec::new();
+    for (chunk, &chunk_offset) in chunk_offsets.iter().enumerate() {
+        let per_chunk = runs.iter().rev().find(|(first, _)| *first as usize <= chunk + 1)?.1;
+        let mut offset = chunk_offset;
+        for _ in 0..per_chunk {
+            let Some(&size) = sizes.get(offsets.len()) else { break };
+            offsets.push(offset);
+            offset += u64::from(size);
+        }
+    }
+
+    let mut marks = Vec::new();
+    for ((&start, &offset), &size) in times.iter().zip(&offsets).zip(&sizes) {
+        if offset.saturating_add(u64::from(size)) > file_len {
+            return None;
+        }
+        let mut sample = vec![0u8; size as usize];
+        file.seek(SeekFrom::Start(offset)).ok()?;
+        file.read_exact(&mut sample).ok()?;
+        let len = u16::from_be_bytes([*sample.first()?, *sample.get(1)?]) as usize;
+        let text = sample.get(2..2 + len)?;
+        let title = match text.strip_prefix(b"\xFE\xFF") {
+            Some(utf16) => String::from_utf16_lossy(
+                &utf16.chunks_exact(2).map(|c| u16::from_be_bytes([c[0], c[1]])).collect::<Vec<_>>(),
+            ),
+            None => String::from_utf8_lossy(text).to_string(),
+        };
+        marks.push((Some(title), start, None));
+    }
+    (!marks.is_empty()).then(|| finish(marks, duration))
+}
+
+fn mp4_chapters(path: &Path, duration: f64) -> Option<Vec<Chapter>> {
+    let mut file = File::open(path).ok()?;
+    let &(_, offset, size) = top_level_atoms(&mut file).ok()?.iter().find(|(kind, _, _)| kind == b"moov")?;
+    let mut moov = vec![0u8; size as usize];
+    file.seek(SeekFrom::Start(offset)).ok()?;
+    file.read_exact(&mut moov).ok()?;
+    let moov = parse_atoms(&parse_atoms(&moov).ok()?.pop()?.body).ok()?;
+
+    quicktime_chapters(&mut file, &moov, duration)
+        .or_else(|| nero_chapters(&moov, duration))
+}
+
+/// Chapters kept in the container rather than in tags symphonia reports
+pub fn read_container(path: &Path, duration: f64) -> Vec<Chapter> {
+    let mut header = [0u8; 8];
+    let read = File::open(path).and_then(|mut file| file.read_exact(&mut header));
+    if read.is_err() {
+        return Vec::new();
+    }
+    let chapters = if header.starts_with(b"ID3") {
+        id3_chapters(path, duration)
+    } else if &header[4..8] == b"ftyp" {
+        mp4_chapters(path, duration)
+    } else {
+        None
+    };
+    chapters.unwrap_or_default()
+}
+
+/// Vorbis comment chapters of an Ogg or FLAC file
+fn comment_chapters(path: &Path, duration: f64) -> Vec<Chapter> {
+    let comments: Vec<(String, String)> = file_manager::read_tags(path)
+        .into_iter()
+        .map(|tag| (tag.key, tag.value.to_string()))
+        .collect();
+    from_comments(comments.iter().map(|(k, v)| (k.as_str(), v.as_str())), duration)
+}
+
+/// Chapters of the file at `path`, from whichever source it has
+pub fn read(path: &Path, duration: f64) -> Vec<Chapter> {
+    let chapters = read_container(path, duration);
+    if !chapters.is_empty() {
+        return chapters;
+    }
+    comment_chapters(path, duration)
+}
+
+/// Index of the chapter playing at `position`
+pub fn index_at(chapters: &[Chapter], position: f64) -> Option<usize> {
+    chapters.iter().rposition(|chapter| chapter.start <= position + 1e-6)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::temp_dir;
+
+    fn atom(kind: &[u8; 4], body: Vec<u8>) -> Vec<u8> {
+        Atom::new(kind, body).to_bytes()
+    }
+
+    #[test]
+    fn test_vorbis_comment_chapters() {
+        let comments = [
+            ("CHAPTER002", "00:01:30.500"),
+            ("CHAPTER001", "00:00:00.000"),
+            ("CHAPTER001NAME", "Вступление"),
+            ("chapter002name", "Part One"),
+            ("CHAPTER003NAME", "No start"),
+            ("TITLE", "Book"),
+        ];
+        let chapters = from_comments(comments, 600.0);
+        assert_eq!(chapters, vec![
+            Chapter { title: "Вступление".into(), start: 0.0, end: 90.5 },
+            Chapter { title: "Part One".into(), start: 90.5, end: 600.0 },
+        ]);
+        assert_eq!(index_at(&chapters, 90.0), Some(0));
+        assert_eq!(index_at(&chapters, 90.5), Some(1));
+        assert_eq!(parse_timestamp("1:02:03.25"), Some(3723.25));
+    }
+
+    #[test]
+    fn test_id3_chapters_follow_table_of_contents() {
+        use id3::frame::{Chapter as Chap, Content, Frame, TableOfContents};
+        use id3::{Tag, TagLike, Version};
+
+        let mut tag = Tag::new();
+        for (id, start, end, title) in [("b", 60_000, 120_000, "Second"), ("a", 0, 60_000, "First"), ("x", 0, 5_000, "Ad")] {
+            tag.add_frame(Chap {
+                element_id: id.into(),
+                start_time: start,
+                end_time: end,
+                start_offset: u32::MAX,
+                end_offset: u32::MAX,
+                frames: vec![Frame::with_content("TIT2", Content::Text(title.into()))],
+            });
+        }
+        tag.add_frame(TableOfContents {
+            element_id: "toc".into(),
+            top_level: true,
+            ordered: true,
+            elements: vec!["a".into(), "b".into()],
+            frames: Vec::new(),
+        });
+        let path = temp_dir("chapters-id3").join("book.mp3");
+        std::fs::write(&path, [0xFF, 0xFB, 0x90, 0x00]).unwrap();
+        tag.write_to_path(&path, Version::Id3v24).unwrap();
+
+        let chapters = read(&path, 130.0);
+        let titles: Vec<&str> = chapters.iter().map(|c| c.title.as_str()).collect();
+        assert_eq!(titles, vec!["First", "Second"]);
+        assert_eq!((chapters[1].start, chapters[1].end), (60.0, 120.0));
+    }
+
+    #[test]
+    fn test_mp4_chapter_track_and_nero_chapters() {
+        let full = |version: u8, rest: &[u8]| [&[version, 0, 0, 0][..], rest].concat();
+        let u32s = |values: &[u32]| values.iter().flat_map(|v| v.to_be_bytes()).collect::<Vec<u8>>();
+
+        // Text samples live in mdat right after ftyp
+        let ftyp = atom(b"ftyp", b"M4B \0\0\0\0".to_vec());
+        let samples: Vec<Vec<u8>> = ["Opening", "Middle"].iter()
+            .map(|t| [&(t.len() as u16).to_be_bytes()[..], t.as_bytes()].concat())
+            .collect();
+        let mdat = atom(b"mdat", samples.concat());
+        let first_sample = (ftyp.len() + 8) as u32;
+
+        let audio_trak = atom(b"trak", [
+            atom(b"tkhd", full(0, &u32s(&[0, 0, 1]))),
+            atom(b"tref", atom(b"chap", u32s(&[2]))),
+        ].concat());
+        let text_trak = |stts: &[u32]| atom(b"trak", [
+            atom(b"tkhd", full(0, &u32s(&[0, 0, 2]))),
+            atom(b"mdia", [
+                atom(b"mdhd", full(0, &u32s(&[0, 0, 100, 1500]))),
+                atom(b"minf", atom(b"stbl", [
+                    atom(b"stts", full(0, &u32s(stts))),
+                    atom(b"stsz", full(0, &u32s(&[0, 2, samples[0].len() as u32, samples[1].len() as u32]))),
+                    atom(b"stsc", full(0, &u32s(&[1, 1, 2, 1]))),
+                    atom(b"stco", full(0, &u32s(&[1, first_sample]))),
+                ].concat())),
+            ].concat()),
+        ].concat());
+        let mut chpl = vec![1, 0, 0, 0, 0, 0, 0, 0, 1];
+        chpl.extend_from_slice(&50_000_000u64.to_be_bytes());
+        chpl.push(4);
+        chpl.extend_from_slice(b"Nero");
+        let udta = atom(b"udta", atom(b"chpl", chpl));
+
+        let dir = temp_dir("chapters-mp4");
+        let path = dir.join("book.m4b");
+        let expected = vec![
+            Chapter { title: "Opening".into(), start: 0.0, end: 10.0 },
+            Chapter { title: "Middle".into(), start: 10.0, end: 15.0 },
+        ];
+        let moov = atom(b"moov", [audio_trak.clone(), text_trak(&[2, 1, 1000, 1, 500]), udta.clone()].concat());
+        std::fs::write(&path, [ftyp.clone(), mdat.clone(), moov].concat()).unwrap();
+        assert_eq!(read(&path, 15.0), expected);
+
+        // Counts beyond what the atoms hold are capped rather than trusted
+        let moov = atom(b"moov", [audio_trak.clone(), text_trak(&[u32::MAX, u32::MAX, 1000]), udta.clone()].concat());
+        std::fs::write(&path, [ftyp.clone(), mdat.clone(), moov].concat()).unwrap();
+        assert_eq!(read(&path, 15.0), expected);
+
+        // Without a chapter track the Nero list is used
+        let moov = atom(b"moov", [audio_trak, udta].concat());
+        std::fs::write(&path, [ftyp, mdat, moov].concat()).unwrap();
+        assert_eq!(read(&path, 15.0), vec![Chapter { title: "Nero".into(), start: 5.0, end: 15.0 }]);
+    }
+}
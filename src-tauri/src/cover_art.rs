@@ -0,0 +1,336 @@
+// Cover Art Module
+// Finds embedded or folder album art and serves resized thumbnails from a disk cache
+
+use crate::errors::CoverArtError;
+use crate::library::FileStamp;
+use base64::Engine;
+use image::codecs::jpeg::JpegEncoder;
+use image::DynamicImage;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::{MetadataOptions, MetadataRevision, StandardVisualKey, Visual};
+use symphonia::core::probe::Hint;
+
+pub const DEFAULT_THUMBNAIL_SIZE: u32 = 300;
+const MAX_THUMBNAIL_SIZE: u32 = 2048;
+const JPEG_QUALITY: u8 = 85;
+
+/// Folder image names in order of preference, matched case-insensitively
+const FOLDER_IMAGE_NAMES: &[&str] = &["cover", "folder", "front", "album", "albumart"];
+const FOLDER_IMAGE_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "webp"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CoverSource {
+    Embedded,
+    Folder,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CoverArt {
+    /// md5 of the original image bytes, shared by every track using the same picture
+    pub hash: String,
+    pub source: CoverSource,
+    pub width: u32,
+    pub height: u32,
+    /// Cached JPEG thumbnail on disk
+    pub path: String,
+    /// The same thumbnail as a `data:image/jpeg;base64,...` URL for direct use in `<img>`
+    pub data_url: String,
+}
+
+/// Pick the front cover from a metadata revision, or any picture if none is marked
+fn best_visual(revision: &MetadataRevision) -> Option<&Visual> {
+    let visuals = revision.visuals();
+    visuals
+        .iter()
+        .find(|v| v.usage == Some(StandardVisualKey::FrontCover))
+        .or_else(|| visuals.first())
+}
+
+/// Picture embedded in the file (ID3 APIC, FLAC PICTURE, MP4 covr, Vorbis METADATA_BLOCK_PICTURE)
+pub fn embedded_picture(file_path: &Path) -> Option<Vec<u8>> {
+    let file = File::open(file_path).ok()?;
+    let mss = MediaSourceStream::new(Box::new(file), Default::default());
+
+    let mut hint = Hint::new();
+    if let Some(ext_str) = file_path.extension().and_then(|e| e.to_str()) {
+        hint.with_extension(ext_str);
+    }
+
+    let mut probed = symphonia::default::get_probe()
+        .format(&hint, mss, &FormatOptions::default(), &MetadataOptions::default())
+        .ok()?;
+
+    if let Some(visual) = probed.format.metadata().current().and_then(best_visual) {
+        return Some(visual.data.to_vec());
+    }
+    let metadata = probed.metadata.get()?;
+    let visual = metadata.current().and_then(best_visual)?;
+    Some(visual.data.to_vec())
+}
+
+/// `cover.jpg`, `Folder.png` and friends next to the track
+pub fn folder_picture(dir: &Path) -> Option<PathBuf> {
+    let mut candidates: Vec<(usize, usize, PathBuf)> = std::fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter_map(|path| {
+            let stem = path.file_stem()?.to_str()?.to_lowercase();
+            let ext = path.extension()?.to_str()?.to_lowercase();
+            let name_rank = FOLDER_IMAGE_NAMES.iter().position(|n| *n == stem)?;
+            let ext_rank = FOLDER_IMAGE_EXTENSIONS.iter().position(|e| *e == ext)?;
+            Some((name_rank, ext_rank, path))
+        })
+        .collect();
+
+    candidates.sort();
+    candidates.into_iter().next().map(|(_, _, path)| path)
+}
+
+/// The folder image a track would use, with its stamp so a replaced image is noticed
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct FolderImage {
+    path: PathBuf,
+    stamp: FileStamp,
+}
+
+impl FolderImage {
+    fn next_to(file_path: &Path) -> Option<Self> {
+        let path = folder_picture(file_path.parent()?)?;
+        let stamp = FileStamp::of(&path).ok()?;
+        Some(Self { path, stamp })
+    }
+}
+
+/// Thumbnails on disk, named `<content hash>_<size>.jpg`
+#[derive(Debug, Clone)]
+pub struct CoverCache {
+    dir: PathBuf,
+}
+
+impl CoverCache {
+    pub fn new(dir: &Path) -> Self {
+        Self { dir: dir.to_path_buf() }
+    }
+
+    fn thumbnail_path(&self, hash: &str, size: u32) -> PathBuf {
+        self.dir.join(format!("{}_{}.jpg", hash, size))
+    }
+
+    /// Cached thumbnail for a picture already seen, without decoding anything
+    pub fn cached(&self, hash: &str, source: CoverSource, size: u32) -> Option<CoverArt> {
+        let path = self.thumbnail_path(hash, size);
+        let bytes = std::fs::read(&path).ok()?;
+        let (width, height) = image::image_dimensions(&path).ok()?;
+        Some(cover_art(hash, source, width, height, &path, &bytes))
+    }
+
+    /// Resize `picture` to fit in `size`x`size` and store it, unless already cached
+    pub fn thumbnail(&self, picture: &[u8], source: CoverSource, size: u32) -> Result<CoverArt, CoverArtError> {
+        let hash = format!("{:x}", md5::compute(picture));
+        if let Some(cover) = self.cached(&hash, source, size) {
+            return Ok(cover);
+        }
+
+        let image = image::load_from_memory(picture)
+            .map_err(|e| CoverArtError::ImageError(e.to_string()))?;
+        let image = if image.width() > size || image.height() > size {
+            image.thumbnail(size, size)
+        } else {
+            image
+        };
+        // JPEG has no alpha channel
+        let image = DynamicImage::ImageRgb8(image.to_rgb8());
+
+        let mut bytes = Vec::new();
+        image.write_with_encoder(JpegEncoder::new_with_quality(&mut bytes, JPEG_QUALITY))
+            .map_err(|e| CoverArtError::ImageError(e.to_string()))?;
+
+        std::fs::create_dir_all(&self.dir)?;
+        let path = self.thumbnail_path(&hash, size);
+        let temp_path = path.with_extension("jpg.tmp");
+        std::fs::write(&temp_path, &bytes)?;
+        std::fs::rename(&temp_path, &path)?;
+
+        Ok(cover_art(&hash, source, image.width(), image.height(), &path, &bytes))
+    }
+}
+
+fn cover_art(hash: &str, source: CoverSource, width: u32, height: u32, path: &Path, bytes: &[u8]) -> CoverArt {
+    CoverArt {
+        hash: hash.to_string(),
+        source,
+        width,
+        height,
+        path: path.to_string_lossy().to_string(),
+        data_url: format!(
+            "data:image/jpeg;base64,{}",
+            base64::engine::general_purpose::STANDARD.encode(bytes)
+        ),
+    }
+}
+
+/// Which picture a track resolved to, so repeated requests skip probing the file.
+/// Only found covers are kept: a missing one may turn up as a folder image.
+#[derive(Debug, Clone)]
+struct ResolvedCover {
+    stamp: FileStamp,
+    /// Set for folder art; embedded art wins over any folder image
+    folder_image: Option<FolderImage>,
+    hash: String,
+    source: CoverSource,
+}
+
+pub struct CoverArtService {
+    cache: CoverCache,
+    resolved: Mutex<HashMap<String, ResolvedCover>>,
+}
+
+impl CoverArtService {
+    pub fn new(cache_dir: &Path) -> Self {
+        Self {
+            cache: CoverCache::new(cache_dir),
+            resolved: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Thumbnail for the track at `file_path`, or `None` if it has no art
+    pub fn cover_for(&self, track_id: &str, file_path: &Path, size: u32) -> Result<Option<CoverArt>, CoverArtError> {
+        let size = size.clamp(16, MAX_THUMBNAIL_SIZE);
+        let stamp = FileStamp::of(file_path)?;
+
+        let known = self.resolved.lock()
+            .ok()
+            .and_then(|resolved| resolved.get(track_id).cloned())
+            .filter(|resolved| resolved.stamp == stamp)
+            // A folder image may have been replaced, or a preferred one added
+            .filter(|resolved| resolved.folder_image.is_none() || resolved.folder_image == FolderImage::next_to(file_path));
+        if let Some(known) = known {
+            if let Some(cover) = self.cache.cached(&known.hash, known.source, size) {
+                return Ok(Some(cover));
+            }
+        }
+
+        // Embedded art first, then a picture in the folder
+        let (picture, source, folder_image) = match embedded_picture(file_path) {
+            Some(picture) => (picture, CoverSource::Embedded, None),
+            None => {
+                let Some(image) = FolderImage::next_to(file_path) else { return Ok(None) };
+                let Ok(picture) = std::fs::read(&image.path) else { return Ok(None) };
+                (picture, CoverSource::Folder, Some(image))
+            }
+        };
+        let cover = self.cache.thumbnail(&picture, source, size)?;
+
+        if let Ok(mut resolved) = self.resolved.lock() {
+            resolved.insert(track_id.to_string(), ResolvedCover {
+                stamp,
+                folder_image,
+                hash: cover.hash.clone(),
+                source,
+            });
+        }
+
+        Ok(Some(cover))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{sine_wav, temp_dir};
+    use image::{ImageFormat, Rgba, RgbaImage};
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let image = RgbaImage::from_pixel(width, height, Rgba([200, 30, 30, 128]));
+        let mut bytes = std::io::Cursor::new(Vec::new());
+        image.write_to(&mut bytes, ImageFormat::Png).unwrap();
+        bytes.into_inner()
+    }
+
+    #[test]
+    fn test_folder_picture_preference() {
+        let dir = temp_dir("cover-folder");
+        assert!(folder_picture(&dir).is_none());
+
+        std::fs::write(dir.join("back.jpg"), b"x").unwrap();
+        std::fs::write(dir.join("Folder.PNG"), b"x").unwrap();
+        assert_eq!(folder_picture(&dir).unwrap(), dir.join("Folder.PNG"));
+
+        std::fs::write(dir.join("cover.webp"), b"x").unwrap();
+        std::fs::write(dir.join("Cover.jpg"), b"x").unwrap();
+        assert_eq!(folder_picture(&dir).unwrap(), dir.join("Cover.jpg"));
+    }
+
+    #[test]
+    fn test_thumbnails_are_resized_and_cached() {
+        let dir = temp_dir("cover-cache");
+        let music = dir.join("album");
+        std::fs::create_dir_all(&music).unwrap();
+        let track = music.join("01.wav");
+        std::fs::write(&track, sine_wav(0.1, 8000, 440.0)).unwrap();
+
+        let service = CoverArtService::new(&dir.join("covers"));
+        assert!(service.cover_for("t1", &track, 100).unwrap().is_none());
+
+        // A folder image added later is picked up although the track is unchanged
+        std::fs::write(music.join("folder.png"), png(400, 200)).unwrap();
+        let cover = service.cover_for("t1", &track, 100).unwrap().unwrap();
+        assert_eq!(cover.source, CoverSource::Folder);
+        assert_eq!((cover.width, cover.height), (100, 50));
+        assert!(cover.data_url.starts_with("data:image/jpeg;base64,"));
+        assert!(Path::new(&cover.path).exists());
+
+        let again = service.cover_for("t1", &track, 100).unwrap().unwrap();
+        assert_eq!(again.path, cover.path);
+
+        // Small pictures are not upscaled
+        let large = service.cover_for("t1", &track, 1000).unwrap().unwrap();
+        assert_eq!((large.width, large.height), (400, 200));
+        assert_ne!(large.path, cover.path);
+
+        // So is a preferred image next to the one in use
+        std::fs::write(music.join("cover.png"), png(200, 400)).unwrap();
+        let replaced = service.cover_for("t1", &track, 100).unwrap().unwrap();
+        assert_eq!((replaced.width, replaced.height), (50, 100));
+    }
+}
+
+// Tauri Commands
+
+use crate::cue;
+use crate::library::LibraryState;
+use tauri::State;
+
+pub struct CoverArtState {
+    pub service: Arc<CoverArtService>,
+}
+
+#[tauri::command]
+pub async fn get_cover_art(
+    track_id: String,
+    size: Option<u32>,
+    library: State<'_, LibraryState>,
+    covers: State<'_, CoverArtState>,
+) -> Result<Option<CoverArt>, String> {
+    let track = library.library.lock()
+        .map_err(|e| format!("Failed to lock library: {}", e))?
+        .track(&track_id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| CoverArtError::TrackNotFound(track_id.clone()).to_string())?;
+
+    let service = covers.service.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        service.cover_for(&track_id, &cue::audio_path(&track.file_path), size.unwrap_or(DEFAULT_THUMBNAIL_SIZE))
+            .map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| format!("Cover art task failed: {}", e))?
+}
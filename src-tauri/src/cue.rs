@@ -0,0 +1,593 @@
+// CUE Module
+// Parses CUE sheets (sidecar files and FLAC-embedded) and maps their tracks to
+// virtual tracks: spans of a single audio file addressed as `<file>#cue:<number>`
+
+use crate::file_manager::TrackMetadata;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+/// Separator between the audio file and the track number in a virtual track's path
+pub const VIRTUAL_MARKER: &str = "#cue:";
+/// CUE times are minutes:seconds:frames, with 75 CD frames per second
+const FRAMES_PER_SECOND: f64 = 75.0;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CueTrack {
+    pub number: u32,
+    pub title: Option<String>,
+    pub performer: Option<String>,
+    pub songwriter: Option<String>,
+    pub isrc: Option<String>,
+    /// INDEX 01 (or INDEX 00 when there is no 01), in seconds into the file
+    pub start: f64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CueFile {
+    pub name: String,
+    pub tracks: Vec<CueTrack>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CueSheet {
+    pub title: Option<String>,
+    pub performer: Option<String>,
+    pub genre: Option<String>,
+    pub date: Option<String>,
+    pub files: Vec<CueFile>,
+}
+
+/// One track's span of its audio file, with the sheet-level tags it inherits
+#[derive(Debug, Clone, PartialEq)]
+pub struct CueSegment {
+    pub track: CueTrack,
+    /// Start of the next track in the same file; `None` runs to the end
+    pub end: Option<f64>,
+    pub track_total: u32,
+    pub album: Option<String>,
+    pub album_artist: Option<String>,
+    pub genre: Option<String>,
+    pub date: Option<String>,
+}
+
+/// CUE sheets, playlists and lyrics are UTF-8 when written by current tools;
+/// older EAC rips and players of Russian releases wrote Windows-1251
+pub fn decode_text(bytes: &[u8]) -> String {
+    let bytes = bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(bytes);
+    match std::str::from_utf8(bytes) {
+        Ok(text) => text.to_string(),
+        Err(_) => bytes.iter().map(|&b| crate::radio::decode_cp1251(b)).collect(),
+    }
+}
+
+/// Split a command line into words, keeping "quoted strings" together
+fn words(line: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut rest = line.trim();
+    while !rest.is_empty() {
+        if let Some(quoted) = rest.strip_prefix('"') {
+            let end = quoted.find('"').unwrap_or(quoted.len());
+            words.push(quoted[..end].to_string());
+            rest = quoted.get(end + 1..).unwrap_or("").trim_start();
+        } else {
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            words.push(rest[..end].to_string());
+            rest = rest[end..].trim_start();
+        }
+    }
+    words
+}
+
+fn parse_time(value: &str) -> Option<f64> {
+    let mut parts = value.split(':').map(|part| part.trim().parse::<u32>().ok());
+    let (minutes, seconds, frames) = (parts.next()??, parts.next()??, parts.next()??);
+    Some(f64::from(minutes) * 60.0 + f64::from(seconds) + f64::from(frames) / FRAMES_PER_SECOND)
+}
+
+/// Parse CUE sheet text; unknown commands are ignored
+pub fn parse(text: &str) -> CueSheet {
+    let mut sheet = CueSheet::default();
+    let mut track: Option<CueTrack> = None;
+    let mut pregap: Option<f64> = None;
+    let mut has_index_01 = false;
+    // Skipped data tracks still end the sheet-level header
+    let mut seen_track = false;
+
+    // Finishes the track being read, if it had any index at all
+    let finish = |sheet: &mut CueSheet, track: &mut Option<CueTrack>, pregap: &mut Option<f64>, has_index_01: bool| {
+        if let Some(mut done) = track.take() {
+            if !has_index_01 {
+                match pregap.take() {
+                    Some(start) => done.start = start,
+                    None => return,
+                }
+            }
+            if let Some(file) = sheet.files.last_mut() {
+                file.tracks.push(done);
+            }
+        }
+        *pregap = None;
+    };
+
+    for line in text.lines() {
+        let words = words(line);
+        let Some(command) = words.first().map(|w| w.to_uppercase()) else { continue };
+        let arg = |i: usize| words.get(i).cloned().filter(|w| !w.is_empty());
+
+        match command.as_str() {
+            "FILE" => {
+                finish(&mut sheet, &mut track, &mut pregap, has_index_01);
+                sheet.files.push(CueFile { name: arg(1).unwrap_or_default(), tracks: Vec::new() });
+            }
+            "TRACK" => {
+                finish(&mut sheet, &mut track, &mut pregap, has_index_01);
+                has_index_01 = false;
+                seen_track = true;
+                // Data tracks on enhanced CDs have no audio to play
+                let is_audio = arg(2).map_or(true, |kind| kind.eq_ignore_ascii_case("AUDIO"));
+                if let (Some(number), true) = (arg(1).and_then(|n| n.parse().ok()), is_audio) {
+                    track = Some(CueTrack { number, ..Default::default() });
+                }
+            }
+            "INDEX" => {
+                let Some(current) = track.as_mut() else { continue };
+                let (Some(index), Some(time)) = (arg(1), arg(2).as_deref().and_then(parse_time)) else { continue };
+                match index.parse::<u32>() {
+                    Ok(0) => pregap = Some(time),
+                    Ok(1) => {
+                        current.start = time;
+                        has_index_01 = true;
+                    }
+                    _ => {}
+                }
+            }
+            "TITLE" | "PERFORMER" | "SONGWRITER" | "ISRC" => {
+                let value = arg(1);
+                match (track.as_mut(), command.as_str()) {
+                    (Some(t), "TITLE") => t.title = value,
+                    (Some(t), "PERFORMER") => t.performer = value,
+                    (Some(t), "SONGWRITER") => t.songwriter = value,
+                    (Some(t), "ISRC") => t.isrc = value,
+                    (None, "TITLE") if !seen_track => sheet.title = value,
+                    (None, "PERFORMER") if !seen_track => sheet.performer = value,
+                    _ => {}
+                }
+            }
+            "REM" => match arg(1).map(|key| key.to_uppercase()).as_deref() {
+                Some("GENRE") if !seen_track => sheet.genre = arg(2),
+                Some("DATE") if !seen_track => sheet.date = arg(2),
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+    finish(&mut sheet, &mut track, &mut pregap, has_index_01);
+    sheet
+}
+
+/// Segments for the tracks of one FILE entry, each ending where the next begins
+pub fn segments(sheet: &CueSheet, tracks: &[CueTrack]) -> Vec<CueSegment> {
+    let mut tracks = tracks.to_vec();
+    tracks.sort_by(|a, b| a.start.total_cmp(&b.start));
+    let total = tracks.len() as u32;
+
+    let ends: Vec<Option<f64>> = tracks.iter().skip(1).map(|next| Some(next.start)).chain([None]).collect();
+    tracks.into_iter()
+        .zip(ends)
+        .map(|(track, end)| CueSegment {
+            track,
+            end,
+            track_total: total,
+            album: sheet.title.clone(),
+            album_artist: sheet.performer.clone(),
+            genre: sheet.genre.clone(),
+            date: sheet.date.clone(),
+        })
+        .collect()
+}
+
+/// The audio file a FILE entry names. Sheets often outlive a conversion
+/// (`album.wav` became `album.flac`), so a file with the same stem will do.
+pub fn resolve_file(cue_dir: &Path, name: &str) -> Option<PathBuf> {
+    let named = cue_dir.join(name.replace('\\', "/"));
+    if named.is_file() {
+        return Some(named);
+    }
+
+    let stem = named.file_stem()?.to_str()?.to_lowercase();
+    let mut candidates: Vec<PathBuf> = std::fs::read_dir(named.parent()?).ok()?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| {
+            path.is_file()
+                && path.file_stem().and_then(|s| s.to_str()).is_some_and(|s| s.to_lowercase() == stem)
+                && !path.extension().and_then(|e| e.to_str()).is_some_and(|e| e.eq_ignore_ascii_case("cue"))
+        })
+        .collect();
+    candidates.sort();
+    candidates.into_iter().next()
+}
+
+fn read_sheet(cue_path: &Path) -> Option<CueSheet> {
+    let bytes = std::fs::read(cue_path).ok()?;
+    Some(parse(&decode_text(&bytes)))
+}
+
+/// Every audio file the sheet at `cue_path` splits into two or more tracks
+pub fn sidecar_sheet_segments(cue_path: &Path) -> Vec<(PathBuf, Vec<CueSegment>)> {
+    let Some(sheet) = read_sheet(cue_path) else { return Vec::new() };
+    let cue_dir = cue_path.parent().unwrap_or(Path::new(""));
+
+    sheet.files.iter()
+        .filter(|file| file.tracks.len() > 1)
+        .filter_map(|file| Some((resolve_file(cue_dir, &file.name)?, segments(&sheet, &file.tracks))))
+        .collect()
+}
+
+/// Whether `path` names a CUE sheet
+pub fn is_sheet(path: &Path) -> bool {
+    path.extension().and_then(|e| e.to_str()).is_some_and(|e| e.eq_ignore_ascii_case("cue"))
+}
+
+/// CUE sheets directly inside `dir`
+pub fn sheets_in(dir: &Path) -> Vec<PathBuf> {
+    std::fs::read_dir(dir)
+        .map(|entries| entries
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| is_sheet(path))
+            .collect())
+        .unwrap_or_default()
+}
+
+/// Segments from a `.cue` next to `audio_path` that names it; a sheet with
+/// the audio file's own name is tried first
+fn sidecar_segments(audio_path: &Path) -> Option<Vec<CueSegment>> {
+    let mut cue_files = sheets_in(audio_path.parent()?);
+    let stem = audio_path.file_stem();
+    cue_files.sort_by_key(|path| (path.file_stem() != stem, path.clone()));
+
+    cue_files.iter()
+        .flat_map(|cue_path| sidecar_sheet_segments(cue_path))
+        .find(|(path, _)| path == audio_path)
+        .map(|(_, segments)| segments)
+}
+
+/// CUE sheet stored inside a FLAC file: a CUESHEET Vorbis comment (a text
+/// sheet, with titles) or else the binary CUESHEET metadata block
+pub fn embedded_segments(audio_path: &Path) -> Option<Vec<CueSegment>> {
+    let is_flac = audio_path.extension().and_then(|e| e.to_str()).is_some_and(|e| e.eq_ignore_ascii_case("flac"));
+    if !is_flac {
+        return None;
+    }
+
+    let blocks = read_flac_metadata(audio_path)?;
+    let sheet = match (blocks.cuesheet_comment, blocks.cuesheet_block) {
+        (Some(text), _) => {
+            let mut sheet = parse(&text);
+            // An embedded sheet describes its own file, whatever FILE says
+            let tracks = sheet.files.drain(..).flat_map(|file| file.tracks).collect();
+            sheet.files.push(CueFile { name: String::new(), tracks });
+            sheet
+        }
+        (None, Some(tracks)) => CueSheet {
+            files: vec![CueFile { name: String::new(), tracks }],
+            ..Default::default()
+        },
+        (None, None) => return None,
+    };
+
+    let tracks = &sheet.files.first()?.tracks;
+    (tracks.len() > 1).then(|| segments(&sheet, tracks))
+}
+
+/// Tracks the file is split into, from a sidecar sheet or an embedded one;
+/// empty for an ordinary single-track file
+pub fn segments_for(audio_path: &Path) -> Vec<CueSegment> {
+    sidecar_segments(audio_path)
+        .or_else(|| embedded_segments(audio_path))
+        .unwrap_or_default()
+}
+
+#[derive(Default)]
+struct FlacCueBlocks {
+    cuesheet_comment: Option<String>,
+    cuesheet_block: Option<Vec<CueTrack>>,
+}
+
+/// Read only the metadata blocks at the front of a FLAC file
+fn read_flac_metadata(path: &Path) -> Option<FlacCueBlocks> {
+    let mut file = File::open(path).ok()?;
+    let mut magic = [0u8; 4];
+    file.read_exact(&mut magic).ok()?;
+    if &magic[..3] == b"ID3" {
+        // Some taggers put ID3v2 in front of FLAC; its size is syncsafe
+        let mut header = [0u8; 6];
+        file.read_exact(&mut header).ok()?;
+        let size = header[2..6].iter().fold(0u64, |acc, &b| (acc << 7) | u64::from(b & 0x7F));
+        file.seek(SeekFrom::Current(size as i64)).ok()?;
+        file.read_exact(&mut magic).ok()?;
+    }
+    if &magic != b"fLaC" {
+        return None;
+    }
+
+    let mut blocks = FlacCueBlocks::default();
+    let mut sample_rate = 0u32;
+    loop {
+        let mut header = [0u8; 4];
+        file.read_exact(&mut header).ok()?;
+        let is_last = header[0] & 0x80 != 0;
+        let kind = header[0] & 0x7F;
+        let length = u32::from_be_bytes([0, header[1], header[2], header[3]]) as usize;
+
+        match kind {
+            // STREAMINFO, VORBIS_COMMENT and CUESHEET are small; pictures are skipped
+            0 | 4 | 5 => {
+                let mut data = vec![0u8; length];
+                file.read_exact(&mut data).ok()?;
+                match kind {
+                    0 if data.len() >= 13 => {
+                        sample_rate = (u32::from(data[10]) << 12) | (u32::from(data[11]) << 4) | (u32::from(data[12]) >> 4);
+                    }
+                    4 => blocks.cuesheet_comment = vorbis_comment(&data, "CUESHEET"),
+                    5 if sample_rate > 0 => blocks.cuesheet_block = parse_cuesheet_block(&data, sample_rate),
+                    _ => {}
+                }
+            }
+            _ => {
+                file.seek(SeekFrom::Current(length as i64)).ok()?;
+            }
+        }
+        if is_last {
+            break;
+        }
+    }
+    Some(blocks)
+}
+
+/// Value of `key` in a FLAC VORBIS_COMMENT block (little-endian lengths)
+fn vorbis_comment(data: &[u8], key: &str) -> Option<String> {
+    let read_u32 = |at: usize| data.get(at..at + 4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize);
+    let mut at = 4 + read_u32(0)?;
+    let count = read_u32(at)?;
+    at += 4;
+    for _ in 0..count {
+        let length = read_u32(at)?;
+        let comment = data.get(at + 4..at + 4 + length)?;
+        at += 4 + length;
+        let comment = String::from_utf8_lossy(comment);
+        if let Some((name, value)) = comment.split_once('=') {
+            if name.eq_ignore_ascii_case(key) {
+                return Some(value.to_string());
+            }
+        }
+    }
+    None
+}
+
+/// Binary CUESHEET block: track offsets in samples, without any titles
+fn parse_cuesheet_block(data: &[u8], sample_rate: u32) -> Option<Vec<CueTrack>> {
+    // Catalog number, lead-in samples, CD flag and reserved bytes come first
+    let mut at = 128 + 8 + 1 + 258;
+    let track_count = *data.get(at)?;
+    at += 1;
+
+    let mut tracks = Vec::new();
+    for _ in 0..track_count {
+        let offset = u64::from_be_bytes(data.get(at..at + 8)?.try_into().ok()?);
+        let number = *data.get(at + 8)?;
+        let isrc = String::from_utf8_lossy(data.get(at + 9..at + 21)?).trim_matches('\0').trim().to_string();
+        let is_audio = data.get(at + 21)? & 0x80 == 0;
+        let index_count = *data.get(at + 35)? as usize;
+        at += 36;
+
+        let mut start = None;
+        for i in 0..index_count {
+            let point = data.get(at + i * 12..at + i * 12 + 12)?;
+            let index_offset = u64::from_be_bytes(point[..8].try_into().ok()?);
+            match point[8] {
+                1 => start = Some(offset + index_offset),
+                0 if start.is_none() => start = Some(offset + index_offset),
+                _ => {}
+            }
+        }
+        at += index_count * 12;
+
+        // 170 (CD) and 255 mark the lead-out, which is not a track
+        if let (Some(start), true, false) = (start, is_audio, number == 170 || number == 255) {
+            tracks.push(CueTrack {
+                number: number.into(),
+                isrc: Some(isrc).filter(|isrc| !isrc.is_empty()),
+                start: start as f64 / f64::from(sample_rate),
+                ..Default::default()
+            });
+        }
+    }
+    Some(tracks)
+}
+
+/// Path string for track `number` of `audio_path`
+pub fn virtual_path(audio_path: &Path, number: u32) -> PathBuf {
+    PathBuf::from(format!("{}{}{}", audio_path.to_string_lossy(), VIRTUAL_MARKER, number))
+}
+
+/// The real file behind a path, and the CUE track number if it is virtual
+pub fn split_virtual_path(path: &str) -> (PathBuf, Option<u32>) {
+    if let Some((file, number)) = path.rsplit_once(VIRTUAL_MARKER) {
+        if let Ok(number) = number.parse() {
+            return (PathBuf::from(file), Some(number));
+        }
+    }
+    (PathBuf::from(path), None)
+}
+
+/// The file on disk that holds a track's audio
+pub fn audio_path(path: &str) -> PathBuf {
+    split_virtual_path(path).0
+}
+
+pub fn is_virtual(path: &str) -> bool {
+    split_virtual_path(path).1.is_some()
+}
+
+/// Look up track `number` of the sheet that splits `audio_path`
+pub fn find_segment(audio_path: &Path, number: u32) -> Option<CueSegment> {
+    segments_for(audio_path).into_iter().find(|segment| segment.track.number == number)
+}
+
+/// Virtual paths for each segment, or just the file when it is not split
+pub fn virtual_paths(audio_path: &Path, segments: &[CueSegment]) -> Vec<PathBuf> {
+    if segments.is_empty() {
+        return vec![audio_path.to_path_buf()];
+    }
+    segments.iter().map(|segment| virtual_path(audio_path, segment.track.number)).collect()
+}
+
+/// Expand a file into its CUE tracks' virtual paths, or keep it as it is
+pub fn expand(audio_path: &Path) -> Vec<PathBuf> {
+    virtual_paths(audio_path, &segments_for(audio_path))
+}
+
+/// Turn whole-file metadata into the metadata of one CUE track
+pub fn apply_segment(track: &mut TrackMetadata, segment: &CueSegment) {
+    let cue = &segment.track;
+    if let Some(title) = &cue.title {
+        track.title = title.clone();
+    } else {
+        track.title = format!("Track {:02}", cue.number);
+    }
+    if let Some(performer) = cue.performer.as_ref().or(segment.album_artist.as_ref()) {
+        track.artist = performer.clone();
+    }
+    if segment.album.is_some() {
+        track.album = segment.album.clone();
+    }
+    if segment.album_artist.is_some() {
+        track.album_artist = segment.album_artist.clone();
+    }
+    if cue.songwriter.is_some() {
+        track.composer = cue.songwriter.clone();
+    }
+    if segment.genre.is_some() {
+        track.genre = segment.genre.clone();
+    }
+    if let Some(date) = &segment.date {
+        track.date = Some(date.clone());
+        track.year = date.get(..4).and_then(|year| year.parse().ok()).or(track.year);
+    }
+    track.track_number = Some(cue.number);
+    track.track_total = Some(segment.track_total);
+
+    let file_duration = track.duration;
+    let end = segment.end.unwrap_or(file_duration);
+    track.duration = (end - cue.start).max(0.0);
+    track.start_time = Some(cue.start);
+    track.end_time = segment.end;
+    // Bytes belong to the whole file; a share of it would not identify anything
+    track.file_size = None;
+    track.chapters.clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{sine_wav, temp_dir};
+
+    const SHEET: &str = "REM GENRE Rock\r\nREM DATE 1988\r\nPERFORMER \"Кино\"\r\nTITLE \"Группа крови\"\r\n\
+        FILE \"album.wav\" WAVE\r\n  TRACK 01 AUDIO\r\n    TITLE \"Группа крови\"\r\n    INDEX 01 00:00:00\r\n\
+          TRACK 02 AUDIO\r\n    TITLE \"Закрой за мной дверь\"\r\n    PERFORMER \"В. Цой\"\r\n    INDEX 00 00:00:40\r\n    INDEX 01 00:00:45\r\n\
+          TRACK 03 DATA\r\n    INDEX 01 00:01:00\r\n\
+          TRACK 04 AUDIO\r\n    INDEX 00 00:01:30\r\n";
+
+    #[test]
+    fn test_parse_sheet() {
+        let sheet = parse(SHEET);
+        assert_eq!(sheet.title.as_deref(), Some("Группа крови"));
+        assert_eq!((sheet.genre.as_deref(), sheet.date.as_deref()), (Some("Rock"), Some("1988")));
+        let tracks = &sheet.files[0].tracks;
+        let numbers: Vec<u32> = tracks.iter().map(|t| t.number).collect();
+        assert_eq!(numbers, [1, 2, 4]);
+        assert_eq!(tracks[1].start, 0.6);
+        assert_eq!(tracks[1].performer.as_deref(), Some("В. Цой"));
+        // No INDEX 01: the pregap is all there is
+        assert_eq!(tracks[2].start, 1.4);
+
+        let segments = segments(&sheet, tracks);
+        assert_eq!(segments[0].end, Some(0.6));
+        assert_eq!(segments[2].end, None);
+        assert_eq!(segments[2].track_total, 3);
+
+        // Windows-1251 sheets decode too
+        let cp1251 = b"TITLE \"\xca\xe8\xed\xee\"";
+        assert_eq!(parse(&decode_text(cp1251)).title.as_deref(), Some("Кино"));
+    }
+
+    #[test]
+    fn test_sidecar_sheet_expands_into_virtual_tracks() {
+        let dir = temp_dir("cue-sidecar");
+        // The sheet still names the WAV the rip started out as
+        let audio = dir.join("album.flac");
+        std::fs::write(&audio, sine_wav(2.0, 8000, 440.0)).unwrap();
+        std::fs::write(dir.join("album.cue"), SHEET).unwrap();
+
+        let paths = expand(&audio);
+        assert_eq!(paths, vec![virtual_path(&audio, 1), virtual_path(&audio, 2), virtual_path(&audio, 4)]);
+        let (file, number) = split_virtual_path(&paths[1].to_string_lossy());
+        assert_eq!((file, number), (audio.clone(), Some(2)));
+        assert_eq!(split_virtual_path("/music/Track #1.mp3"), (PathBuf::from("/music/Track #1.mp3"), None));
+
+        let mut track = TrackMetadata { title: "album".into(), artist: "Unknown Artist".into(), duration: 2.0, file_size: Some(1), ..Default::default() };
+        apply_segment(&mut track, &find_segment(&audio, 2).unwrap());
+        assert_eq!((track.title.as_str(), track.artist.as_str()), ("Закрой за мной дверь", "В. Цой"));
+        assert_eq!((track.album.as_deref(), track.year), (Some("Группа крови"), Some(1988)));
+        assert_eq!((track.start_time, track.end_time), (Some(0.6), Some(1.4)));
+        assert!((track.duration - 0.8).abs() < 1e-9);
+        assert_eq!(track.file_size, None);
+    }
+
+    #[test]
+    fn test_embedded_flac_cuesheet_block() {
+        fn block(kind: u8, last: bool, data: &[u8]) -> Vec<u8> {
+            let mut out = vec![kind | if last { 0x80 } else { 0 }];
+            out.extend_from_slice(&(data.len() as u32).to_be_bytes()[1..]);
+            out.extend_from_slice(data);
+            out
+        }
+
+        // STREAMINFO with a 44100 Hz sample rate at bytes 10..13
+        let mut streaminfo = vec![0u8; 34];
+        streaminfo[10] = (44100u32 >> 12) as u8;
+        streaminfo[11] = (44100u32 >> 4) as u8;
+        streaminfo[12] = ((44100u32 & 0xF) << 4) as u8;
+
+        let mut cuesheet = vec![0u8; 395];
+        cuesheet.push(3);
+        for (number, offset) in [(1u8, 0u64), (2, 441000), (170, 882000)] {
+            cuesheet.extend_from_slice(&offset.to_be_bytes());
+            cuesheet.push(number);
+            cuesheet.extend_from_slice(&[0u8; 12]);
+            cuesheet.extend_from_slice(&[0u8; 14]);
+            let points: u8 = if number == 170 { 0 } else { 1 };
+            cuesheet.push(points);
+            if points == 1 {
+                cuesheet.extend_from_slice(&0u64.to_be_bytes());
+                cuesheet.extend_from_slice(&[1, 0, 0, 0]);
+            }
+        }
+
+        let mut flac = b"fLaC".to_vec();
+        flac.extend(block(0, false, &streaminfo));
+        flac.extend(block(6, false, &[0u8; 64]));
+        flac.extend(block(5, true, &cuesheet));
+
+        let dir = temp_dir("cue-embedded");
+        let path = dir.join("rip.flac");
+        std::fs::write(&path, flac).unwrap();
+
+        let segments = embedded_segments(&path).unwrap();
+        assert_eq!(segments.len(), 2);
+        assert_eq!((segments[1].track.number, segments[1].track.start), (2, 10.0));
+        assert_eq!(segments[0].end, Some(10.0));
+        assert_eq!(expand(&path).len(), 2);
+    }
+}
@@ -0,0 +1,481 @@
+// Duplicates Module
+// Groups library tracks that are the same recording: identical files, identical tags
+// and duration, or matching acoustic signatures, and suggests the best copy to keep
+
+use crate::file_manager::TrackMetadata;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::DecoderOptions;
+use symphonia::core::errors::Error as SymphoniaError;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+
+/// Tracks whose tags match count as copies if their lengths differ by at most this
+const TAG_DURATION_TOLERANCE: f64 = 2.0;
+/// Acoustic candidates must be this close in length before signatures are compared
+const ACOUSTIC_DURATION_TOLERANCE: f64 = 3.0;
+
+/// Acoustic signature: one bit per frame, set when the frame is louder than
+/// the average of the frames around it
+const SIGNATURE_FRAME_MS: u32 = 50;
+const SIGNATURE_WINDOW: usize = 5;
+const SIGNATURE_SECONDS: u32 = 60;
+/// Fewer frames than this say too little to compare
+const SIGNATURE_MIN_FRAMES: usize = 40;
+/// Share of matching bits above which two signatures are the same recording;
+/// unrelated audio sits around one half
+const SIGNATURE_MATCH: f64 = 0.85;
+/// Frames of offset tried when lining signatures up (encoder delay, leading silence)
+const SIGNATURE_MAX_SHIFT: isize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicateKind {
+    /// Byte-for-byte identical files
+    ExactFile,
+    /// Same title and artist, and nearly the same length
+    SameTags,
+    /// Same recording by ear, e.g. a FLAC and its MP3 transcode with different tags
+    Acoustic,
+}
+
+fn default_methods() -> Vec<DuplicateKind> {
+    vec![DuplicateKind::ExactFile, DuplicateKind::SameTags]
+}
+
+/// Which checks to run; acoustic matching decodes audio, so it is opt-in
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DuplicateOptions {
+    #[serde(default = "default_methods")]
+    pub methods: Vec<DuplicateKind>,
+}
+
+impl Default for DuplicateOptions {
+    fn default() -> Self {
+        Self { methods: default_methods() }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DuplicateGroup {
+    pub kind: DuplicateKind,
+    pub tracks: Vec<TrackMetadata>,
+    /// ID of the copy worth keeping
+    pub best: String,
+}
+
+fn is_lossless(codec: &str) -> bool {
+    matches!(codec, "flac" | "alac" | "wavpack" | "ape")
+        || (codec.starts_with("pcm_") && !matches!(codec, "pcm_alaw" | "pcm_mulaw"))
+}
+
+/// Lossless first, then bitrate, then resolution, then the bigger file
+fn quality(track: &TrackMetadata) -> (bool, u32, u64, u64) {
+    let resolution = u64::from(track.sample_rate.unwrap_or(0)) * u64::from(track.bit_depth.unwrap_or(0));
+    (
+        track.codec.as_deref().is_some_and(is_lossless),
+        track.bitrate.unwrap_or(0),
+        resolution,
+        track.file_size.unwrap_or(0),
+    )
+}
+
+fn group(kind: DuplicateKind, mut tracks: Vec<TrackMetadata>) -> DuplicateGroup {
+    tracks.sort_by(|a, b| quality(b).cmp(&quality(a)).then_with(|| a.file_path.cmp(&b.file_path)));
+    DuplicateGroup {
+        kind,
+        best: tracks[0].id.clone(),
+        tracks,
+    }
+}
+
+/// md5 of the whole file
+fn file_hash(path: &Path) -> Option<String> {
+    let mut file = File::open(path).ok()?;
+    let mut context = md5::Context::new();
+    let mut buffer = vec![0u8; 64 * 1024];
+    loop {
+        let read = file.read(&mut buffer).ok()?;
+        if read == 0 {
+            break;
+        }
+        context.consume(&buffer[..read]);
+    }
+    Some(format!("{:x}", context.compute()))
+}
+
+/// Files are only hashed when another file has exactly the same size
+fn exact_groups(tracks: &[TrackMetadata]) -> Vec<Vec<usize>> {
+    let mut by_size: HashMap<u64, Vec<usize>> = HashMap::new();
+    for (index, track) in tracks.iter().enumerate() {
+        if let Some(size) = track.file_size.filter(|&size| size > 0) {
+            by_size.entry(size).or_default().push(index);
+        }
+    }
+
+    let mut groups = Vec::new();
+    for candidates in by_size.into_values().filter(|c| c.len() > 1) {
+        let mut by_hash: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+        for index in candidates {
+            if let Some(hash) = file_hash(Path::new(&tracks[index].file_path)) {
+                by_hash.entry(hash).or_default().push(index);
+            }
+        }
+        groups.extend(by_hash.into_values().filter(|g| g.len() > 1));
+    }
+    groups
+}
+
+fn normalize(value: &str) -> String {
+    value.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase()
+}
+
+fn tag_groups(tracks: &[TrackMetadata]) -> Vec<Vec<usize>> {
+    let mut by_tags: BTreeMap<(String, String), Vec<usize>> = BTreeMap::new();
+    for (index, track) in tracks.iter().enumerate() {
+        // Without real tags the title is just the file name
+        if track.artist == "Unknown Artist" || track.title.trim().is_empty() {
+            continue;
+        }
+        by_tags.entry((normalize(&track.title), normalize(&track.artist))).or_default().push(index);
+    }
+
+    let mut groups = Vec::new();
+    for mut candidates in by_tags.into_values().filter(|c| c.len() > 1) {
+        // Split runs of similar length, so a live version stays apart from the studio one
+        candidates.sort_by(|&a, &b| tracks[a].duration.total_cmp(&tracks[b].duration));
+        let mut run = vec![candidates[0]];
+        for pair in candidates.windows(2) {
+            if tracks[pair[1]].duration - tracks[pair[0]].duration > TAG_DURATION_TOLERANCE {
+                groups.push(std::mem::take(&mut run));
+            }
+            run.push(pair[1]);
+        }
+        groups.push(run);
+    }
+    groups.retain(|g| g.len() > 1);
+    groups
+}
+
+/// Acoustic signature of the first `SIGNATURE_SECONDS` of a file
+pub fn acoustic_fingerprint(path: &Path) -> Option<Vec<u8>> {
+    let file = File::open(path).ok()?;
+    let mss = MediaSourceStream::new(Box::new(file), Default::default());
+    let mut hint = Hint::new();
+    if let Some(ext_str) = path.extension().and_then(|e| e.to_str()) {
+        hint.with_extension(ext_str);
+    }
+
+    let mut format = symphonia::default::get_probe()
+        .format(&hint, mss, &FormatOptions::default(), &MetadataOptions::default())
+        .ok()?
+        .format;
+    let track = format.default_track()?;
+    let track_id = track.id;
+    let sample_rate = track.codec_params.sample_rate?;
+    let mut decoder = symphonia::default::get_codecs()
+        .make(&track.codec_params, &DecoderOptions::default())
+        .ok()?;
+
+    let frame_len = (sample_rate * SIGNATURE_FRAME_MS / 1000).max(1) as usize;
+    let max_frames = (SIGNATURE_SECONDS * 1000 / SIGNATURE_FRAME_MS) as usize;
+    let mut energies: Vec<f32> = Vec::with_capacity(max_frames);
+    let (mut energy, mut in_frame) = (0.0f32, 0usize);
+
+    'decode: while energies.len() < max_frames {
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            Err(_) => break,
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+        let decoded = match decoder.decode(&packet) {
+            Ok(decoded) => decoded,
+            Err(SymphoniaError::DecodeError(_)) => continue,
+            Err(_) => break,
+        };
+
+        let spec = *decoded.spec();
+        let channels = spec.channels.count().max(1);
+        let mut samples = SampleBuffer::<f32>::new(decoded.capacity() as u64, spec);
+        samples.copy_interleaved_ref(decoded);
+
+        // Downmix to mono and sum the energy of each frame
+        for frame in samples.samples().chunks(channels) {
+            let mono = frame.iter().sum::<f32>() / channels as f32;
+            energy += mono * mono;
+            in_frame += 1;
+            if in_frame == frame_len {
+                energies.push(energy);
+                energy = 0.0;
+                in_frame = 0;
+                if energies.len() == max_frames {
+                    break 'decode;
+                }
+            }
+        }
+    }
+
+    if energies.len() < SIGNATURE_MIN_FRAMES {
+        return None;
+    }
+    // Comparing against the local average rather than the previous frame keeps
+    // steady passages stable across codecs and sample rates
+    let mut signature = vec![0u8; energies.len().div_ceil(8)];
+    for (i, energy) in energies.iter().enumerate() {
+        let window = &energies[i.saturating_sub(SIGNATURE_WINDOW)..(i + SIGNATURE_WINDOW + 1).min(energies.len())];
+        let average = window.iter().sum::<f32>() / window.len() as f32;
+        if *energy > average {
+            signature[i / 8] |= 1 << (i % 8);
+        }
+    }
+    Some(signature)
+}
+
+fn bit(signature: &[u8], index: usize) -> bool {
+    signature[index / 8] & (1 << (index % 8)) != 0
+}
+
+/// Share of matching bits at the best small offset
+fn similarity(a: &[u8], b: &[u8]) -> f64 {
+    let bits = a.len().min(b.len()) * 8;
+    let mut best = 0.0f64;
+    for shift in -SIGNATURE_MAX_SHIFT..=SIGNATURE_MAX_SHIFT {
+        let (mut same, mut compared) = (0usize, 0usize);
+        for i in 0..bits {
+            let j = i as isize + shift;
+            if j < 0 || j as usize >= bits {
+                continue;
+            }
+            compared += 1;
+            if bit(a, i) == bit(b, j as usize) {
+                same += 1;
+            }
+        }
+        if compared >= SIGNATURE_MIN_FRAMES {
+            best = best.max(same as f64 / compared as f64);
+        }
+    }
+    best
+}
+
+fn acoustic_groups<F>(tracks: &[TrackMetadata], mut fingerprint: F) -> Vec<Vec<usize>>
+where
+    F: FnMut(&TrackMetadata) -> Option<Vec<u8>>,
+{
+    let mut order: Vec<usize> = (0..tracks.len()).filter(|&i| tracks[i].duration > 0.0).collect();
+    order.sort_by(|&a, &b| tracks[a].duration.total_cmp(&tracks[b].duration));
+
+    let mut signatures: HashMap<usize, Option<Vec<u8>>> = HashMap::new();
+    let mut signature = |index: usize| -> Option<Vec<u8>> {
+        signatures.entry(index).or_insert_with(|| fingerprint(&tracks[index])).clone()
+    };
+
+    // Union-find over matching pairs of similar length
+    let mut parent: Vec<usize> = (0..tracks.len()).collect();
+    fn root(parent: &mut [usize], mut i: usize) -> usize {
+        while parent[i] != i {
+            parent[i] = parent[parent[i]];
+            i = parent[i];
+        }
+        i
+    }
+
+    for (position, &a) in order.iter().enumerate() {
+        for &b in &order[position + 1..] {
+            if tracks[b].duration - tracks[a].duration > ACOUSTIC_DURATION_TOLERANCE {
+                break;
+            }
+            let (Some(sig_a), Some(sig_b)) = (signature(a), signature(b)) else { continue };
+            if similarity(&sig_a, &sig_b) >= SIGNATURE_MATCH {
+                let (root_a, root_b) = (root(&mut parent, a), root(&mut parent, b));
+                parent[root_a] = root_b;
+            }
+        }
+    }
+
+    let mut groups: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+    for &index in &order {
+        groups.entry(root(&mut parent, index)).or_default().push(index);
+    }
+    groups.into_values().filter(|g| g.len() > 1).collect()
+}
+
+/// Find duplicate groups among `tracks`. Groups that only repeat what a
+/// stricter check already found are left out. `fingerprint` supplies acoustic
+/// signatures (usually cached) and is only called when acoustic matching is on.
+pub fn group_duplicates<F>(tracks: &[TrackMetadata], options: &DuplicateOptions, fingerprint: F) -> Vec<DuplicateGroup>
+where
+    F: FnMut(&TrackMetadata) -> Option<Vec<u8>>,
+{
+    let mut found: Vec<(DuplicateKind, Vec<usize>)> = Vec::new();
+    let mut fingerprint = Some(fingerprint);
+    for kind in [DuplicateKind::ExactFile, DuplicateKind::SameTags, DuplicateKind::Acoustic] {
+        if !options.methods.contains(&kind) {
+            continue;
+        }
+        let groups = match kind {
+            DuplicateKind::ExactFile => exact_groups(tracks),
+            DuplicateKind::SameTags => tag_groups(tracks),
+            DuplicateKind::Acoustic => match fingerprint.take() {
+                Some(fingerprint) => acoustic_groups(tracks, fingerprint),
+                None => Vec::new(),
+            },
+        };
+        for members in groups {
+            let set: HashSet<usize> = members.iter().copied().collect();
+            let known = found.iter().any(|(_, earlier)| set.iter().all(|i| earlier.contains(i)));
+            if !known {
+                found.push((kind, members));
+            }
+        }
+    }
+
+    found
+        .into_iter()
+        .map(|(kind, members)| group(kind, members.into_iter().map(|i| tracks[i].clone()).collect()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{temp_dir, wav_from_fn};
+
+    fn track(id: &str, title: &str, duration: f64, codec: &str, bitrate: u32) -> TrackMetadata {
+        TrackMetadata {
+            id: id.to_string(),
+            title: title.to_string(),
+            artist: "Кино".to_string(),
+            duration,
+            file_path: format!("/music/{}", id),
+            codec: Some(codec.to_string()),
+            bitrate: Some(bitrate),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_same_tags_and_best_copy() {
+        let tracks = vec![
+            track("mp3", "Кукушка", 401.0, "mp3", 320),
+            track("flac", "  КУКУШКА ", 400.2, "flac", 900),
+            track("aac", "Кукушка", 399.5, "aac", 256),
+            track("live", "Кукушка", 452.0, "flac", 1000),
+            track("other", "Пачка сигарет", 268.0, "mp3", 320),
+        ];
+
+        let groups = group_duplicates(&tracks, &DuplicateOptions::default(), |_| None);
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].kind, DuplicateKind::SameTags);
+        assert_eq!(groups[0].best, "flac");
+        let ids: Vec<&str> = groups[0].tracks.iter().map(|t| t.id.as_str()).collect();
+        assert_eq!(ids, vec!["flac", "mp3", "aac"]);
+    }
+
+    /// A tone whose loudness changes every 100 ms following `seed`
+    fn pulsing_wav(seed: u32, sample_rate: u32) -> Vec<u8> {
+        let levels: Vec<f32> = (0..200u32)
+            .map(|i| ((i.wrapping_mul(2654435761).wrapping_add(seed.wrapping_mul(40503)) >> 7) % 100) as f32 / 110.0)
+            .collect();
+        wav_from_fn(10.0, sample_rate, move |t| {
+            levels[(t * 10.0) as usize] * (t * 440.0 * std::f32::consts::TAU).sin()
+        })
+    }
+
+    #[test]
+    fn test_exact_and_acoustic_groups() {
+        let dir = temp_dir("duplicates");
+        let file = |name: &str, bytes: Vec<u8>| {
+            let path = dir.join(name);
+            std::fs::write(&path, &bytes).unwrap();
+            TrackMetadata {
+                id: name.to_string(),
+                title: name.to_string(),
+                artist: "Unknown Artist".to_string(),
+                duration: 10.0,
+                file_path: path.to_string_lossy().to_string(),
+                file_size: Some(bytes.len() as u64),
+                sample_rate: Some(if name.starts_with("hi") { 16000 } else { 8000 }),
+                bit_depth: Some(16),
+                codec: Some("pcm_s16le".to_string()),
+                ..Default::default()
+            }
+        };
+        let tracks = vec![
+            file("a.wav", pulsing_wav(1, 8000)),
+            file("a copy.wav", pulsing_wav(1, 8000)),
+            file("hi a.wav", pulsing_wav(1, 16000)),
+            file("b.wav", pulsing_wav(2, 8000)),
+        ];
+
+        let exact = group_duplicates(&tracks, &DuplicateOptions::default(), |_| None);
+        assert_eq!(exact.len(), 1);
+        assert_eq!(exact[0].kind, DuplicateKind::ExactFile);
+        assert_eq!(exact[0].tracks.len(), 2);
+
+        let options = DuplicateOptions { methods: vec![DuplicateKind::ExactFile, DuplicateKind::Acoustic] };
+        let mut decoded = 0;
+        let groups = group_duplicates(&tracks, &options, |t| {
+            decoded += 1;
+            acoustic_fingerprint(Path::new(&t.file_path))
+        });
+        assert_eq!(decoded, 4);
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[1].kind, DuplicateKind::Acoustic);
+        let mut ids: Vec<&str> = groups[1].tracks.iter().map(|t| t.id.as_str()).collect();
+        ids.sort();
+        assert_eq!(ids, vec!["a copy.wav", "a.wav", "hi a.wav"]);
+        assert_eq!(groups[1].best, "hi a.wav");
+    }
+}
+
+// Tauri Commands
+
+use crate::library::{LibraryState, TrackQuery};
+use tauri::State;
+
+/// Group the library's duplicates. Acoustic signatures are cached in the
+/// library, so only new or changed files are decoded again.
+#[tauri::command]
+pub async fn find_duplicates(
+    options: Option<DuplicateOptions>,
+    library: State<'_, LibraryState>,
+) -> Result<Vec<DuplicateGroup>, String> {
+    let library = library.library.clone();
+    let options = options.unwrap_or_default();
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let tracks = library.lock()
+            .map_err(|e| format!("Failed to lock library: {}", e))?
+            .tracks(&TrackQuery::default())
+            .map_err(|e| e.to_string())?;
+
+        let groups = group_duplicates(&tracks, &options, |track| {
+            let cached = library.lock().ok()
+                .and_then(|library| library.acoustic_fingerprint(&track.id).ok().flatten());
+            if cached.is_some() {
+                return cached;
+            }
+
+            // Decode without holding the library
+            let fingerprint = acoustic_fingerprint(Path::new(&track.file_path))?;
+            if let Ok(library) = library.lock() {
+                if let Err(e) = library.set_acoustic_fingerprint(&track.id, &fingerprint) {
+                    log::warn!("Failed to cache fingerprint for {}: {}", track.file_path, e);
+                }
+            }
+            Some(fingerprint)
+        });
+        Ok(groups)
+    })
+    .await
+    .map_err(|e| format!("Duplicate search failed: {}", e))?
+}
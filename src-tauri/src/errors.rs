@@ -54,6 +54,9 @@ pub enum AudioEngineError {
     
     #[error("Audio device error: {0}")]
     DeviceError(String),
+    
+    #[error("Stream error: {0}")]
+    StreamError(String),
 }
 
 impl From<AudioEngineError> for String {
@@ -79,3 +82,141 @@ impl From<PermissionError> for String {
         error.to_string()
     }
 }
+
+#[derive(Error, Debug)]
+pub enum RadioError {
+    #[error("Invalid stream URL: {0}")]
+    InvalidUrl(String),
+    
+    #[error("Station not found: {0}")]
+    StationNotFound(String),
+    
+    #[error("Failed to parse stations: {0}")]
+    ParseError(String),
+    
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+}
+
+impl From<RadioError> for String {
+    fn from(error: RadioError) -> Self {
+        error.to_string()
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum SessionError {
+    #[error("Failed to parse session: {0}")]
+    ParseError(String),
+    
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+}
+
+impl From<SessionError> for String {
+    fn from(error: SessionError) -> Self {
+        error.to_string()
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum LibraryError {
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] rusqlite::Error),
+    
+    #[error("Folder not in library: {0}")]
+    FolderNotFound(String),
+    
+    #[error("Scan failed: {0}")]
+    ScanError(String),
+    
+    #[error("Invalid value: {0}")]
+    InvalidValue(String),
+    
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+}
+
+impl From<LibraryError> for String {
+    fn from(error: LibraryError) -> Self {
+        error.to_string()
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum CoverArtError {
+    #[error("Track not found: {0}")]
+    TrackNotFound(String),
+    
+    #[error("Image error: {0}")]
+    ImageError(String),
+    
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+}
+
+impl From<CoverArtError> for String {
+    fn from(error: CoverArtError) -> Self {
+        error.to_string()
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum TagWriteError {
+    #[error("Tag writing not supported for {0}")]
+    UnsupportedFormat(String),
+    
+    #[error("Invalid file: {0}")]
+    InvalidFile(String),
+    
+    #[error("Invalid tag value: {0}")]
+    InvalidValue(String),
+    
+    #[error("File is currently playing: {0}")]
+    FileInUse(String),
+    
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+}
+
+impl From<TagWriteError> for String {
+    fn from(error: TagWriteError) -> Self {
+        error.to_string()
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum PlaylistError {
+    #[error("Unsupported playlist format: {0}")]
+    UnsupportedFormat(String),
+    
+    #[error("Failed to parse playlist: {0}")]
+    ParseError(String),
+    
+    #[error("Playlist not found: {0}")]
+    NotFound(i64),
+    
+    #[error("Track not in library: {0}")]
+    TrackNotFound(String),
+    
+    #[error("Invalid playlist position: {0}")]
+    InvalidPosition(usize),
+    
+    #[error("Invalid playlist name: {0:?}")]
+    InvalidName(String),
+    
+    #[error("Invalid smart playlist rules: {0}")]
+    InvalidRules(String),
+    
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] rusqlite::Error),
+    
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+}
+
+impl From<PlaylistError> for String {
+    fn from(error: PlaylistError) -> Self {
+        error.to_string()
+    }
+}
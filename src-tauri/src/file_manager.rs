@@ -2,22 +2,68 @@
 // Handles file system operations, audio file picking, and metadata extraction
 
 use crate::errors::FileManagerError;
+use crate::chapters::{self, Chapter};
+use crate::cue;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use walkdir::WalkDir;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct TrackMetadata {
     pub id: String,
     pub title: String,
     pub artist: String,
     pub duration: f64,
     pub file_path: String,
+    
+    // Tags
+    pub album: Option<String>,
+    pub album_artist: Option<String>,
+    pub track_number: Option<u32>,
+    pub track_total: Option<u32>,
+    pub disc_number: Option<u32>,
+    pub disc_total: Option<u32>,
+    pub year: Option<i32>,
+    pub date: Option<String>,
+    pub genre: Option<String>,
+    pub composer: Option<String>,
+    pub comment: Option<String>,
+    pub bpm: Option<u32>,
+    pub compilation: Option<bool>,
+    pub musicbrainz_track_id: Option<String>,
+    pub musicbrainz_album_id: Option<String>,
+    pub musicbrainz_artist_id: Option<String>,
+    pub musicbrainz_album_artist_id: Option<String>,
+    
+    // Technical info
+    pub codec: Option<String>,
+    pub sample_rate: Option<u32>,
+    pub bit_depth: Option<u32>,
+    pub channels: Option<u32>,
+    /// Average bitrate in kbit/s
+    pub bitrate: Option<u32>,
+    pub file_size: Option<u64>,
+    
+    // CUE sheet tracks: the span of `file_path`'s audio file they cover, in seconds
+    pub start_time: Option<f64>,
+    pub end_time: Option<f64>,
+    
+    /// Audiobook and podcast chapters, in playback order
+    #[serde(default)]
+    pub chapters: Vec<Chapter>,
 }
 
+/// Extensions that are never audio, so scans do not probe every cover and playlist
+const NON_AUDIO_EXTENSIONS: &[&str] = &[
+    "jpg", "jpeg", "png", "gif", "webp", "bmp", "txt", "nfo", "log", "cue", "lrc",
+    "m3u", "m3u8", "pls", "xspf", "pdf", "db", "ini", "json", "xml", "html", "sfv", "md5",
+];
+
 #[derive(Debug, Clone)]
 pub struct FileManager {
     supported_formats: Vec<String>,
+    include_extensionless: bool,
 }
 
 impl FileManager {
@@ -33,10 +79,19 @@ impl FileManager {
                 "opus".to_string(),
                 "wma".to_string(),
             ],
+            include_extensionless: false,
         }
     }
     
-    /// Check if a file has a supported audio format
+    /// Also probe files without any extension when scanning (e.g. copies from
+    /// Android content providers)
+    pub fn with_extensionless_files(mut self, include: bool) -> Self {
+        self.include_extensionless = include;
+        self
+    }
+    
+    /// Check if a file has a supported audio extension. This is only a cheap
+    /// first guess; `is_audio_file` looks at the contents when it is not enough.
     pub fn is_supported_format(&self, path: &Path) -> bool {
         if let Some(extension) = path.extension() {
             if let Some(ext_str) = extension.to_str() {
@@ -46,6 +101,45 @@ impl FileManager {
         false
     }
     
+    /// Whether the file contents look like audio, whatever it is called.
+    /// Symphonia probes container markers, so the extension is at most a hint.
+    pub fn sniff_audio(&self, path: &Path) -> bool {
+        let Ok(file) = File::open(path) else { return false };
+        let mss = MediaSourceStream::new(Box::new(file), Default::default());
+        
+        let mut hint = Hint::new();
+        if let Some(ext_str) = path.extension().and_then(|e| e.to_str()) {
+            hint.with_extension(ext_str);
+        }
+        
+        let Ok(probed) = symphonia::default::get_probe()
+            .format(&hint, mss, &FormatOptions::default(), &MetadataOptions::default())
+        else {
+            return false;
+        };
+        
+        // A stray frame sync inside some other file can fool the probe; a real
+        // stream has a codec and a sample rate
+        probed.format.tracks().iter().any(|track| {
+            track.codec_params.codec != CODEC_TYPE_NULL && track.codec_params.sample_rate.is_some()
+        })
+    }
+    
+    /// Decide whether a file found while scanning is audio: known audio and
+    /// non-audio extensions are trusted, anything else is sniffed
+    pub fn is_audio_file(&self, path: &Path) -> bool {
+        let extension = path.extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_lowercase());
+        
+        match extension {
+            Some(ext) if self.supported_formats.contains(&ext) => true,
+            Some(ext) if NON_AUDIO_EXTENSIONS.contains(&ext.as_str()) => false,
+            Some(_) => self.sniff_audio(path),
+            None => self.include_extensionless && self.sniff_audio(path),
+        }
+    }
+    
     /// Get list of supported formats
     pub fn get_supported_formats(&self) -> &[String] {
         &self.supported_formats
@@ -61,7 +155,8 @@ impl FileManager {
             return Err(format!("Path is not a file: {}", path.display()));
         }
         
-        if !self.is_supported_format(path) {
+        // Explicitly chosen files are always sniffed, extension or not
+        if !self.is_supported_format(path) && !self.sniff_audio(path) {
             return Err(format!(
                 "Unsupported file format: {}. Supported formats: {}",
                 path.display(),
@@ -83,6 +178,7 @@ impl FileManager {
         }
         
         let mut audio_files = Vec::new();
+        let mut cue_sheets: HashMap<PathBuf, Vec<cue::CueSegment>> = HashMap::new();
         
         for entry in WalkDir::new(dir_path)
             .follow_links(true)
@@ -90,12 +186,25 @@ impl FileManager {
             .filter_map(|e| e.ok())
         {
             let path = entry.path();
-            if path.is_file() && self.is_supported_format(path) {
+            if !path.is_file() {
+                continue;
+            }
+            if path.extension().and_then(|e| e.to_str()).is_some_and(|e| e.eq_ignore_ascii_case("cue")) {
+                cue_sheets.extend(cue::sidecar_sheet_segments(path));
+            } else if self.is_audio_file(path) {
                 audio_files.push(path.to_path_buf());
             }
         }
         
-        Ok(audio_files)
+        // Files split by a CUE sheet are listed as one virtual path per track
+        Ok(audio_files
+            .iter()
+            .flat_map(|path| match cue_sheets.get(path) {
+                Some(segments) => cue::virtual_paths(path, segments),
+                // Every sidecar sheet has been read already
+                None => cue::virtual_paths(path, &cue::embedded_segments(path).unwrap_or_default()),
+            })
+            .collect())
     }
     
     /// Get file name without extension
@@ -133,11 +242,69 @@ mod tests {
         assert_eq!(fm.get_file_name(Path::new("song.mp3")), "song");
         assert_eq!(fm.get_file_name(Path::new("/path/to/song.mp3")), "song");
     }
+    
+    #[test]
+    fn test_parse_tag_values() {
+        assert_eq!(parse_number_pair("3"), (Some(3), None));
+        assert_eq!(parse_number_pair("03/12"), (Some(3), Some(12)));
+        assert_eq!(parse_number_pair("0"), (None, None));
+        assert_eq!(parse_year("1988-06-21T00:00:00"), Some(1988));
+        assert_eq!(parse_year("88"), None);
+    }
+    
+    #[test]
+    fn test_extract_technical_metadata() {
+        let dir = crate::test_support::temp_dir("metadata");
+        let path = dir.join("tone.wav");
+        std::fs::write(&path, crate::test_support::sine_wav(2.0, 8000, 440.0)).unwrap();
+        
+        let track = FileManager::new().extract_metadata(&path).unwrap();
+        assert_eq!(track.title, "tone");
+        assert_eq!(track.duration, 2.0);
+        assert_eq!(track.sample_rate, Some(8000));
+        assert_eq!(track.channels, Some(1));
+        assert_eq!(track.bit_depth, Some(16));
+        assert_eq!(track.bitrate, Some(128));
+        assert_eq!(track.file_size, Some(32044));
+        assert!(track.codec.is_some());
+        assert_eq!(track.album, None);
+    }
+    
+    #[test]
+    fn test_scan_sniffs_content() {
+        let dir = crate::test_support::temp_dir("sniff");
+        let wav = crate::test_support::sine_wav(0.1, 8000, 440.0);
+        std::fs::write(dir.join("tone.mp3"), &wav).unwrap();
+        std::fs::write(dir.join("tone.bin"), &wav).unwrap();
+        std::fs::write(dir.join("tone"), &wav).unwrap();
+        std::fs::write(dir.join("cover.jpg"), &wav).unwrap();
+        std::fs::write(dir.join("notes.dat"), b"not audio at all").unwrap();
+        std::fs::write(dir.join("README"), b"not audio either").unwrap();
+        
+        let names = |fm: &FileManager| {
+            let mut names: Vec<String> = fm.scan_directory(&dir).unwrap()
+                .iter()
+                .map(|p| p.file_name().unwrap().to_string_lossy().to_string())
+                .collect();
+            names.sort();
+            names
+        };
+        assert_eq!(names(&FileManager::new()), vec!["tone.bin", "tone.mp3"]);
+        assert_eq!(names(&FileManager::new().with_extensionless_files(true)), vec!["tone", "tone.bin", "tone.mp3"]);
+        
+        // Picked files are judged by content alone
+        let fm = FileManager::new();
+        assert!(fm.validate_audio_file(&dir.join("tone")).is_ok());
+        assert!(fm.validate_audio_file(&dir.join("notes.dat")).is_err());
+        assert_eq!(fm.extract_metadata(&dir.join("tone")).unwrap().duration, 0.1);
+    }
 }
 
 // Tauri Commands
 
-use tauri::AppHandle;
+use crate::library::LibraryState;
+use crate::scanner::{self, ScanState};
+use tauri::{AppHandle, Manager, State};
 
 #[tauri::command]
 pub async fn pick_audio_files(app: AppHandle) -> Result<Vec<String>, String> {
@@ -213,6 +380,17 @@ pub async fn pick_audio_folder(app: AppHandle) -> Result<Vec<String>, String> {
             
             log::info!("Found {} audio files", audio_files.len());
             
+            // Remember the folder as a library root
+            if let Some(library) = app.try_state::<LibraryState>() {
+                let result = library.library.lock()
+                    .map_err(|e| e.to_string())
+                    .and_then(|library| library.add_folder(path).map_err(|e| e.to_string()));
+                match result {
+                    Ok(_) => crate::watcher::watch_folder(&app, path),
+                    Err(e) => log::warn!("Failed to add {} to library: {}", path.display(), e),
+                }
+            }
+            
             // Convert paths to strings
             let file_paths: Vec<String> = audio_files
                 .iter()
@@ -227,9 +405,10 @@ pub async fn pick_audio_folder(app: AppHandle) -> Result<Vec<String>, String> {
 
 // Metadata extraction using symphonia
 
-use symphonia::core::formats::FormatOptions;
+use symphonia::core::codecs::CODEC_TYPE_NULL;
+use symphonia::core::formats::{FormatOptions, FormatReader};
 use symphonia::core::io::MediaSourceStream;
-use symphonia::core::meta::MetadataOptions;
+use symphonia::core::meta::{MetadataOptions, StandardTagKey, Tag};
 use symphonia::core::probe::Hint;
 use std::fs::File;
 use std::time::Duration;
@@ -237,6 +416,11 @@ use std::time::Duration;
 impl FileManager {
     /// Extract metadata from an audio file
     pub fn extract_metadata(&self, file_path: &Path) -> Result<TrackMetadata, String> {
+        // A CUE track is read from its file, then narrowed down to its span
+        let path_str = file_path.to_string_lossy().to_string();
+        let (audio_path, cue_number) = cue::split_virtual_path(&path_str);
+        let file_path = audio_path.as_path();
+        
         // Validate file first
         self.validate_audio_file(file_path)?;
         
@@ -259,61 +443,239 @@ impl FileManager {
         let format_opts = FormatOptions::default();
         let metadata_opts = MetadataOptions::default();
         
-        let probed = symphonia::default::get_probe()
+        let mut probed = symphonia::default::get_probe()
             .format(&hint, mss, &format_opts, &metadata_opts)
             .map_err(|e| format!("Failed to probe file: {}", e))?;
         
-        let mut format = probed.format;
-        
-        // Get metadata
-        let metadata = format.metadata();
+        let mut track = TrackMetadata {
+            file_path: path_str,
+            ..Default::default()
+        };
         
-        let mut title = None;
-        let mut artist = None;
+        // Tags found while probing (e.g. ID3v2 in front of an MP3) come first,
+        // then tags from the container itself, which win on conflicts
+        let mut comments = Vec::new();
+        if let Some(metadata_rev) = probed.metadata.get().as_ref().and_then(|m| m.current()) {
+            for tag in metadata_rev.tags() {
+                apply_tag(&mut track, tag);
+                comments.push((tag.key.clone(), tag.value.to_string()));
+            }
+        }
         
-        // Try to get metadata from tags
-        if let Some(metadata_rev) = metadata.current() {
+        let mut format = probed.format;
+        if let Some(metadata_rev) = format.metadata().current() {
             for tag in metadata_rev.tags() {
-                match tag.std_key {
-                    Some(symphonia::core::meta::StandardTagKey::TrackTitle) => {
-                        title = Some(tag.value.to_string());
-                    }
-                    Some(symphonia::core::meta::StandardTagKey::Artist) => {
-                        artist = Some(tag.value.to_string());
-                    }
-                    _ => {}
-                }
+                apply_tag(&mut track, tag);
+                comments.push((tag.key.clone(), tag.value.to_string()));
             }
         }
         
-        // Calculate duration
-        let mut duration_secs = 0.0;
-        if let Some(track) = format.default_track() {
-            if let Some(time_base) = track.codec_params.time_base {
-                if let Some(n_frames) = track.codec_params.n_frames {
-                    let duration = time_base.calc_time(n_frames);
-                    duration_secs = duration.seconds as f64 + (duration.frac as f64);
-                }
+        if let Some(default_track) = format.default_track() {
+            let params = &default_track.codec_params;
+            
+            // Calculate duration
+            if let (Some(time_base), Some(n_frames)) = (params.time_base, params.n_frames) {
+                let duration = time_base.calc_time(n_frames);
+                track.duration = duration.seconds as f64 + duration.frac;
             }
+            
+            track.codec = symphonia::default::get_codecs()
+                .get_codec(params.codec)
+                .map(|descriptor| descriptor.short_name.to_string());
+            track.sample_rate = params.sample_rate;
+            track.bit_depth = params.bits_per_sample;
+            track.channels = params.channels.map(|channels| channels.count() as u32);
+        }
+        
+        // The ID follows the audio, not the path, so moves and renames keep it
+        track.id = audio_fingerprint(format.as_mut())
+            .unwrap_or_else(|| format!("{:x}", md5::compute(track.file_path.as_bytes())));
+        
+        track.file_size = std::fs::metadata(file_path).ok().map(|m| m.len());
+        if let Some(file_size) = track.file_size.filter(|_| track.duration > 0.0) {
+            track.bitrate = Some((file_size as f64 * 8.0 / track.duration / 1000.0).round() as u32);
+        }
+        
+        // CHAPTERxxx comments came with the tags; ID3 and MP4 chapters need their own read
+        track.chapters = chapters::from_comments(comments.iter().map(|(k, v)| (k.as_str(), v.as_str())), track.duration);
+        if track.chapters.is_empty() {
+            track.chapters = chapters::read_container(file_path, track.duration);
         }
         
         // Fallback to file name if no title
-        let final_title = title.unwrap_or_else(|| self.get_file_name(file_path));
-        let final_artist = artist.unwrap_or_else(|| "Unknown Artist".to_string());
+        if track.title.is_empty() {
+            track.title = self.get_file_name(file_path);
+        }
+        if track.artist.is_empty() {
+            track.artist = "Unknown Artist".to_string();
+        }
         
-        // Generate ID from file path
-        let id = format!("{:x}", md5::compute(file_path.to_string_lossy().as_bytes()));
+        if let Some(number) = cue_number {
+            let segment = cue::find_segment(file_path, number)
+                .ok_or_else(|| format!("CUE track {} not found for {}", number, file_path.display()))?;
+            cue::apply_segment(&mut track, &segment);
+            // Every track of the file shares its audio fingerprint
+            track.id = format!("{:x}", md5::compute(format!("{}:{}", track.id, number)));
+        }
         
-        Ok(TrackMetadata {
-            id,
-            title: final_title,
-            artist: final_artist,
-            duration: duration_secs,
-            file_path: file_path.to_string_lossy().to_string(),
+        Ok(track)
+    }
+    
+    /// Extract metadata, falling back to the file name when the file cannot be probed
+    pub fn metadata_or_fallback(&self, file_path: &Path) -> TrackMetadata {
+        self.extract_metadata(file_path).unwrap_or_else(|e| {
+            log::warn!("Failed to extract metadata from {}: {}", file_path.display(), e);
+            let path_str = file_path.to_string_lossy().to_string();
+            TrackMetadata {
+                id: format!("{:x}", md5::compute(path_str.as_bytes())),
+                title: self.get_file_name(file_path),
+                artist: "Unknown Artist".to_string(),
+                file_size: std::fs::metadata(file_path).ok().map(|m| m.len()),
+                file_path: path_str,
+                ..Default::default()
+            }
         })
     }
 }
 
+/// Every tag of a file, as probing reports it, without decoding any audio
+pub fn read_tags(file_path: &Path) -> Vec<Tag> {
+    let Ok(file) = File::open(file_path) else { return Vec::new() };
+    let mss = MediaSourceStream::new(Box::new(file), Default::default());
+    let mut hint = Hint::new();
+    if let Some(ext_str) = file_path.extension().and_then(|e| e.to_str()) {
+        hint.with_extension(ext_str);
+    }
+    let Ok(mut probed) = symphonia::default::get_probe()
+        .format(&hint, mss, &FormatOptions::default(), &MetadataOptions::default())
+    else {
+        return Vec::new();
+    };
+    
+    let mut tags = Vec::new();
+    if let Some(metadata_rev) = probed.metadata.get().as_ref().and_then(|m| m.current()) {
+        tags.extend_from_slice(metadata_rev.tags());
+    }
+    if let Some(metadata_rev) = probed.format.metadata().current() {
+        tags.extend_from_slice(metadata_rev.tags());
+    }
+    tags
+}
+
+/// Audio covered by the fingerprint, and a cap for streams without timestamps
+const FINGERPRINT_SECONDS: u64 = 30;
+const FINGERPRINT_MAX_BYTES: usize = 4 * 1024 * 1024;
+
+/// md5 of the stream parameters and the compressed packets of the first
+/// `FINGERPRINT_SECONDS` of the default track. Tags are never part of the
+/// packets, so editing them, renaming or moving the file keeps the same value.
+fn audio_fingerprint(format: &mut dyn FormatReader) -> Option<String> {
+    let track = format.default_track()?;
+    let track_id = track.id;
+    let params = &track.codec_params;
+    let time_base = params.time_base;
+    
+    let mut context = md5::Context::new();
+    context.consume(format!(
+        "{}:{}:{}",
+        params.codec,
+        params.sample_rate.unwrap_or(0),
+        params.channels.map(|c| c.count()).unwrap_or(0)
+    ));
+    
+    let mut hashed = 0;
+    while hashed < FINGERPRINT_MAX_BYTES {
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            Err(_) => break,
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+        if let Some(time_base) = time_base {
+            if time_base.calc_time(packet.ts()).seconds >= FINGERPRINT_SECONDS {
+                break;
+            }
+        }
+        context.consume(packet.buf());
+        hashed += packet.buf().len();
+    }
+    
+    // No audio at all: nothing to identify the file by
+    (hashed > 0).then(|| format!("{:x}", context.compute()))
+}
+
+/// Copy one symphonia tag into the matching `TrackMetadata` field
+fn apply_tag(track: &mut TrackMetadata, tag: &Tag) {
+    let Some(key) = tag.std_key else { return };
+    let value = tag.value.to_string();
+    let value = value.trim();
+    if value.is_empty() {
+        return;
+    }
+    let text = Some(value.to_string());
+    
+    match key {
+        StandardTagKey::TrackTitle => track.title = value.to_string(),
+        StandardTagKey::Artist => track.artist = value.to_string(),
+        StandardTagKey::Album => track.album = text,
+        StandardTagKey::AlbumArtist => track.album_artist = text,
+        StandardTagKey::TrackNumber => {
+            let (number, total) = parse_number_pair(value);
+            track.track_number = number.or(track.track_number);
+            track.track_total = total.or(track.track_total);
+        }
+        StandardTagKey::TrackTotal => track.track_total = parse_number_pair(value).0,
+        StandardTagKey::DiscNumber => {
+            let (number, total) = parse_number_pair(value);
+            track.disc_number = number.or(track.disc_number);
+            track.disc_total = total.or(track.disc_total);
+        }
+        StandardTagKey::DiscTotal => track.disc_total = parse_number_pair(value).0,
+        StandardTagKey::Date | StandardTagKey::ReleaseDate => {
+            track.year = parse_year(value).or(track.year);
+            track.date = text;
+        }
+        StandardTagKey::OriginalDate if track.date.is_none() => {
+            track.year = parse_year(value);
+            track.date = text;
+        }
+        StandardTagKey::Genre => track.genre = text,
+        StandardTagKey::Composer => track.composer = text,
+        StandardTagKey::Comment => track.comment = text,
+        StandardTagKey::Bpm => track.bpm = value.parse::<f64>().ok().map(|bpm| bpm.round() as u32),
+        StandardTagKey::Compilation => track.compilation = Some(matches!(value, "1" | "true" | "True" | "TRUE" | "yes")),
+        StandardTagKey::MusicBrainzTrackId | StandardTagKey::MusicBrainzRecordingId => {
+            track.musicbrainz_track_id = text;
+        }
+        StandardTagKey::MusicBrainzAlbumId => track.musicbrainz_album_id = text,
+        StandardTagKey::MusicBrainzArtistId => track.musicbrainz_artist_id = text,
+        StandardTagKey::MusicBrainzAlbumArtistId => track.musicbrainz_album_artist_id = text,
+        _ => {}
+    }
+}
+
+/// Parse "3", "3/12" or "03 of 12" style number tags
+fn parse_number_pair(value: &str) -> (Option<u32>, Option<u32>) {
+    let mut numbers = value
+        .split(|c: char| !c.is_ascii_digit())
+        .filter(|part| !part.is_empty())
+        .map(|part| part.parse::<u32>().ok());
+    let number = numbers.next().flatten().filter(|&n| n > 0);
+    let total = numbers.next().flatten().filter(|&n| n > 0);
+    (number, total)
+}
+
+/// Year from a date tag such as "1988", "1988-06-21" or "1988-06-21T00:00:00"
+fn parse_year(value: &str) -> Option<i32> {
+    let digits: String = value.trim().chars().take_while(|c| c.is_ascii_digit()).collect();
+    if digits.len() == 4 {
+        digits.parse().ok()
+    } else {
+        None
+    }
+}
+
 #[tauri::command]
 pub async fn get_metadata(file_path: String) -> Result<TrackMetadata, String> {
     let file_manager = FileManager::new();
@@ -322,28 +684,63 @@ pub async fn get_metadata(file_path: String) -> Result<TrackMetadata, String> {
     file_manager.extract_metadata(path)
 }
 
+/// Metadata for many files at once. Files the library already knows are
+/// answered from it; the rest are probed on the scanner's worker pool and
+/// stored as they come in, with batches streamed as `library:scan_progress`.
 #[tauri::command]
-pub async fn get_multiple_metadata(file_paths: Vec<String>) -> Result<Vec<TrackMetadata>, String> {
+pub async fn get_multiple_metadata(
+    file_paths: Vec<String>,
+    library: State<'_, LibraryState>,
+    scans: State<'_, ScanState>,
+    app: AppHandle,
+) -> Result<Vec<TrackMetadata>, String> {
     let file_manager = FileManager::new();
-    let mut metadata_list = Vec::new();
-    
-    for file_path in file_paths {
-        let path = Path::new(&file_path);
-        match file_manager.extract_metadata(path) {
-            Ok(metadata) => metadata_list.push(metadata),
-            Err(e) => {
-                log::warn!("Failed to extract metadata from {}: {}", file_path, e);
-                // Create fallback metadata
-                metadata_list.push(TrackMetadata {
-                    id: format!("{:x}", md5::compute(file_path.as_bytes())),
-                    title: file_manager.get_file_name(path),
-                    artist: "Unknown Artist".to_string(),
-                    duration: 0.0,
-                    file_path,
-                });
+    let library = library.library.clone();
+    let handle = scans.begin();
+    
+    let metadata = {
+        let handle = handle.clone();
+        tauri::async_runtime::spawn_blocking(move || {
+            // Files already in the library are not probed again unless they changed
+            let mut metadata_list: Vec<Option<TrackMetadata>> = {
+                let library = library.lock()
+                    .map_err(|e| format!("Failed to lock library: {}", e))?;
+                file_paths.iter()
+                    .map(|file_path| library.fresh_track_by_path(file_path).unwrap_or_else(|e| {
+                        log::warn!("Library lookup failed for {}: {}", file_path, e);
+                        None
+                    }))
+                    .collect()
+            };
+            
+            let pending: Vec<usize> = (0..file_paths.len()).filter(|&i| metadata_list[i].is_none()).collect();
+            let paths: Vec<PathBuf> = pending.iter().map(|&i| PathBuf::from(&file_paths[i])).collect();
+            let extracted = scanner::extract_all(&file_manager, &paths, &handle, |progress| {
+                // Store each batch before reporting it, so the IDs sent are final
+                match library.lock() {
+                    Ok(library) => {
+                        for track in progress.tracks.iter_mut() {
+                            match library.upsert_track(track) {
+                                Ok(id) => track.id = id,
+                                Err(e) => log::warn!("Failed to store {} in library: {}", track.file_path, e),
+                            }
+                        }
+                    }
+                    Err(e) => log::warn!("Failed to lock library: {}", e),
+                }
+                scanner::emit_progress(&app, progress);
+            });
+            
+            for (index, track) in pending.into_iter().zip(extracted) {
+                metadata_list[index] = track;
             }
-        }
-    }
+            // Files skipped by a cancelled scan are left out
+            Ok(metadata_list.into_iter().flatten().collect())
+        })
+        .await
+        .map_err(|e| format!("Metadata task failed: {}", e))
+    };
+    scans.end(&handle);
     
-    Ok(metadata_list)
+    metadata?
 }
@@ -0,0 +1,294 @@
+// History Module
+// Append-only log of every listen to a library track (when it started, how long
+// it was heard, where it was played from, whether it counted or was skipped),
+// queryable by date range and exportable to CSV or JSON
+
+use crate::browse::Page;
+use crate::errors::LibraryError;
+use crate::library::Library;
+use rusqlite::{params, Row};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub id: i64,
+    pub track_id: String,
+    /// Names as they were when the track was played
+    pub title: String,
+    pub artist: String,
+    pub album: Option<String>,
+    /// Unix time the track was loaded
+    pub started_at: i64,
+    /// Seconds actually heard, seeks excluded
+    pub listened: f64,
+    pub source: Option<String>,
+    /// Heard long enough to count as a play
+    pub completed: bool,
+    /// Left early
+    pub skipped: bool,
+}
+
+/// A listen to log; the track's names are copied from the library
+#[derive(Debug, Clone)]
+pub struct Listened<'a> {
+    pub track_id: &'a str,
+    pub started_at: i64,
+    pub listened: f64,
+    pub source: Option<&'a str>,
+    pub completed: bool,
+    pub skipped: bool,
+}
+
+/// Range and paging for `Library::history`; `from` is inclusive, `to` exclusive
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HistoryQuery {
+    pub from: Option<i64>,
+    pub to: Option<i64>,
+    pub track_id: Option<String>,
+    pub limit: Option<u32>,
+    pub offset: Option<u32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HistoryFormat {
+    Csv,
+    Json,
+}
+
+impl HistoryFormat {
+    pub fn from_path(path: &Path) -> Result<Self, LibraryError> {
+        let extension = path.extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_lowercase());
+
+        match extension.as_deref() {
+            Some("csv") => Ok(HistoryFormat::Csv),
+            Some("json") => Ok(HistoryFormat::Json),
+            _ => Err(LibraryError::InvalidValue(format!("Unknown history format: {}", path.display()))),
+        }
+    }
+}
+
+const HISTORY_FILTER: &str = "(?1 IS NULL OR started_at >= ?1)
+    AND (?2 IS NULL OR started_at < ?2)
+    AND (?3 IS NULL OR track_id = ?3)";
+
+fn entry_from_row(row: &Row) -> rusqlite::Result<HistoryEntry> {
+    Ok(HistoryEntry {
+        id: row.get(0)?,
+        track_id: row.get(1)?,
+        title: row.get(2)?,
+        artist: row.get(3)?,
+        album: row.get(4)?,
+        started_at: row.get(5)?,
+        listened: row.get(6)?,
+        source: row.get(7)?,
+        completed: row.get(8)?,
+        skipped: row.get(9)?,
+    })
+}
+
+impl Library {
+    /// Log a listen; returns its entry ID, or `None` for a track not in the library
+    pub fn append_history(&self, listen: &Listened) -> Result<Option<i64>, LibraryError> {
+        let inserted = self.conn.execute(
+            "INSERT INTO history (track_id, title, artist, album, started_at, listened, source, completed, skipped)
+             SELECT t.id, t.title, ar.name, al.title, ?2, ?3, ?4, ?5, ?6
+             FROM tracks t
+             JOIN artists ar ON ar.id = t.artist_id
+             LEFT JOIN albums al ON al.id = t.album_id
+             WHERE t.id = ?1",
+            params![listen.track_id, listen.started_at, listen.listened, listen.source, listen.completed, listen.skipped],
+        )?;
+        Ok((inserted > 0).then(|| self.conn.last_insert_rowid()))
+    }
+
+    /// Final listening time of an entry logged while the track was still playing
+    pub fn set_history_listened(&self, id: i64, listened: f64) -> Result<(), LibraryError> {
+        self.conn.execute("UPDATE history SET listened = ?1 WHERE id = ?2", params![listened, id])?;
+        Ok(())
+    }
+
+    /// Entries in the range, newest first
+    pub fn history(&self, query: &HistoryQuery) -> Result<Page<HistoryEntry>, LibraryError> {
+        let range = params![query.from, query.to, query.track_id];
+        let total = self.conn.query_row(
+            &format!("SELECT COUNT(*) FROM history WHERE {}", HISTORY_FILTER),
+            range,
+            |row| row.get(0),
+        )?;
+
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT id, track_id, title, artist, album, started_at, listened, source, completed, skipped
+             FROM history WHERE {}
+             ORDER BY started_at DESC, id DESC
+             LIMIT ?4 OFFSET ?5",
+            HISTORY_FILTER
+        ))?;
+        let items = stmt
+            .query_map(
+                params![
+                    query.from,
+                    query.to,
+                    query.track_id,
+                    query.limit.map(i64::from).unwrap_or(-1),
+                    query.offset.unwrap_or(0),
+                ],
+                entry_from_row,
+            )?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Page { total, items })
+    }
+
+    /// Write the entries in the range to `path`, oldest first; returns how many
+    pub fn export_history(&self, path: &Path, format: HistoryFormat, query: &HistoryQuery) -> Result<u32, LibraryError> {
+        let mut entries = self.history(query)?.items;
+        entries.reverse();
+
+        let contents = match format {
+            HistoryFormat::Csv => to_csv(&entries),
+            HistoryFormat::Json => serde_json::to_string_pretty(&entries)
+                .map_err(|e| LibraryError::InvalidValue(e.to_string()))?,
+        };
+        std::fs::write(path, contents)?;
+        Ok(entries.len() as u32)
+    }
+}
+
+/// Quote a CSV field when it holds a separator, quote or line break
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn to_csv(entries: &[HistoryEntry]) -> String {
+    let mut csv = String::from("started_at,track_id,title,artist,album,listened,source,completed,skipped\r\n");
+    for entry in entries {
+        let fields = [
+            entry.started_at.to_string(),
+            csv_field(&entry.track_id),
+            csv_field(&entry.title),
+            csv_field(&entry.artist),
+            csv_field(entry.album.as_deref().unwrap_or("")),
+            format!("{:.1}", entry.listened),
+            csv_field(entry.source.as_deref().unwrap_or("")),
+            entry.completed.to_string(),
+            entry.skipped.to_string(),
+        ];
+        csv.push_str(&fields.join(","));
+        csv.push_str("\r\n");
+    }
+    csv
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_manager::TrackMetadata;
+
+    fn listen(track_id: &str, started_at: i64, completed: bool) -> Listened<'_> {
+        Listened { track_id, started_at, listened: 30.0, source: Some("playlist:1"), completed, skipped: !completed }
+    }
+
+    #[test]
+    fn test_history_outlives_track_and_filters_by_range() {
+        let library = Library::open_in_memory().unwrap();
+        let track = TrackMetadata {
+            id: "abc".to_string(),
+            title: "Hello, \"World\"".to_string(),
+            artist: "Artist".to_string(),
+            album: Some("Album".to_string()),
+            file_path: "/music/hello.mp3".to_string(),
+            ..Default::default()
+        };
+        let id = library.upsert_track(&track).unwrap();
+
+        let first = library.append_history(&listen(&id, 100, true)).unwrap().unwrap();
+        library.set_history_listened(first, 180.0).unwrap();
+        library.append_history(&listen(&id, 200, false)).unwrap();
+        library.append_history(&listen(&id, 300, true)).unwrap();
+        // Unknown tracks are not logged
+        assert_eq!(library.append_history(&listen("missing", 400, true)).unwrap(), None);
+
+        library.conn.execute("DELETE FROM tracks WHERE id = ?1", [&id]).unwrap();
+
+        let all = library.history(&HistoryQuery::default()).unwrap();
+        assert_eq!(all.total, 3);
+        let times: Vec<i64> = all.items.iter().map(|e| e.started_at).collect();
+        assert_eq!(times, vec![300, 200, 100]);
+        assert_eq!((all.items[2].title.as_str(), all.items[2].listened), ("Hello, \"World\"", 180.0));
+
+        let range = library.history(&HistoryQuery { from: Some(150), to: Some(300), ..Default::default() }).unwrap();
+        assert_eq!(range.total, 1);
+        assert!(range.items[0].skipped);
+    }
+
+    #[test]
+    fn test_csv_quotes_fields() {
+        let entry = HistoryEntry {
+            id: 1,
+            track_id: "abc".to_string(),
+            title: "Hello, \"World\"".to_string(),
+            artist: "Artist".to_string(),
+            album: None,
+            started_at: 100,
+            listened: 12.34,
+            source: Some("album:2".to_string()),
+            completed: false,
+            skipped: true,
+        };
+        let csv = to_csv(&[entry]);
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[1], "100,abc,\"Hello, \"\"World\"\"\",Artist,,12.3,album:2,false,true");
+    }
+}
+
+// Tauri Commands
+
+use crate::library::LibraryState;
+use std::path::PathBuf;
+use tauri::State;
+
+#[tauri::command]
+pub fn get_history(query: Option<HistoryQuery>, library: State<'_, LibraryState>) -> Result<Page<HistoryEntry>, String> {
+    let library = library.library.lock()
+        .map_err(|e| format!("Failed to lock library: {}", e))?;
+
+    library.history(&query.unwrap_or_default())
+        .map_err(|e| e.to_string())
+}
+
+/// Export the history in a range; the format follows the extension unless given
+#[tauri::command]
+pub async fn export_history(
+    path: String,
+    format: Option<HistoryFormat>,
+    query: Option<HistoryQuery>,
+    library: State<'_, LibraryState>,
+) -> Result<u32, String> {
+    let library = library.library.clone();
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let path = PathBuf::from(path);
+        let format = match format {
+            Some(format) => format,
+            None => HistoryFormat::from_path(&path).map_err(|e| e.to_string())?,
+        };
+        let mut query = query.unwrap_or_default();
+        query.limit = None;
+        query.offset = None;
+
+        library.lock()
+            .map_err(|e| format!("Failed to lock library: {}", e))?
+            .export_history(&path, format, &query)
+            .map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| format!("History export failed: {}", e))?
+}
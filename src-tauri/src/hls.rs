@@ -0,0 +1,714 @@
+// HLS Module
+// HTTP Live Streaming audio: playlist parsing, variant selection and live
+// playlist reloading. Audio is pulled out of MPEG-TS segments; fMP4 and
+// packed-audio segments are passed through to the decoder as-is.
+
+use crate::errors::AudioEngineError;
+use crate::http_stream::{self, BufferWriter, Cancelled, HttpStream};
+use std::io::Read;
+use std::time::Duration;
+use url::Url;
+
+/// How many segments back from the live edge playback starts
+const LIVE_EDGE_SEGMENTS: usize = 3;
+
+/// Attempts per segment or playlist fetch before giving up on it
+const FETCH_ATTEMPTS: u32 = 3;
+
+/// Consecutive failed live playlist reloads before the stream errors out
+const MAX_RELOAD_FAILURES: u32 = 8;
+
+const TS_PACKET_SIZE: usize = 188;
+const TS_SYNC_BYTE: u8 = 0x47;
+
+/// MPEG-1/2 audio and ADTS AAC; other stream types are skipped
+const TS_AUDIO_STREAM_TYPES: [u8; 3] = [0x03, 0x04, 0x0F];
+
+/// Returns true if the URL points at an HLS playlist
+pub fn is_hls_url(source: &str) -> bool {
+    let path = source.split(['?', '#']).next().unwrap_or(source);
+    path.to_ascii_lowercase().ends_with(".m3u8")
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Variant {
+    pub uri: Url,
+    pub bandwidth: u64,
+    pub codecs: Option<String>,
+    pub audio_group: Option<String>,
+}
+
+/// Alternative audio playlist from `#EXT-X-MEDIA:TYPE=AUDIO`
+#[derive(Debug, Clone, PartialEq)]
+pub struct AudioRendition {
+    pub uri: Url,
+    pub group_id: String,
+    pub is_default: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Segment {
+    pub uri: Url,
+    pub duration: f64,
+    pub sequence: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MediaPlaylist {
+    pub target_duration: f64,
+    pub segments: Vec<Segment>,
+    /// `#EXT-X-MAP` initialization segment for fMP4 streams
+    pub init_segment: Option<Url>,
+    /// `#EXT-X-ENDLIST` seen; otherwise the playlist is live
+    pub ended: bool,
+}
+
+impl MediaPlaylist {
+    /// Sequence number playback should start from
+    fn start_sequence(&self) -> u64 {
+        let start = if self.ended {
+            0
+        } else {
+            self.segments.len().saturating_sub(LIVE_EDGE_SEGMENTS)
+        };
+        self.segments.get(start).map(|s| s.sequence).unwrap_or(0)
+    }
+
+    /// Total duration, known only once the playlist has ended
+    pub fn duration(&self) -> Option<f64> {
+        self.ended.then(|| self.segments.iter().map(|s| s.duration).sum())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Playlist {
+    Master {
+        variants: Vec<Variant>,
+        renditions: Vec<AudioRendition>,
+    },
+    Media(MediaPlaylist),
+}
+
+/// Split an attribute list such as `BANDWIDTH=64000,CODECS="mp4a.40.2,mp4a.40.5"`
+fn parse_attributes(input: &str) -> Vec<(String, String)> {
+    let mut attributes = Vec::new();
+    let mut rest = input.trim();
+
+    while let Some(eq) = rest.find('=') {
+        let key = rest[..eq].trim().to_string();
+        rest = &rest[eq + 1..];
+
+        let value = if let Some(quoted) = rest.strip_prefix('"') {
+            let end = quoted.find('"').unwrap_or(quoted.len());
+            let value = &quoted[..end];
+            rest = quoted.get(end + 1..).unwrap_or("");
+            value
+        } else {
+            let end = rest.find(',').unwrap_or(rest.len());
+            let value = &rest[..end];
+            rest = &rest[end..];
+            value
+        };
+
+        attributes.push((key, value.trim().to_string()));
+        rest = rest.trim_start_matches(',').trim_start();
+    }
+
+    attributes
+}
+
+fn attribute<'a>(attributes: &'a [(String, String)], key: &str) -> Option<&'a str> {
+    attributes
+        .iter()
+        .find(|(name, _)| name == key)
+        .map(|(_, value)| value.as_str())
+}
+
+/// Parse a master or media playlist, resolving URIs against `base`
+pub fn parse_playlist(text: &str, base: &Url) -> Result<Playlist, String> {
+    let mut lines = text.lines().map(str::trim).filter(|line| !line.is_empty());
+    if lines.next() != Some("#EXTM3U") {
+        return Err("Not an M3U8 playlist".to_string());
+    }
+
+    let resolve = |uri: &str| base.join(uri).map_err(|e| format!("Invalid URI {}: {}", uri, e));
+
+    let mut variants = Vec::new();
+    let mut renditions = Vec::new();
+    let mut pending_variant: Option<Vec<(String, String)>> = None;
+
+    let mut segments = Vec::new();
+    let mut pending_duration: Option<f64> = None;
+    let mut media_sequence = 0u64;
+    let mut target_duration = 0.0;
+    let mut init_segment = None;
+    let mut ended = false;
+
+    for line in lines {
+        if let Some(attrs) = line.strip_prefix("#EXT-X-STREAM-INF:") {
+            pending_variant = Some(parse_attributes(attrs));
+        } else if let Some(attrs) = line.strip_prefix("#EXT-X-MEDIA:") {
+            let attrs = parse_attributes(attrs);
+            if let (Some("AUDIO"), Some(uri), Some(group_id)) = (
+                attribute(&attrs, "TYPE"),
+                attribute(&attrs, "URI"),
+                attribute(&attrs, "GROUP-ID"),
+            ) {
+                renditions.push(AudioRendition {
+                    uri: resolve(uri)?,
+                    group_id: group_id.to_string(),
+                    is_default: attribute(&attrs, "DEFAULT") == Some("YES"),
+                });
+            }
+        } else if let Some(value) = line.strip_prefix("#EXT-X-TARGETDURATION:") {
+            target_duration = value.trim().parse().unwrap_or(0.0);
+        } else if let Some(value) = line.strip_prefix("#EXT-X-MEDIA-SEQUENCE:") {
+            media_sequence = value.trim().parse().unwrap_or(0);
+        } else if let Some(value) = line.strip_prefix("#EXTINF:") {
+            let duration = value.split(',').next().unwrap_or("").trim();
+            pending_duration = Some(duration.parse().unwrap_or(0.0));
+        } else if let Some(attrs) = line.strip_prefix("#EXT-X-MAP:") {
+            if let Some(uri) = attribute(&parse_attributes(attrs), "URI") {
+                init_segment = Some(resolve(uri)?);
+            }
+        } else if let Some(attrs) = line.strip_prefix("#EXT-X-KEY:") {
+            let attrs = parse_attributes(attrs);
+            if attribute(&attrs, "METHOD").is_some_and(|method| method != "NONE") {
+                return Err("Encrypted HLS streams are not supported".to_string());
+            }
+        } else if line == "#EXT-X-ENDLIST" {
+            ended = true;
+        } else if line.starts_with('#') {
+            // Unknown or irrelevant tag
+        } else if let Some(attrs) = pending_variant.take() {
+            variants.push(Variant {
+                uri: resolve(line)?,
+                bandwidth: attribute(&attrs, "BANDWIDTH").and_then(|b| b.parse().ok()).unwrap_or(0),
+                codecs: attribute(&attrs, "CODECS").map(str::to_string),
+                audio_group: attribute(&attrs, "AUDIO").map(str::to_string),
+            });
+        } else if let Some(duration) = pending_duration.take() {
+            segments.push(Segment {
+                uri: resolve(line)?,
+                duration,
+                sequence: media_sequence + segments.len() as u64,
+            });
+        }
+    }
+
+    if !variants.is_empty() {
+        return Ok(Playlist::Master { variants, renditions });
+    }
+
+    Ok(Playlist::Media(MediaPlaylist {
+        target_duration,
+        segments,
+        init_segment,
+        ended,
+    }))
+}
+
+/// Pick the highest-bandwidth variant within `max_bandwidth`, falling back
+/// to the lowest one when none fit
+pub fn select_variant(variants: &[Variant], max_bandwidth: Option<u64>) -> Option<&Variant> {
+    variants
+        .iter()
+        .filter(|v| max_bandwidth.map_or(true, |max| v.bandwidth <= max))
+        .max_by_key(|v| v.bandwidth)
+        .or_else(|| variants.iter().min_by_key(|v| v.bandwidth))
+}
+
+/// Audio-only playlist for the variant's audio group, if the master has one
+fn audio_rendition<'a>(variant: &Variant, renditions: &'a [AudioRendition]) -> Option<&'a AudioRendition> {
+    let group = variant.audio_group.as_deref()?;
+    let mut in_group = renditions.iter().filter(|r| r.group_id == group);
+    let first = in_group.clone().next();
+    in_group.find(|r| r.is_default).or(first)
+}
+
+/// Pulls the first supported audio elementary stream out of MPEG-TS packets
+#[derive(Debug, Default)]
+struct TsDemuxer {
+    pmt_pid: Option<u16>,
+    audio_pid: Option<u16>,
+    pmt_seen: bool,
+}
+
+impl TsDemuxer {
+    fn demux(&mut self, data: &[u8]) -> Result<Vec<u8>, String> {
+        let mut audio = Vec::with_capacity(data.len());
+
+        for packet in data.chunks_exact(TS_PACKET_SIZE) {
+            if packet[0] != TS_SYNC_BYTE {
+                continue;
+            }
+
+            let payload_start = packet[1] & 0x40 != 0;
+            let pid = ((packet[1] as u16 & 0x1F) << 8) | packet[2] as u16;
+            let adaptation = (packet[3] >> 4) & 0x03;
+
+            let offset = match adaptation {
+                0b01 => 4,
+                0b11 => 5 + packet[4] as usize,
+                _ => continue, // No payload
+            };
+            let Some(payload) = packet.get(offset..) else { continue };
+
+            if pid == 0 {
+                if payload_start {
+                    self.parse_pat(payload);
+                }
+            } else if Some(pid) == self.pmt_pid {
+                if payload_start {
+                    self.parse_pmt(payload);
+                }
+            } else if Some(pid) == self.audio_pid {
+                if payload_start {
+                    audio.extend_from_slice(pes_payload(payload));
+                } else {
+                    audio.extend_from_slice(payload);
+                }
+            }
+        }
+
+        if self.pmt_seen && self.audio_pid.is_none() {
+            return Err("HLS segment has no supported audio stream".to_string());
+        }
+
+        Ok(audio)
+    }
+
+    fn parse_pat(&mut self, payload: &[u8]) {
+        let Some(table) = psi_section(payload) else { return };
+        // Program entries sit between the 8-byte header and the CRC
+        let entries = table.get(8..table.len().saturating_sub(4)).unwrap_or(&[]);
+        for entry in entries.chunks_exact(4) {
+            let program = u16::from_be_bytes([entry[0], entry[1]]);
+            if program != 0 {
+                self.pmt_pid = Some(((entry[2] as u16 & 0x1F) << 8) | entry[3] as u16);
+                return;
+            }
+        }
+    }
+
+    fn parse_pmt(&mut self, payload: &[u8]) {
+        let Some(table) = psi_section(payload) else { return };
+        if table.len() < 16 {
+            return;
+        }
+        self.pmt_seen = true;
+
+        let program_info_length = ((table[10] as usize & 0x0F) << 8) | table[11] as usize;
+        let end = table.len() - 4;
+        let mut pos = 12 + program_info_length;
+
+        while pos + 5 <= end {
+            let stream_type = table[pos];
+            let pid = ((table[pos + 1] as u16 & 0x1F) << 8) | table[pos + 2] as u16;
+            let info_length = ((table[pos + 3] as usize & 0x0F) << 8) | table[pos + 4] as usize;
+
+            if TS_AUDIO_STREAM_TYPES.contains(&stream_type) {
+                self.audio_pid = Some(pid);
+                return;
+            }
+            pos += 5 + info_length;
+        }
+    }
+}
+
+/// PSI table following the pointer field, trimmed to its section length
+fn psi_section(payload: &[u8]) -> Option<&[u8]> {
+    let pointer = *payload.first()? as usize;
+    let table = payload.get(1 + pointer..)?;
+    let length = ((*table.get(1)? as usize & 0x0F) << 8) | *table.get(2)? as usize;
+    table.get(..3 + length)
+}
+
+/// Elementary stream bytes of a packet that starts a PES packet
+fn pes_payload(payload: &[u8]) -> &[u8] {
+    if payload.len() < 9 || payload[..3] != [0x00, 0x00, 0x01] {
+        return payload;
+    }
+    let header_length = payload[8] as usize;
+    payload.get(9 + header_length..).unwrap_or(&[])
+}
+
+/// Skip an ID3v2 tag at the start of a packed-audio segment
+fn strip_id3(data: &[u8]) -> &[u8] {
+    if data.len() < 10 || &data[..3] != b"ID3" {
+        return data;
+    }
+    let size = data[6..10].iter().fold(0usize, |acc, &b| (acc << 7) | (b & 0x7F) as usize);
+    let footer = if data[5] & 0x10 != 0 { 10 } else { 0 };
+    data.get(10 + size + footer..).unwrap_or(&[])
+}
+
+fn is_transport_stream(data: &[u8]) -> bool {
+    data.len() >= TS_PACKET_SIZE
+        && data[0] == TS_SYNC_BYTE
+        && data.get(TS_PACKET_SIZE).map_or(true, |&b| b == TS_SYNC_BYTE)
+}
+
+/// Turns downloaded segments into a continuous stream for the decoder
+#[derive(Debug, Default)]
+struct SegmentDemuxer {
+    ts: TsDemuxer,
+}
+
+impl SegmentDemuxer {
+    fn extract_audio(&mut self, data: &[u8]) -> Result<Vec<u8>, String> {
+        if is_transport_stream(data) {
+            self.ts.demux(data)
+        } else {
+            // fMP4 fragments and packed ADTS/MP3 are already decodable
+            Ok(strip_id3(data).to_vec())
+        }
+    }
+}
+
+fn fetch_bytes(agent: &ureq::Agent, url: &Url) -> Result<Vec<u8>, String> {
+    let response = agent
+        .get(url.as_str())
+        .call()
+        .map_err(|e| format!("Failed to fetch {}: {}", url, e))?;
+    let mut bytes = Vec::new();
+    response
+        .into_reader()
+        .read_to_end(&mut bytes)
+        .map_err(|e| format!("Failed to read {}: {}", url, e))?;
+    Ok(bytes)
+}
+
+fn fetch_playlist(agent: &ureq::Agent, url: &Url) -> Result<Playlist, String> {
+    let bytes = fetch_bytes(agent, url)?;
+    parse_playlist(&String::from_utf8_lossy(&bytes), url)
+}
+
+/// Fetch with retries; `Ok(Err(..))` means every attempt failed
+fn fetch_with_retry(agent: &ureq::Agent, url: &Url, writer: &BufferWriter) -> Result<Result<Vec<u8>, String>, Cancelled> {
+    let mut last_error = String::new();
+    for attempt in 1..=FETCH_ATTEMPTS {
+        match fetch_bytes(agent, url) {
+            Ok(bytes) => return Ok(Ok(bytes)),
+            Err(e) => {
+                log::warn!("HLS fetch attempt {} failed: {}", attempt, e);
+                last_error = e;
+            }
+        }
+        if attempt < FETCH_ATTEMPTS {
+            writer.wait(http_stream::reconnect_delay(attempt))?;
+        }
+    }
+    Ok(Err(last_error))
+}
+
+/// Open an HLS playlist as a decodable stream; also returns the total
+/// duration for finished (VOD) playlists
+pub fn open(url: &str, max_bandwidth: Option<u64>) -> Result<(HttpStream, Option<f64>), AudioEngineError> {
+    let agent = http_stream::agent();
+    let mut playlist_url = Url::parse(url)
+        .map_err(|e| AudioEngineError::StreamError(format!("Invalid playlist URL {}: {}", url, e)))?;
+    let mut playlist = fetch_playlist(&agent, &playlist_url).map_err(AudioEngineError::StreamError)?;
+
+    if let Playlist::Master { variants, renditions } = &playlist {
+        let variant = select_variant(variants, max_bandwidth)
+            .ok_or_else(|| AudioEngineError::StreamError("Master playlist has no variants".to_string()))?;
+        log::info!("Selected HLS variant {} ({} bps)", variant.uri, variant.bandwidth);
+
+        playlist_url = audio_rendition(variant, renditions)
+            .map(|rendition| rendition.uri.clone())
+            .unwrap_or_else(|| variant.uri.clone());
+        playlist = fetch_playlist(&agent, &playlist_url).map_err(AudioEngineError::StreamError)?;
+    }
+
+    let Playlist::Media(media) = playlist else {
+        return Err(AudioEngineError::StreamError("Variant playlist is not a media playlist".to_string()));
+    };
+
+    let duration = media.duration();
+    let stream_url = playlist_url.to_string();
+    let stream_agent = agent.clone();
+    let stream = HttpStream::from_producer(&stream_url, agent, None, false, move |writer| {
+        run(stream_agent, playlist_url, media, writer)
+    });
+
+    Ok((stream, duration))
+}
+
+/// Download segments in order, reloading live playlists as they advance
+fn run(agent: ureq::Agent, playlist_url: Url, mut playlist: MediaPlaylist, writer: BufferWriter) {
+    let mut demuxer = SegmentDemuxer::default();
+    let mut next_sequence = playlist.start_sequence();
+    let mut current_init: Option<Url> = None;
+    let mut reload_failures = 0;
+
+    loop {
+        if let Some(init) = playlist.init_segment.clone().filter(|init| current_init.as_ref() != Some(init)) {
+            match fetch_with_retry(&agent, &init, &writer) {
+                Ok(Ok(bytes)) => {
+                    if writer.write(&bytes).is_err() {
+                        return;
+                    }
+                    current_init = Some(init);
+                }
+                Ok(Err(e)) => return writer.fail(e),
+                Err(Cancelled) => return,
+            }
+        }
+
+        if let Some(first) = playlist.segments.first() {
+            if next_sequence < first.sequence {
+                log::warn!("Fell behind the live window, skipping to segment {}", first.sequence);
+                next_sequence = first.sequence;
+            }
+        }
+
+        let mut fetched = 0;
+        let first_new = next_sequence;
+        for segment in playlist.segments.iter().filter(|s| s.sequence >= first_new) {
+            let bytes = match fetch_with_retry(&agent, &segment.uri, &writer) {
+                Ok(Ok(bytes)) => bytes,
+                Ok(Err(e)) if playlist.ended => return writer.fail(e),
+                Ok(Err(e)) => {
+                    // A live stream can survive a missing segment
+                    log::warn!("Skipping HLS segment {}: {}", segment.sequence, e);
+                    next_sequence = segment.sequence + 1;
+                    continue;
+                }
+                Err(Cancelled) => return,
+            };
+
+            let audio = match demuxer.extract_audio(&bytes) {
+                Ok(audio) => audio,
+                Err(e) => return writer.fail(e),
+            };
+            if writer.write(&audio).is_err() {
+                return;
+            }
+
+            next_sequence = segment.sequence + 1;
+            fetched += 1;
+        }
+
+        if playlist.ended {
+            return writer.finish();
+        }
+
+        // Reload after a target duration, or sooner if nothing new arrived
+        let target = Duration::from_secs_f64(playlist.target_duration.max(1.0));
+        let wait = if fetched > 0 { target } else { target / 2 };
+        if writer.wait(wait).is_err() {
+            return;
+        }
+
+        match fetch_playlist(&agent, &playlist_url) {
+            Ok(Playlist::Media(reloaded)) => {
+                playlist = reloaded;
+                reload_failures = 0;
+            }
+            Ok(Playlist::Master { .. }) => return writer.fail("Live playlist turned into a master playlist".to_string()),
+            Err(e) => {
+                reload_failures += 1;
+                log::warn!("Failed to reload HLS playlist ({}): {}", reload_failures, e);
+                if reload_failures >= MAX_RELOAD_FAILURES {
+                    return writer.fail(e);
+                }
+                // Keep the old playlist; nothing past `next_sequence` will be refetched
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{serve_bytes, temp_dir, TestServer};
+    use std::path::Path;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Arc;
+
+    const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/hls");
+
+    fn fixture(name: &str) -> String {
+        std::fs::read_to_string(Path::new(FIXTURES).join(name)).unwrap()
+    }
+
+    /// Wrap `payload` in a PAT, a PMT announcing ADTS AAC, and PES packets
+    fn ts_segment(payload: &[u8]) -> Vec<u8> {
+        const PMT_PID: u16 = 0x1000;
+        const AUDIO_PID: u16 = 0x101;
+
+        fn packet(pid: u16, start: bool, counter: u8, body: &[u8]) -> Vec<u8> {
+            let mut packet = vec![TS_SYNC_BYTE, ((start as u8) << 6) | (pid >> 8) as u8, pid as u8];
+            let stuffing = TS_PACKET_SIZE - 4 - body.len();
+            if stuffing > 0 {
+                // Pad with an adaptation field
+                packet.push(0x30 | (counter & 0x0F));
+                packet.push((stuffing - 1) as u8);
+                if stuffing > 1 {
+                    packet.push(0x00);
+                    packet.extend(std::iter::repeat(0xFF).take(stuffing - 2));
+                }
+            } else {
+                packet.push(0x10 | (counter & 0x0F));
+            }
+            packet.extend_from_slice(body);
+            packet
+        }
+
+        let pat = [0x00, 0x00, 0xB0, 0x0D, 0x00, 0x01, 0xC1, 0x00, 0x00, 0x00, 0x01, 0xF0, 0x00, 0, 0, 0, 0];
+        let pmt = [
+            0x00, 0x02, 0xB0, 0x12, 0x00, 0x01, 0xC1, 0x00, 0x00, 0xE1, 0x01, 0xF0, 0x00, 0x0F, 0xE1, 0x01, 0xF0,
+            0x00, 0, 0, 0, 0,
+        ];
+
+        let mut segment = packet(0, true, 0, &pat);
+        segment.extend(packet(PMT_PID, true, 0, &pmt));
+
+        let mut pes = vec![0x00, 0x00, 0x01, 0xC0, 0x00, 0x00, 0x80, 0x80, 0x05, 0x21, 0x00, 0x01, 0x00, 0x01];
+        pes.extend_from_slice(payload);
+        for (counter, chunk) in pes.chunks(TS_PACKET_SIZE - 4).enumerate() {
+            segment.extend(packet(AUDIO_PID, counter == 0, counter as u8, chunk));
+        }
+        segment
+    }
+
+    fn base() -> Url {
+        Url::parse("http://example.com/radio/master.m3u8").unwrap()
+    }
+
+    #[test]
+    fn test_parse_master_playlist() {
+        let Playlist::Master { variants, .. } = parse_playlist(&fixture("master.m3u8"), &base()).unwrap() else {
+            panic!("expected master playlist");
+        };
+        assert_eq!(variants.len(), 2);
+        assert_eq!(variants[0].bandwidth, 64000);
+        assert_eq!(variants[0].uri.as_str(), "http://example.com/radio/low/index.m3u8");
+        assert_eq!(variants[1].codecs.as_deref(), Some("mp4a.40.2"));
+
+        assert_eq!(select_variant(&variants, None).unwrap().bandwidth, 128000);
+        assert_eq!(select_variant(&variants, Some(100000)).unwrap().bandwidth, 64000);
+        assert_eq!(select_variant(&variants, Some(1000)).unwrap().bandwidth, 64000);
+    }
+
+    #[test]
+    fn test_audio_rendition_group() {
+        let text = "#EXTM3U\n\
+            #EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID=\"aac\",NAME=\"Commentary\",URI=\"commentary.m3u8\"\n\
+            #EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID=\"aac\",NAME=\"Main\",DEFAULT=YES,URI=\"main.m3u8\"\n\
+            #EXT-X-STREAM-INF:BANDWIDTH=96000,CODECS=\"mp4a.40.2,avc1.4d401e\",AUDIO=\"aac\"\n\
+            video.m3u8\n";
+        let Playlist::Master { variants, renditions } = parse_playlist(text, &base()).unwrap() else {
+            panic!("expected master playlist");
+        };
+        assert_eq!(variants[0].codecs.as_deref(), Some("mp4a.40.2,avc1.4d401e"));
+        let rendition = audio_rendition(&variants[0], &renditions).unwrap();
+        assert_eq!(rendition.uri.as_str(), "http://example.com/radio/main.m3u8");
+    }
+
+    #[test]
+    fn test_parse_media_playlist() {
+        let Playlist::Media(media) = parse_playlist(&fixture("fmp4/index.m3u8"), &base()).unwrap() else {
+            panic!("expected media playlist");
+        };
+        assert!(media.ended);
+        assert_eq!(media.target_duration, 6.0);
+        assert_eq!(media.init_segment.as_ref().unwrap().as_str(), "http://example.com/radio/init.mp4");
+        assert_eq!(media.segments.iter().map(|s| s.sequence).collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(media.duration(), Some(12.0));
+
+        let encrypted = "#EXTM3U\n#EXT-X-KEY:METHOD=AES-128,URI=\"key\"\n#EXTINF:4,\na.ts\n";
+        assert!(parse_playlist(encrypted, &base()).is_err());
+    }
+
+    #[test]
+    fn test_demux_ts_and_strip_id3() {
+        let payload: Vec<u8> = (0..500u32).map(|i| (i % 251) as u8).collect();
+        let mut demuxer = SegmentDemuxer::default();
+        assert_eq!(demuxer.extract_audio(&ts_segment(&payload)).unwrap(), payload);
+
+        let mut packed = b"ID3\x04\x00\x00\x00\x00\x00\x02AB".to_vec();
+        packed.extend_from_slice(&[0xFF, 0xF1, 0x50]);
+        assert_eq!(demuxer.extract_audio(&packed).unwrap(), vec![0xFF, 0xF1, 0x50]);
+    }
+
+    #[test]
+    fn test_vod_plays_selected_variant() {
+        let dir = temp_dir("hls-vod");
+        for path in ["master.m3u8", "low/index.m3u8", "high/index.m3u8"] {
+            std::fs::create_dir_all(dir.join(path).parent().unwrap()).unwrap();
+            std::fs::write(dir.join(path), fixture(path)).unwrap();
+        }
+        let mut expected = Vec::new();
+        for (variant, fill) in [("low", 0x10u8), ("high", 0x20u8)] {
+            for index in 0..3u8 {
+                let payload = vec![fill + index; 300];
+                std::fs::write(dir.join(format!("{}/seg{}.ts", variant, index)), ts_segment(&payload)).unwrap();
+                if variant == "high" {
+                    expected.extend(payload);
+                }
+            }
+        }
+
+        let server = TestServer::serve_dir(&dir);
+        let (mut stream, duration) = open(&server.url("master.m3u8"), None).unwrap();
+        assert_eq!(duration, Some(24.5));
+
+        let mut audio = Vec::new();
+        stream.read_to_end(&mut audio).unwrap();
+        assert_eq!(audio, expected);
+    }
+
+    #[test]
+    fn test_fmp4_prepends_init_segment() {
+        let dir = temp_dir("hls-fmp4");
+        std::fs::write(dir.join("index.m3u8"), fixture("fmp4/index.m3u8")).unwrap();
+        std::fs::write(dir.join("init.mp4"), b"ftypmoov").unwrap();
+        std::fs::write(dir.join("frag1.m4s"), b"moofmdat1").unwrap();
+        std::fs::write(dir.join("frag2.m4s"), b"moofmdat2").unwrap();
+
+        let server = TestServer::serve_dir(&dir);
+        let (mut stream, _) = open(&server.url("index.m3u8"), None).unwrap();
+
+        let mut audio = Vec::new();
+        stream.read_to_end(&mut audio).unwrap();
+        assert_eq!(audio, b"ftypmoovmoofmdat1moofmdat2");
+    }
+
+    #[test]
+    fn test_live_playlist_starts_near_edge_and_reloads() {
+        // Each playlist request slides the six-segment window forward by one
+        let reloads = Arc::new(AtomicU64::new(0));
+        let counter = reloads.clone();
+        let server = TestServer::start(move |request, stream| {
+            if request.path.ends_with(".m3u8") {
+                let first = 10 + counter.fetch_add(1, Ordering::SeqCst);
+                let mut playlist = format!("#EXTM3U\n#EXT-X-TARGETDURATION:1\n#EXT-X-MEDIA-SEQUENCE:{}\n", first);
+                for sequence in first..first + 6 {
+                    playlist.push_str(&format!("#EXTINF:1.0,\nseg{}.aac\n", sequence));
+                }
+                serve_bytes(request, stream, playlist.as_bytes(), "application/vnd.apple.mpegurl");
+            } else {
+                let sequence: u8 = request.path.trim_start_matches("/seg").trim_end_matches(".aac").parse().unwrap();
+                serve_bytes(request, stream, &[sequence; 8], "audio/aac");
+            }
+        });
+
+        let (mut stream, duration) = open(&server.url("live.m3u8"), None).unwrap();
+        assert_eq!(duration, None);
+
+        let mut audio = [0u8; 32];
+        stream.read_exact(&mut audio).unwrap();
+        let sequences: Vec<u8> = audio.chunks(8).map(|chunk| chunk[0]).collect();
+        assert_eq!(sequences, vec![13, 14, 15, 16]);
+        assert!(reloads.load(Ordering::SeqCst) >= 2);
+    }
+
+    #[test]
+    fn test_is_hls_url() {
+        assert!(is_hls_url("https://example.com/live/stream.m3u8"));
+        assert!(is_hls_url("https://example.com/live/STREAM.M3U8?token=abc"));
+        assert!(!is_hls_url("https://example.com/live/stream.mp3"));
+    }
+}
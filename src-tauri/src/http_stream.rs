@@ -0,0 +1,743 @@
+// HTTP Stream Module
+// Exposes a remote http(s) resource as a `Read + Seek` source for the decoder.
+// A background thread fills a ring buffer ahead of the read position; seeks
+// outside the buffered window are served with HTTP range requests.
+// Shoutcast/Icecast servers are detected from their `icy-*` headers and
+// played as live streams that reconnect with backoff when dropped.
+
+use crate::errors::AudioEngineError;
+use crate::radio::{IcyMetadata, IcyReader};
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+
+/// How many bytes the fetcher keeps ahead of the read position
+const READ_AHEAD: usize = 2 * 1024 * 1024;
+
+/// How many already-read bytes are retained for short backward seeks
+/// (the decoder probe rewinds a little while sniffing the container)
+const BACK_BUFFER: usize = 256 * 1024;
+
+/// Forward seeks closer than this are satisfied by waiting for the
+/// fetcher rather than opening a new range request
+const SEEK_WAIT_WINDOW: u64 = 256 * 1024;
+
+const CHUNK_SIZE: usize = 16 * 1024;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const READ_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Live streams give up after this many consecutive failed reconnects
+const RECONNECT_ATTEMPTS: u32 = 8;
+const RECONNECT_INITIAL_DELAY: Duration = Duration::from_millis(500);
+const RECONNECT_MAX_DELAY: Duration = Duration::from_secs(30);
+
+/// Events raised by a stream while it plays
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum StreamEvent {
+    /// The station announced a new song
+    Metadata(IcyMetadata),
+    Reconnecting { attempt: u32, delay_ms: u64 },
+    Reconnected,
+}
+
+pub type StreamEventHandler = Arc<dyn Fn(StreamEvent) + Send + Sync>;
+
+/// Returns true if the string looks like a remote stream we can open
+pub fn is_remote_url(source: &str) -> bool {
+    let lower = source.trim_start().to_ascii_lowercase();
+    lower.starts_with("http://") || lower.starts_with("https://")
+}
+
+pub(crate) fn agent() -> ureq::Agent {
+    ureq::AgentBuilder::new()
+        .timeout_connect(CONNECT_TIMEOUT)
+        .timeout_read(READ_TIMEOUT)
+        .build()
+}
+
+struct Buffer {
+    data: VecDeque<u8>,
+    /// Absolute stream offset of `data[0]`
+    start: u64,
+    /// Absolute offset the reader will consume next
+    read_pos: u64,
+    eof: bool,
+    error: Option<String>,
+    /// Bumped on every restart so stale fetchers stop writing
+    generation: u64,
+}
+
+impl Buffer {
+    fn end(&self) -> u64 {
+        self.start + self.data.len() as u64
+    }
+
+    /// Drop data more than `BACK_BUFFER` behind the reader
+    fn trim(&mut self) {
+        let behind = self.read_pos.saturating_sub(self.start).saturating_sub(BACK_BUFFER as u64);
+        let excess = behind.min(self.data.len() as u64) as usize;
+        if excess > 0 {
+            self.data.drain(..excess);
+            self.start += excess as u64;
+        }
+    }
+}
+
+struct Shared {
+    buffer: Mutex<Buffer>,
+    cond: Condvar,
+    buffering: AtomicBool,
+    closed: AtomicBool,
+    content_length: Option<u64>,
+}
+
+/// Cheap handle for observing a stream after it has been handed to the decoder
+#[derive(Clone)]
+pub struct StreamStatus {
+    shared: Arc<Shared>,
+}
+
+impl StreamStatus {
+    /// True while the reader is waiting on the network
+    pub fn is_buffering(&self) -> bool {
+        self.shared.buffering.load(Ordering::SeqCst)
+    }
+
+    /// Fraction of the read-ahead window currently filled (0.0 to 1.0)
+    pub fn buffer_fill(&self) -> f32 {
+        let Ok(buffer) = self.shared.buffer.lock() else {
+            return 0.0;
+        };
+        if buffer.eof {
+            return 1.0;
+        }
+        let ahead = buffer.end().saturating_sub(buffer.read_pos);
+        (ahead as f32 / READ_AHEAD as f32).min(1.0)
+    }
+}
+
+pub struct HttpStream {
+    url: String,
+    agent: ureq::Agent,
+    shared: Arc<Shared>,
+    pos: u64,
+    accepts_ranges: bool,
+}
+
+impl HttpStream {
+    /// Connect to `url` and start filling the buffer in the background;
+    /// metadata and reconnects of live streams are reported to `on_event`
+    pub fn open(url: &str, on_event: Option<StreamEventHandler>) -> Result<Self, AudioEngineError> {
+        let agent = agent();
+        let response = agent
+            .get(url)
+            .set("Range", "bytes=0-")
+            .set("Icy-MetaData", "1")
+            .call()
+            .map_err(|e| AudioEngineError::StreamError(format!("Failed to open {}: {}", url, e)))?;
+
+        let is_live = response.header("icy-metaint").is_some() || response.header("icy-name").is_some();
+
+        let accepts_ranges = !is_live
+            && (response.status() == 206
+                || response
+                    .header("Accept-Ranges")
+                    .map(|value| value.eq_ignore_ascii_case("bytes"))
+                    .unwrap_or(false));
+
+        let content_length = if is_live {
+            None
+        } else {
+            response
+                .header("Content-Range")
+                .and_then(|value| value.rsplit('/').next())
+                .and_then(|total| total.trim().parse().ok())
+                .or_else(|| response.header("Content-Length").and_then(|value| value.trim().parse().ok()))
+        };
+
+        let live_agent = agent.clone();
+        let live_url = url.to_string();
+        Ok(Self::from_producer(url, agent, content_length, accepts_ranges, move |writer| {
+            if is_live {
+                run_live(live_agent, live_url, response, writer, on_event);
+            } else {
+                fill_buffer(response.into_reader(), 0, writer);
+            }
+        }))
+    }
+
+    /// Build a stream whose bytes are produced by `producer` on a background thread
+    pub(crate) fn from_producer<F>(
+        url: &str,
+        agent: ureq::Agent,
+        content_length: Option<u64>,
+        accepts_ranges: bool,
+        producer: F,
+    ) -> Self
+    where
+        F: FnOnce(BufferWriter) + Send + 'static,
+    {
+        let shared = Arc::new(Shared {
+            buffer: Mutex::new(Buffer {
+                data: VecDeque::new(),
+                start: 0,
+                read_pos: 0,
+                eof: false,
+                error: None,
+                generation: 0,
+            }),
+            cond: Condvar::new(),
+            buffering: AtomicBool::new(true),
+            closed: AtomicBool::new(false),
+            content_length,
+        });
+
+        let writer = BufferWriter::new(shared.clone(), 0);
+        std::thread::spawn(move || producer(writer));
+
+        Self {
+            url: url.to_string(),
+            agent,
+            shared,
+            pos: 0,
+            accepts_ranges,
+        }
+    }
+
+    pub fn status(&self) -> StreamStatus {
+        StreamStatus {
+            shared: self.shared.clone(),
+        }
+    }
+
+    /// Drop the buffer and fetch again from `offset` with a range request
+    fn restart(&self, offset: u64) -> io::Result<()> {
+        let generation = {
+            let mut buffer = self.lock()?;
+            buffer.generation += 1;
+            buffer.data.clear();
+            buffer.start = offset;
+            buffer.read_pos = offset;
+            buffer.eof = false;
+            buffer.error = None;
+            buffer.generation
+        };
+        self.shared.cond.notify_all();
+
+        let agent = self.agent.clone();
+        let url = self.url.clone();
+        let writer = BufferWriter::new(self.shared.clone(), generation);
+        std::thread::spawn(move || {
+            let request = agent.get(&url).set("Range", &format!("bytes={}-", offset));
+            match request.call() {
+                Ok(response) => {
+                    // A server that ignores the range sends the whole body
+                    let skip = if response.status() == 206 { 0 } else { offset };
+                    fill_buffer(response.into_reader(), skip, writer);
+                }
+                Err(e) => writer.fail(e.to_string()),
+            }
+        });
+
+        Ok(())
+    }
+
+    fn lock(&self) -> io::Result<std::sync::MutexGuard<'_, Buffer>> {
+        self.shared
+            .buffer
+            .lock()
+            .map_err(|e| io::Error::other(format!("Failed to lock stream buffer: {}", e)))
+    }
+}
+
+impl Read for HttpStream {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        if out.is_empty() {
+            return Ok(0);
+        }
+
+        let shared = self.shared.clone();
+        let lock_error = |e: std::sync::PoisonError<_>| {
+            io::Error::other(format!("Failed to lock stream buffer: {}", e))
+        };
+
+        let mut buffer = shared.buffer.lock().map_err(lock_error)?;
+        loop {
+            if self.pos < buffer.start {
+                // Data behind the back buffer has already been discarded
+                drop(buffer);
+                if !self.accepts_ranges {
+                    return Err(io::Error::new(io::ErrorKind::Unsupported, "Stream does not support seeking backwards"));
+                }
+                self.restart(self.pos)?;
+                buffer = shared.buffer.lock().map_err(lock_error)?;
+                continue;
+            }
+
+            if self.pos < buffer.end() {
+                let offset = (self.pos - buffer.start) as usize;
+                let available = buffer.data.len() - offset;
+                let count = available.min(out.len());
+                for (dst, src) in out.iter_mut().zip(buffer.data.range(offset..offset + count)) {
+                    *dst = *src;
+                }
+
+                self.pos += count as u64;
+                buffer.read_pos = self.pos;
+                buffer.trim();
+                shared.buffering.store(false, Ordering::SeqCst);
+                shared.cond.notify_all();
+                return Ok(count);
+            }
+
+            if let Some(error) = buffer.error.clone() {
+                shared.buffering.store(false, Ordering::SeqCst);
+                return Err(io::Error::other(error));
+            }
+            if buffer.eof {
+                shared.buffering.store(false, Ordering::SeqCst);
+                return Ok(0);
+            }
+
+            shared.buffering.store(true, Ordering::SeqCst);
+            let (guard, timeout) = shared
+                .cond
+                .wait_timeout(buffer, READ_TIMEOUT)
+                .map_err(|e| io::Error::other(format!("Failed to wait for stream data: {}", e)))?;
+            buffer = guard;
+            if timeout.timed_out() && self.pos >= buffer.end() && !buffer.eof {
+                return Err(io::Error::new(io::ErrorKind::TimedOut, "Timed out waiting for stream data"));
+            }
+        }
+    }
+}
+
+impl Seek for HttpStream {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(offset) => offset as i64,
+            SeekFrom::Current(delta) => self.pos as i64 + delta,
+            SeekFrom::End(delta) => {
+                let length = self.shared.content_length.ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::Unsupported, "Stream length is unknown")
+                })?;
+                length as i64 + delta
+            }
+        };
+        if target < 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "Seek before start of stream"));
+        }
+        let target = target as u64;
+
+        let (start, end) = {
+            let buffer = self.lock()?;
+            (buffer.start, buffer.end())
+        };
+
+        let within_window = target >= start && target <= end + SEEK_WAIT_WINDOW;
+        if !within_window {
+            if self.accepts_ranges {
+                self.restart(target)?;
+            } else if target < start {
+                return Err(io::Error::new(io::ErrorKind::Unsupported, "Stream does not support seeking backwards"));
+            }
+            // Without range support a far forward seek just reads through
+        }
+
+        // Move the read-ahead window along, so a producer waiting for the
+        // reader fills up to the new position instead of stalling
+        self.lock()?.read_pos = target;
+        self.pos = target;
+        self.shared.cond.notify_all();
+        Ok(target)
+    }
+}
+
+impl Drop for HttpStream {
+    fn drop(&mut self) {
+        self.shared.closed.store(true, Ordering::SeqCst);
+        if let Ok(mut buffer) = self.shared.buffer.lock() {
+            buffer.generation += 1;
+        }
+        self.shared.cond.notify_all();
+    }
+}
+
+/// The stream was dropped or restarted; producers should stop
+#[derive(Debug)]
+pub(crate) struct Cancelled;
+
+/// Producer side of the stream buffer
+pub(crate) struct BufferWriter {
+    shared: Arc<Shared>,
+    generation: u64,
+}
+
+impl BufferWriter {
+    fn new(shared: Arc<Shared>, generation: u64) -> Self {
+        Self { shared, generation }
+    }
+
+    fn lock(&self) -> Result<std::sync::MutexGuard<'_, Buffer>, Cancelled> {
+        let buffer = self.shared.buffer.lock().map_err(|_| Cancelled)?;
+        if self.shared.closed.load(Ordering::SeqCst) || buffer.generation != self.generation {
+            return Err(Cancelled);
+        }
+        Ok(buffer)
+    }
+
+    /// Append bytes, blocking while the read-ahead window is full
+    pub fn write(&self, data: &[u8]) -> Result<(), Cancelled> {
+        let mut buffer = self.lock()?;
+        buffer.data.extend(data);
+        // Data the reader seeked past is never read
+        buffer.trim();
+        self.shared.cond.notify_all();
+
+        // Wait for the reader to consume before producing more
+        while buffer.end().saturating_sub(buffer.read_pos) >= READ_AHEAD as u64 {
+            buffer = self.shared.cond.wait(buffer).map_err(|_| Cancelled)?;
+            if self.shared.closed.load(Ordering::SeqCst) || buffer.generation != self.generation {
+                return Err(Cancelled);
+            }
+        }
+        Ok(())
+    }
+
+    /// Mark the end of the stream
+    pub fn finish(&self) {
+        if let Ok(mut buffer) = self.lock() {
+            buffer.eof = true;
+        }
+        self.shared.cond.notify_all();
+    }
+
+    /// Surface an error to the reader
+    pub fn fail(&self, error: String) {
+        if let Ok(mut buffer) = self.lock() {
+            log::warn!("Stream fetch failed: {}", error);
+            buffer.error = Some(error);
+        }
+        self.shared.cond.notify_all();
+    }
+
+    /// Sleep for `duration`, waking early if the stream is dropped
+    pub fn wait(&self, duration: Duration) -> Result<(), Cancelled> {
+        let buffer = self.lock()?;
+        let _ = self
+            .shared
+            .cond
+            .wait_timeout_while(buffer, duration, |_| !self.shared.closed.load(Ordering::SeqCst))
+            .map_err(|_| Cancelled)?;
+        if self.shared.closed.load(Ordering::SeqCst) {
+            return Err(Cancelled);
+        }
+        Ok(())
+    }
+}
+
+enum PumpOutcome {
+    Finished,
+    Failed(String),
+    Cancelled,
+}
+
+/// Copy the response body into the shared buffer
+fn fill_buffer(reader: Box<dyn Read + Send + Sync>, skip: u64, writer: BufferWriter) {
+    match pump(reader, skip, &writer) {
+        (PumpOutcome::Finished, _) => writer.finish(),
+        (PumpOutcome::Failed(error), _) => writer.fail(error),
+        (PumpOutcome::Cancelled, _) => {}
+    }
+}
+
+/// Move bytes from `reader` into the buffer until it ends; also returns
+/// how many bytes were delivered
+fn pump(mut reader: Box<dyn Read + Send + Sync>, mut skip: u64, writer: &BufferWriter) -> (PumpOutcome, u64) {
+    let mut chunk = vec![0u8; CHUNK_SIZE];
+    let mut delivered = 0;
+
+    loop {
+        let count = match reader.read(&mut chunk) {
+            Ok(0) => return (PumpOutcome::Finished, delivered),
+            Ok(count) => count,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return (PumpOutcome::Failed(e.to_string()), delivered),
+        };
+
+        let mut data = &chunk[..count];
+        if skip > 0 {
+            let skipped = (skip as usize).min(data.len());
+            skip -= skipped as u64;
+            data = &data[skipped..];
+        }
+        if writer.write(data).is_err() {
+            return (PumpOutcome::Cancelled, delivered);
+        }
+        delivered += data.len() as u64;
+    }
+}
+
+/// Wrap a live response so in-band ICY metadata is reported and stripped
+fn live_reader(response: ureq::Response, on_event: Option<StreamEventHandler>) -> Box<dyn Read + Send + Sync> {
+    let metaint = response
+        .header("icy-metaint")
+        .and_then(|value| value.trim().parse::<usize>().ok())
+        .filter(|&metaint| metaint > 0);
+    let reader = response.into_reader();
+
+    match metaint {
+        Some(metaint) => Box::new(IcyReader::new(reader, metaint, move |metadata| {
+            log::info!("Stream title: {:?}", metadata.stream_title);
+            if let Some(on_event) = &on_event {
+                on_event(StreamEvent::Metadata(metadata));
+            }
+        })),
+        None => reader,
+    }
+}
+
+/// Exponential backoff for the given (1-based) reconnect attempt
+pub(crate) fn reconnect_delay(attempt: u32) -> Duration {
+    RECONNECT_INITIAL_DELAY
+        .saturating_mul(1 << attempt.saturating_sub(1).min(16))
+        .min(RECONNECT_MAX_DELAY)
+}
+
+/// Feed a live stream into the buffer, reconnecting with exponential
+/// backoff whenever the server drops the connection
+fn run_live(
+    agent: ureq::Agent,
+    url: String,
+    first: ureq::Response,
+    writer: BufferWriter,
+    on_event: Option<StreamEventHandler>,
+) {
+    let emit = |event: StreamEvent| {
+        if let Some(on_event) = &on_event {
+            on_event(event);
+        }
+    };
+
+    let mut response = Some(first);
+    let mut attempt = 0;
+
+    loop {
+        if let Some(current) = response.take() {
+            let (outcome, delivered) = pump(live_reader(current, on_event.clone()), 0, &writer);
+            match outcome {
+                PumpOutcome::Cancelled => return,
+                PumpOutcome::Finished => log::warn!("Live stream {} ended", url),
+                PumpOutcome::Failed(error) => log::warn!("Live stream {} dropped: {}", url, error),
+            }
+            if delivered > 0 {
+                attempt = 0;
+            }
+        }
+
+        attempt += 1;
+        if attempt > RECONNECT_ATTEMPTS {
+            return writer.fail(format!("Gave up reconnecting to {}", url));
+        }
+
+        let delay = reconnect_delay(attempt);
+        emit(StreamEvent::Reconnecting {
+            attempt,
+            delay_ms: delay.as_millis() as u64,
+        });
+        if writer.wait(delay).is_err() {
+            return;
+        }
+
+        match agent.get(&url).set("Icy-MetaData", "1").call() {
+            Ok(next) => {
+                log::info!("Reconnected to {}", url);
+                emit(StreamEvent::Reconnected);
+                response = Some(next);
+            }
+            Err(e) => log::warn!("Reconnect to {} failed: {}", url, e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{serve_bytes, sine_wav, TestServer};
+
+    fn fixture() -> Vec<u8> {
+        sine_wav(1.0, 8000, 440.0)
+    }
+
+    #[test]
+    fn test_reads_whole_file() {
+        let bytes = fixture();
+        let served = bytes.clone();
+        let server = TestServer::start(move |request, stream| serve_bytes(request, stream, &served, "audio/wav"));
+
+        let mut stream = HttpStream::open(&server.url("tone.wav"), None).unwrap();
+        assert!(stream.accepts_ranges);
+        assert_eq!(stream.shared.content_length, Some(bytes.len() as u64));
+
+        let mut received = Vec::new();
+        stream.read_to_end(&mut received).unwrap();
+        assert_eq!(received, bytes);
+        assert!(!stream.status().is_buffering());
+    }
+
+    #[test]
+    fn test_seek_uses_range_requests() {
+        let bytes = fixture();
+        let served = bytes.clone();
+        let server = TestServer::start(move |request, stream| serve_bytes(request, stream, &served, "audio/wav"));
+
+        let mut stream = HttpStream::open(&server.url("tone.wav"), None).unwrap();
+
+        let mut tail = [0u8; 16];
+        stream.seek(SeekFrom::End(-16)).unwrap();
+        stream.read_exact(&mut tail).unwrap();
+        assert_eq!(&tail[..], &bytes[bytes.len() - 16..]);
+
+        let mut header = [0u8; 4];
+        stream.seek(SeekFrom::Start(0)).unwrap();
+        stream.read_exact(&mut header).unwrap();
+        assert_eq!(&header, b"RIFF");
+    }
+
+    #[test]
+    fn test_far_seek_without_ranges_reads_through() {
+        use std::io::Write;
+
+        let bytes: Vec<u8> = (0..READ_AHEAD + 1024 * 1024).map(|i| (i % 251) as u8).collect();
+        let served = bytes.clone();
+        let server = TestServer::start(move |_, stream| {
+            let header = format!("HTTP/1.0 200 OK\r\nContent-Type: audio/mpeg\r\nContent-Length: {}\r\n\r\n", served.len());
+            let _ = stream.write_all(header.as_bytes());
+            let _ = stream.write_all(&served);
+        });
+
+        let mut stream = HttpStream::open(&server.url("long.mp3"), None).unwrap();
+        assert!(!stream.accepts_ranges);
+
+        // Wait for the producer to fill the read-ahead window and block
+        while stream.status().buffer_fill() < 1.0 {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        let target = READ_AHEAD + 512 * 1024;
+        stream.seek(SeekFrom::Start(target as u64)).unwrap();
+        let mut chunk = [0u8; 8];
+        stream.read_exact(&mut chunk).unwrap();
+        assert_eq!(&chunk[..], &bytes[target..target + 8]);
+        assert!(stream.shared.buffer.lock().unwrap().data.len() <= BACK_BUFFER + READ_AHEAD + CHUNK_SIZE);
+    }
+
+    #[test]
+    fn test_decodes_remote_wav() {
+        let bytes = fixture();
+        let server = TestServer::start(move |request, stream| serve_bytes(request, stream, &bytes, "audio/wav"));
+
+        let stream = HttpStream::open(&server.url("tone.wav"), None).unwrap();
+        let mss = symphonia::core::io::MediaSourceStream::new(
+            Box::new(symphonia::core::io::ReadOnlySource::new(stream)),
+            Default::default(),
+        );
+        let mut hint = symphonia::core::probe::Hint::new();
+        hint.with_extension("wav");
+        let probed = symphonia::default::get_probe()
+            .format(&hint, mss, &Default::default(), &Default::default())
+            .unwrap();
+        let track = probed.format.default_track().unwrap();
+        assert_eq!(track.codec_params.sample_rate, Some(8000));
+        assert_eq!(track.codec_params.n_frames, Some(8000));
+    }
+
+    /// Icecast-style server sending `blocks` audio blocks per connection,
+    /// each followed by a title, then dropping the connection
+    fn mock_icecast(blocks: usize) -> (TestServer, Arc<std::sync::atomic::AtomicUsize>) {
+        use std::io::Write;
+
+        let connections = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counter = connections.clone();
+        let server = TestServer::start(move |request, stream| {
+            assert_eq!(request.header("Icy-MetaData"), Some("1"));
+            let connection = counter.fetch_add(1, Ordering::SeqCst) + 1;
+
+            let mut body = b"HTTP/1.0 200 OK\r\nContent-Type: audio/mpeg\r\nicy-name: Test FM\r\nicy-metaint: 16\r\n\r\n".to_vec();
+            for block in 0..blocks {
+                body.extend_from_slice(&[0xAA; 16]);
+                let mut meta = format!("StreamTitle='Song {}-{}';", connection, block).into_bytes();
+                let padded = meta.len().div_ceil(16) * 16;
+                meta.resize(padded, 0);
+                body.push((padded / 16) as u8);
+                body.extend_from_slice(&meta);
+            }
+            let _ = stream.write_all(&body);
+        });
+        (server, connections)
+    }
+
+    #[test]
+    fn test_icy_metadata_is_stripped_and_reported() {
+        let (server, _) = mock_icecast(3);
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = events.clone();
+        let handler: StreamEventHandler = Arc::new(move |event| sink.lock().unwrap().push(event));
+
+        let mut stream = HttpStream::open(&server.url("live"), Some(handler)).unwrap();
+        assert!(!stream.accepts_ranges);
+        assert_eq!(stream.shared.content_length, None);
+
+        let mut audio = [0u8; 48];
+        stream.read_exact(&mut audio).unwrap();
+        assert!(audio.iter().all(|&b| b == 0xAA));
+
+        let titles: Vec<String> = events
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|event| match event {
+                StreamEvent::Metadata(metadata) => metadata.stream_title.clone(),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(titles[..2], ["Song 1-0".to_string(), "Song 1-1".to_string()]);
+    }
+
+    #[test]
+    fn test_live_stream_reconnects() {
+        let (server, connections) = mock_icecast(2);
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = events.clone();
+        let handler: StreamEventHandler = Arc::new(move |event| sink.lock().unwrap().push(event));
+
+        let mut stream = HttpStream::open(&server.url("live"), Some(handler)).unwrap();
+
+        // Two connections' worth of audio forces at least one reconnect
+        let mut audio = [0u8; 64];
+        stream.read_exact(&mut audio).unwrap();
+        assert!(audio.iter().all(|&b| b == 0xAA));
+        assert!(connections.load(Ordering::SeqCst) >= 2);
+
+        let events = events.lock().unwrap();
+        assert!(events
+            .iter()
+            .any(|event| matches!(event, StreamEvent::Reconnecting { attempt: 1, delay_ms: 500 })));
+        assert!(events.iter().any(|event| matches!(event, StreamEvent::Reconnected)));
+        assert!(events.iter().any(|event| matches!(
+            event,
+            StreamEvent::Metadata(IcyMetadata { stream_title: Some(title), .. }) if title == "Song 2-0"
+        )));
+    }
+
+    #[test]
+    fn test_is_remote_url() {
+        assert!(is_remote_url("http://example.com/stream.mp3"));
+        assert!(is_remote_url("HTTPS://example.com/a.flac"));
+        assert!(!is_remote_url("/storage/emulated/0/Music/a.mp3"));
+        assert!(!is_remote_url("file:///tmp/a.mp3"));
+    }
+}
@@ -1,9 +1,35 @@
 // Module declarations
+mod browse;
+mod chapters;
+mod cover_art;
+mod cue;
+mod duplicates;
 mod errors;
 mod file_manager;
+mod history;
 mod audio_engine;
+mod hls;
+mod http_stream;
+mod library;
+mod lyrics;
 mod media_service;
 mod permissions;
+mod playlist_files;
+mod playlists;
+mod radio;
+mod scanner;
+mod search;
+mod session;
+mod smart_playlists;
+mod stats;
+mod sleep_timer;
+mod tag_writer;
+mod watcher;
+
+#[cfg(test)]
+mod test_support;
+
+use tauri::Manager;
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
@@ -18,6 +44,9 @@ pub fn run() {
     .manage(audio_engine::AudioEngineState {
       engine: std::sync::Arc::new(std::sync::Mutex::new(audio_engine)),
     })
+    .manage(sleep_timer::SleepTimerState::default())
+    .manage(scanner::ScanState::default())
+    .manage(smart_playlists::SmartPlaylistState::default())
     .invoke_handler(tauri::generate_handler![
       permissions::request_permissions,
       permissions::get_permission_status,
@@ -25,15 +54,86 @@ pub fn run() {
       file_manager::pick_audio_folder,
       file_manager::get_metadata,
       file_manager::get_multiple_metadata,
+      cover_art::get_cover_art,
+      lyrics::get_lyrics,
+      library::library_scan_folder,
+      library::library_rescan,
+      library::library_remove_folder,
+      library::library_get_folders,
+      library::library_get_tracks,
+      library::library_get_track,
+      library::library_get_artists,
+      library::library_get_albums,
+      browse::library_browse_artists,
+      browse::library_browse_albums,
+      browse::library_browse_genres,
+      browse::library_browse_decades,
+      browse::library_browse_folder,
+      search::search_library,
+      stats::get_track_stats,
+      stats::set_rating,
+      stats::get_top_played,
+      history::get_history,
+      history::export_history,
+      scanner::library_cancel_scan,
+      duplicates::find_duplicates,
+      tag_writer::write_tags,
+      playlist_files::playlist_import,
+      playlist_files::playlist_export,
+      playlists::playlist_list,
+      playlists::playlist_get_items,
+      playlists::playlist_create,
+      playlists::playlist_rename,
+      playlists::playlist_delete,
+      playlists::playlist_duplicate,
+      playlists::playlist_add_tracks,
+      playlists::playlist_remove_tracks,
+      playlists::playlist_move_tracks,
+      smart_playlists::smart_playlist_list,
+      smart_playlists::smart_playlist_create,
+      smart_playlists::smart_playlist_update,
+      smart_playlists::smart_playlist_delete,
+      smart_playlists::smart_playlist_get_tracks,
+      smart_playlists::smart_playlist_preview,
       audio_engine::audio_load_track,
       audio_engine::audio_play,
       audio_engine::audio_pause,
       audio_engine::audio_stop,
+      audio_engine::audio_seek,
+      audio_engine::audio_next_chapter,
+      audio_engine::audio_previous_chapter,
+      audio_engine::audio_seek_chapter,
       audio_engine::audio_get_state,
       audio_engine::audio_set_volume,
+      audio_engine::audio_set_max_stream_bandwidth,
       audio_engine::audio_check_finished,
+      radio::radio_get_stations,
+      radio::radio_save_station,
+      radio::radio_remove_station,
+      session::session_get,
+      session::session_set_queue,
+      session::session_set_auto_resume,
+      sleep_timer::sleep_timer_start,
+      sleep_timer::sleep_timer_cancel,
+      sleep_timer::sleep_timer_extend,
+      sleep_timer::sleep_timer_get_status,
     ])
     .setup(|app| {
+      let data_dir = app.path().app_data_dir()?;
+      let library = library::LibraryState::new(&data_dir.join("library.db"))?;
+      app.manage(watcher::start(app.handle(), library.library.clone()));
+      app.manage(library);
+      app.manage(cover_art::CoverArtState {
+        service: std::sync::Arc::new(cover_art::CoverArtService::new(
+          &app.path().app_cache_dir()?.join("covers"),
+        )),
+      });
+      app.manage(radio::RadioState::new(&data_dir.join("stations.json"))?);
+      app.manage(session::SessionState::new(&data_dir.join("session.json")));
+      session::start(app.handle());
+      lyrics::start(app.handle());
+      stats::start(app.handle());
+      
       if cfg!(debug_assertions) {
         app.handle().plugin(
           tauri_plugin_log::Builder::default()
@@ -0,0 +1,1453 @@
+// Library Module
+// Persistent SQLite store of tracks, albums, artists and library folders
+
+use crate::cue;
+use crate::errors::LibraryError;
+use crate::file_manager::{FileManager, TrackMetadata};
+use crate::search;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Schema migrations, applied in order and tracked with `PRAGMA user_version`
+const MIGRATIONS: &[&str] = &[
+    "CREATE TABLE folders (
+        id INTEGER PRIMARY KEY,
+        path TEXT NOT NULL UNIQUE,
+        date_added INTEGER NOT NULL
+    );
+    CREATE TABLE artists (
+        id INTEGER PRIMARY KEY,
+        name TEXT NOT NULL UNIQUE
+    );
+    CREATE TABLE albums (
+        id INTEGER PRIMARY KEY,
+        title TEXT NOT NULL,
+        artist_id INTEGER NOT NULL REFERENCES artists(id),
+        UNIQUE (title, artist_id)
+    );
+    CREATE TABLE tracks (
+        id TEXT PRIMARY KEY,
+        file_path TEXT NOT NULL UNIQUE,
+        title TEXT NOT NULL,
+        artist_id INTEGER NOT NULL REFERENCES artists(id),
+        album_id INTEGER REFERENCES albums(id),
+        folder_id INTEGER REFERENCES folders(id) ON DELETE SET NULL,
+        duration REAL NOT NULL,
+        date_added INTEGER NOT NULL
+    );
+    CREATE INDEX tracks_artist ON tracks(artist_id);
+    CREATE INDEX tracks_album ON tracks(album_id);
+    CREATE INDEX tracks_folder ON tracks(folder_id);",
+    // Size and modification time let rescans skip unchanged files
+    "ALTER TABLE tracks ADD COLUMN file_size INTEGER NOT NULL DEFAULT 0;
+    ALTER TABLE tracks ADD COLUMN modified INTEGER NOT NULL DEFAULT 0;",
+    // Full tag and technical metadata; clearing the stamps makes the next rescan fill it in
+    "ALTER TABLE tracks ADD COLUMN album_artist TEXT;
+    ALTER TABLE tracks ADD COLUMN track_number INTEGER;
+    ALTER TABLE tracks ADD COLUMN track_total INTEGER;
+    ALTER TABLE tracks ADD COLUMN disc_number INTEGER;
+    ALTER TABLE tracks ADD COLUMN disc_total INTEGER;
+    ALTER TABLE tracks ADD COLUMN year INTEGER;
+    ALTER TABLE tracks ADD COLUMN date TEXT;
+    ALTER TABLE tracks ADD COLUMN genre TEXT;
+    ALTER TABLE tracks ADD COLUMN composer TEXT;
+    ALTER TABLE tracks ADD COLUMN comment TEXT;
+    ALTER TABLE tracks ADD COLUMN bpm INTEGER;
+    ALTER TABLE tracks ADD COLUMN compilation INTEGER;
+    ALTER TABLE tracks ADD COLUMN musicbrainz_track_id TEXT;
+    ALTER TABLE tracks ADD COLUMN musicbrainz_album_id TEXT;
+    ALTER TABLE tracks ADD COLUMN musicbrainz_artist_id TEXT;
+    ALTER TABLE tracks ADD COLUMN musicbrainz_album_artist_id TEXT;
+    ALTER TABLE tracks ADD COLUMN codec TEXT;
+    ALTER TABLE tracks ADD COLUMN sample_rate INTEGER;
+    ALTER TABLE tracks ADD COLUMN bit_depth INTEGER;
+    ALTER TABLE tracks ADD COLUMN channels INTEGER;
+    ALTER TABLE tracks ADD COLUMN bitrate INTEGER;
+    CREATE INDEX tracks_genre ON tracks(genre);
+    CREATE INDEX tracks_year ON tracks(year);
+    UPDATE tracks SET file_size = 0, modified = 0;",
+    // Per-folder scan option for files without an extension
+    "ALTER TABLE folders ADD COLUMN include_extensionless INTEGER NOT NULL DEFAULT 0;",
+    // Cached acoustic signature for duplicate detection, cleared whenever the file changes
+    "ALTER TABLE tracks ADD COLUMN acoustic_fingerprint BLOB;",
+    // User playlists; entries keep their track ID even while the track is out of the library
+    "CREATE TABLE playlists (
+        id INTEGER PRIMARY KEY,
+        name TEXT NOT NULL,
+        created INTEGER NOT NULL,
+        modified INTEGER NOT NULL
+    );
+    CREATE TABLE playlist_tracks (
+        playlist_id INTEGER NOT NULL REFERENCES playlists(id) ON DELETE CASCADE,
+        position INTEGER NOT NULL,
+        track_id TEXT NOT NULL,
+        PRIMARY KEY (playlist_id, position)
+    );
+    CREATE INDEX playlist_tracks_track ON playlist_tracks(track_id);",
+    // Listening statistics kept by the player, and smart playlist definitions (JSON rules)
+    "ALTER TABLE tracks ADD COLUMN rating INTEGER;
+    ALTER TABLE tracks ADD COLUMN play_count INTEGER NOT NULL DEFAULT 0;
+    ALTER TABLE tracks ADD COLUMN skip_count INTEGER NOT NULL DEFAULT 0;
+    ALTER TABLE tracks ADD COLUMN last_played INTEGER;
+    CREATE TABLE smart_playlists (
+        id INTEGER PRIMARY KEY,
+        name TEXT NOT NULL,
+        query TEXT NOT NULL,
+        created INTEGER NOT NULL,
+        modified INTEGER NOT NULL
+    );",
+    // Span of the file covered by a CUE sheet track
+    "ALTER TABLE tracks ADD COLUMN start_time REAL;
+    ALTER TABLE tracks ADD COLUMN end_time REAL;",
+    // Chapter list as JSON; existing rows pick it up on their next change
+    "ALTER TABLE tracks ADD COLUMN chapters TEXT;",
+    // Folded search tokens per field, see `search::index_track`
+    "CREATE TABLE search_index (
+        track_id TEXT PRIMARY KEY REFERENCES tracks(id) ON DELETE CASCADE ON UPDATE CASCADE,
+        title TEXT NOT NULL,
+        artist TEXT NOT NULL,
+        album TEXT NOT NULL,
+        path TEXT NOT NULL
+    );",
+    // One row per counted play, for play counts over a date range
+    "CREATE TABLE plays (
+        id INTEGER PRIMARY KEY,
+        track_id TEXT NOT NULL REFERENCES tracks(id) ON DELETE CASCADE ON UPDATE CASCADE,
+        played_at INTEGER NOT NULL
+    );
+    CREATE INDEX plays_played_at ON plays(played_at);",
+    // Every listen, with the track's names copied so entries outlive the track;
+    // counted plays carry over from `plays`
+    "CREATE TABLE history (
+        id INTEGER PRIMARY KEY,
+        track_id TEXT NOT NULL,
+        title TEXT NOT NULL,
+        artist TEXT NOT NULL,
+        album TEXT,
+        started_at INTEGER NOT NULL,
+        listened REAL NOT NULL,
+        source TEXT,
+        completed INTEGER NOT NULL,
+        skipped INTEGER NOT NULL
+    );
+    INSERT INTO history (track_id, title, artist, album, started_at, listened, completed, skipped)
+        SELECT p.track_id, t.title, ar.name, al.title, p.played_at, 0, 1, 0
+        FROM plays p
+        JOIN tracks t ON t.id = p.track_id
+        JOIN artists ar ON ar.id = t.artist_id
+        LEFT JOIN albums al ON al.id = t.album_id
+        ORDER BY p.id;
+    DROP TABLE plays;
+    CREATE INDEX history_started_at ON history(started_at);
+    CREATE INDEX history_track ON history(track_id);",
+    // One row per indexed token, so a search only scores tracks that can match;
+    // emptying the index has it rebuilt, tokens included, when the library opens
+    "CREATE TABLE search_tokens (
+        token TEXT NOT NULL,
+        track_id TEXT NOT NULL REFERENCES tracks(id) ON DELETE CASCADE ON UPDATE CASCADE,
+        PRIMARY KEY (token, track_id)
+    ) WITHOUT ROWID;
+    CREATE INDEX search_tokens_track ON search_tokens(track_id);
+    DELETE FROM search_index;",
+];
+
+pub(crate) const TRACK_COLUMNS: &str = "t.id, t.title, ar.name, al.title, t.duration, t.file_path,
+    t.album_artist, t.track_number, t.track_total, t.disc_number, t.disc_total, t.year, t.date,
+    t.genre, t.composer, t.comment, t.bpm, t.compilation, t.musicbrainz_track_id,
+    t.musicbrainz_album_id, t.musicbrainz_artist_id, t.musicbrainz_album_artist_id,
+    t.codec, t.sample_rate, t.bit_depth, t.channels, t.bitrate, t.file_size, t.start_time, t.end_time, t.chapters
+    FROM tracks t
+    JOIN artists ar ON ar.id = t.artist_id
+    LEFT JOIN albums al ON al.id = t.album_id";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LibraryFolder {
+    pub id: i64,
+    pub path: String,
+    pub date_added: i64,
+    pub track_count: u32,
+    /// Content-sniff files that have no extension at all while scanning
+    pub include_extensionless: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Artist {
+    pub id: i64,
+    pub name: String,
+    pub album_count: u32,
+    pub track_count: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Album {
+    pub id: i64,
+    pub title: String,
+    pub artist: String,
+    pub track_count: u32,
+    pub duration: f64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrackSort {
+    #[default]
+    Title,
+    Artist,
+    Album,
+    DateAdded,
+}
+
+/// Filters and paging for `Library::tracks`; every field is optional
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TrackQuery {
+    pub artist_id: Option<i64>,
+    pub album_id: Option<i64>,
+    pub folder_id: Option<i64>,
+    /// Matched without regard to case
+    pub genre: Option<String>,
+    /// First year of the decade, e.g. 1980
+    pub decade: Option<i32>,
+    pub sort: TrackSort,
+    pub limit: Option<u32>,
+    pub offset: Option<u32>,
+}
+
+/// What a folder scan changed in the library
+#[derive(Debug, Clone, Serialize)]
+pub struct ScanSummary {
+    pub folder: LibraryFolder,
+    pub added: u32,
+    pub updated: u32,
+    /// Known tracks found again under a new path
+    pub moved: u32,
+    pub removed: u32,
+    pub unchanged: u32,
+    /// The scan was stopped before every changed file was probed
+    pub cancelled: bool,
+}
+
+/// Files a rescan has to probe, from `Library::plan_rescan`
+#[derive(Debug, Clone)]
+pub struct RescanPlan {
+    pub root: PathBuf,
+    pub to_probe: Vec<(PathBuf, FileStamp)>,
+    pub missing: Vec<String>,
+    pub unchanged: u32,
+}
+
+/// Changed files the watcher has to probe, from `Library::plan_changes`
+#[derive(Debug, Clone, Default)]
+pub struct ChangePlan {
+    pub to_probe: Vec<(PathBuf, FileStamp)>,
+    pub gone: Vec<String>,
+}
+
+/// Track IDs touched by a batch of filesystem changes
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct LibraryChanges {
+    pub added: Vec<String>,
+    pub updated: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+impl LibraryChanges {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.updated.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// File size and modification time (ms since the epoch) as last seen by a scan
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FileStamp {
+    pub size: i64,
+    pub modified: i64,
+}
+
+fn modified_millis(metadata: &std::fs::Metadata) -> std::io::Result<i64> {
+    Ok(metadata.modified()?
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or(0))
+}
+
+impl FileStamp {
+    /// Stamp of the file behind `path`. A CUE track also counts the sheets
+    /// next to it, so editing a sheet gets its tracks probed again.
+    pub fn of(path: &Path) -> std::io::Result<Self> {
+        let (audio_path, cue_number) = cue::split_virtual_path(&path.to_string_lossy());
+        let metadata = std::fs::metadata(&audio_path)?;
+        let mut modified = modified_millis(&metadata)?;
+        if cue_number.is_some() {
+            for sheet in audio_path.parent().map(cue::sheets_in).unwrap_or_default() {
+                if let Ok(sheet_modified) = std::fs::metadata(&sheet).and_then(|m| modified_millis(&m)) {
+                    modified = modified.max(sheet_modified);
+                }
+            }
+        }
+        Ok(Self {
+            size: metadata.len() as i64,
+            modified,
+        })
+    }
+}
+
+pub(crate) fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+pub(crate) fn track_from_row(row: &Row) -> rusqlite::Result<TrackMetadata> {
+    Ok(TrackMetadata {
+        id: row.get(0)?,
+        title: row.get(1)?,
+        artist: row.get(2)?,
+        album: row.get(3)?,
+        duration: row.get(4)?,
+        file_path: row.get(5)?,
+        album_artist: row.get(6)?,
+        track_number: row.get(7)?,
+        track_total: row.get(8)?,
+        disc_number: row.get(9)?,
+        disc_total: row.get(10)?,
+        year: row.get(11)?,
+        date: row.get(12)?,
+        genre: row.get(13)?,
+        composer: row.get(14)?,
+        comment: row.get(15)?,
+        bpm: row.get(16)?,
+        compilation: row.get(17)?,
+        musicbrainz_track_id: row.get(18)?,
+        musicbrainz_album_id: row.get(19)?,
+        musicbrainz_artist_id: row.get(20)?,
+        musicbrainz_album_artist_id: row.get(21)?,
+        codec: row.get(22)?,
+        sample_rate: row.get(23)?,
+        bit_depth: row.get(24)?,
+        channels: row.get(25)?,
+        bitrate: row.get(26)?,
+        file_size: row.get::<_, Option<i64>>(27)?.filter(|&size| size > 0).map(|size| size as u64),
+        start_time: row.get(28)?,
+        end_time: row.get(29)?,
+        chapters: row.get::<_, Option<String>>(30)?
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default(),
+    })
+}
+
+fn artist_id(conn: &Connection, name: &str) -> rusqlite::Result<i64> {
+    conn.execute("INSERT OR IGNORE INTO artists (name) VALUES (?1)", [name])?;
+    conn.query_row("SELECT id FROM artists WHERE name = ?1", [name], |row| row.get(0))
+}
+
+fn album_id(conn: &Connection, title: &str, artist_id: i64) -> rusqlite::Result<i64> {
+    conn.execute(
+        "INSERT OR IGNORE INTO albums (title, artist_id) VALUES (?1, ?2)",
+        params![title, artist_id],
+    )?;
+    conn.query_row(
+        "SELECT id FROM albums WHERE title = ?1 AND artist_id = ?2",
+        params![title, artist_id],
+        |row| row.get(0),
+    )
+}
+
+fn folder_from_row(row: &Row) -> rusqlite::Result<LibraryFolder> {
+    Ok(LibraryFolder {
+        id: row.get(0)?,
+        path: row.get(1)?,
+        date_added: row.get(2)?,
+        track_count: row.get(3)?,
+        include_extensionless: row.get(4)?,
+    })
+}
+
+/// Deepest registered folder containing `file_path`
+pub(crate) fn folder_for_path(conn: &Connection, file_path: &str) -> rusqlite::Result<Option<i64>> {
+    let mut stmt = conn.prepare_cached("SELECT id, path FROM folders")?;
+    let folders = stmt.query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))?;
+
+    let mut best: Option<(i64, usize)> = None;
+    for folder in folders {
+        let (id, path) = folder?;
+        if Path::new(file_path).starts_with(&path) && best.map_or(true, |(_, len)| path.len() > len) {
+            best = Some((id, path.len()));
+        }
+    }
+
+    Ok(best.map(|(id, _)| id))
+}
+
+/// How `upsert_track` matched a track against the rows already stored
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Upserted {
+    Added,
+    Updated,
+    /// Same audio as a track whose file is gone: the row now points at the new path
+    Moved,
+}
+
+/// Give every row keyed by `old_id` the new id
+fn rekey_track(conn: &Connection, old_id: &str, new_id: &str) -> rusqlite::Result<()> {
+    conn.execute("UPDATE tracks SET id = ?1 WHERE id = ?2", [new_id, old_id])?;
+    conn.execute("UPDATE playlist_tracks SET track_id = ?1 WHERE track_id = ?2", [new_id, old_id])?;
+    conn.execute("UPDATE history SET track_id = ?1 WHERE track_id = ?2", [new_id, old_id])?;
+    Ok(())
+}
+
+/// Pick the row `track` belongs to. Track IDs are content fingerprints, so a
+/// known ID at another path is either a moved file (the old path is gone) or
+/// a second copy, which gets an ID of its own derived from its path.
+fn resolve_track_id(conn: &Connection, track: &TrackMetadata) -> rusqlite::Result<(String, Upserted)> {
+    let by_path: Option<String> = conn
+        .query_row("SELECT id FROM tracks WHERE file_path = ?1", [&track.file_path], |row| row.get(0))
+        .optional()?;
+    let by_id: Option<String> = conn
+        .query_row("SELECT file_path FROM tracks WHERE id = ?1", [&track.id], |row| row.get(0))
+        .optional()?;
+
+    let (id, upserted) = match by_id {
+        Some(path) if path == track.file_path => return Ok((track.id.clone(), Upserted::Updated)),
+        Some(path) if !cue::audio_path(&path).exists() => {
+            // Whatever was stored for the new path is stale; its file was replaced
+            if let Some(stale) = &by_path {
+                conn.execute("DELETE FROM tracks WHERE id = ?1", [stale])?;
+            }
+            return Ok((track.id.clone(), Upserted::Moved));
+        }
+        Some(_) => (format!("{:x}", md5::compute(format!("{}:{}", track.id, track.file_path))), Upserted::Added),
+        None => (track.id.clone(), Upserted::Added),
+    };
+
+    match by_path {
+        Some(existing) if existing == id => Ok((id, Upserted::Updated)),
+        // Re-encoded audio, or a row from before IDs were fingerprints
+        Some(existing) => {
+            rekey_track(conn, &existing, &id)?;
+            Ok((id, Upserted::Updated))
+        }
+        None => Ok((id, upserted)),
+    }
+}
+
+/// Insert or refresh a track; returns the ID it is stored under, which differs
+/// from `track.id` for a duplicate copy of a file already in the library
+fn upsert_track(conn: &Connection, track: &TrackMetadata, stamp: FileStamp) -> rusqlite::Result<(String, Upserted)> {
+    let (id, upserted) = resolve_track_id(conn, track)?;
+    let artist_id = artist_id(conn, &track.artist)?;
+    // Compilations are grouped under their album artist rather than each track artist
+    let album_id = match track.album.as_deref().filter(|a| !a.is_empty()) {
+        Some(album) => {
+            let album_artist_id = match track.album_artist.as_deref() {
+                Some(album_artist) => self::artist_id(conn, album_artist)?,
+                None => artist_id,
+            };
+            Some(album_id(conn, album, album_artist_id)?)
+        }
+        None => None,
+    };
+    let folder_id = folder_for_path(conn, &track.file_path)?;
+
+    conn.execute(
+        "INSERT INTO tracks (id, file_path, title, artist_id, album_id, folder_id, duration, date_added,
+            file_size, modified, album_artist, track_number, track_total, disc_number, disc_total,
+            year, date, genre, composer, comment, bpm, compilation, musicbrainz_track_id,
+            musicbrainz_album_id, musicbrainz_artist_id, musicbrainz_album_artist_id,
+            codec, sample_rate, bit_depth, channels, bitrate, start_time, end_time, chapters)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16,
+            ?17, ?18, ?19, ?20, ?21, ?22, ?23, ?24, ?25, ?26, ?27, ?28, ?29, ?30, ?31, ?32, ?33, ?34)
+         ON CONFLICT (id) DO UPDATE SET
+            file_path = excluded.file_path,
+            title = excluded.title,
+            artist_id = excluded.artist_id,
+            album_id = excluded.album_id,
+            folder_id = excluded.folder_id,
+            duration = excluded.duration,
+            file_size = excluded.file_size,
+            modified = excluded.modified,
+            album_artist = excluded.album_artist,
+            track_number = excluded.track_number,
+            track_total = excluded.track_total,
+            disc_number = excluded.disc_number,
+            disc_total = excluded.disc_total,
+            year = excluded.year,
+            date = excluded.date,
+            genre = excluded.genre,
+            composer = excluded.composer,
+            comment = excluded.comment,
+            bpm = excluded.bpm,
+            compilation = excluded.compilation,
+            musicbrainz_track_id = excluded.musicbrainz_track_id,
+            musicbrainz_album_id = excluded.musicbrainz_album_id,
+            musicbrainz_artist_id = excluded.musicbrainz_artist_id,
+            musicbrainz_album_artist_id = excluded.musicbrainz_album_artist_id,
+            codec = excluded.codec,
+            sample_rate = excluded.sample_rate,
+            bit_depth = excluded.bit_depth,
+            channels = excluded.channels,
+            bitrate = excluded.bitrate,
+            start_time = excluded.start_time,
+            end_time = excluded.end_time,
+            chapters = excluded.chapters,
+            acoustic_fingerprint = NULL",
+        params![
+            id,
+            track.file_path,
+            track.title,
+            artist_id,
+            album_id,
+            folder_id,
+            track.duration,
+            now(),
+            stamp.size,
+            stamp.modified,
+            track.album_artist,
+            track.track_number,
+            track.track_total,
+            track.disc_number,
+            track.disc_total,
+            track.year,
+            track.date,
+            track.genre,
+            track.composer,
+            track.comment,
+            track.bpm,
+            track.compilation,
+            track.musicbrainz_track_id,
+            track.musicbrainz_album_id,
+            track.musicbrainz_artist_id,
+            track.musicbrainz_album_artist_id,
+            track.codec,
+            track.sample_rate,
+            track.bit_depth,
+            track.channels,
+            track.bitrate,
+            track.start_time,
+            track.end_time,
+            (!track.chapters.is_empty()).then(|| serde_json::to_string(&track.chapters).unwrap_or_default()),
+        ],
+    )?;
+    search::index_track(conn, &id, track)?;
+
+    Ok((id, upserted))
+}
+
+/// Drop albums and artists no track refers to any more
+fn prune_orphans(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute("DELETE FROM albums WHERE id NOT IN (SELECT album_id FROM tracks WHERE album_id IS NOT NULL)", [])?;
+    conn.execute(
+        "DELETE FROM artists WHERE id NOT IN (SELECT artist_id FROM tracks)
+            AND id NOT IN (SELECT artist_id FROM albums)",
+        [],
+    )?;
+    Ok(())
+}
+
+pub struct Library {
+    pub(crate) conn: Connection,
+}
+
+impl Library {
+    /// Open (creating if needed) the library database at `path`
+    pub fn open(path: &Path) -> Result<Self, LibraryError> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let conn = Connection::open(path)?;
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        Self::init(conn)
+    }
+
+    #[cfg(test)]
+    pub fn open_in_memory() -> Result<Self, LibraryError> {
+        Self::init(Connection::open_in_memory()?)
+    }
+
+    fn init(mut conn: Connection) -> Result<Self, LibraryError> {
+        conn.pragma_update(None, "foreign_keys", "ON")?;
+
+        let version: usize = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        if version < MIGRATIONS.len() {
+            let tx = conn.transaction()?;
+            for migration in &MIGRATIONS[version..] {
+                tx.execute_batch(migration)?;
+            }
+            tx.pragma_update(None, "user_version", MIGRATIONS.len())?;
+            tx.commit()?;
+        }
+        search::index_missing(&conn)?;
+
+        Ok(Self { conn })
+    }
+
+    /// Register a library root; adding it again returns the existing entry
+    pub fn add_folder(&self, path: &Path) -> Result<LibraryFolder, LibraryError> {
+        let path = path.to_string_lossy();
+        self.conn.execute(
+            "INSERT OR IGNORE INTO folders (path, date_added) VALUES (?1, ?2)",
+            params![path, now()],
+        )?;
+        // Tracks cached before the folder was registered now belong to it
+        let folder_id: i64 = self.conn.query_row("SELECT id FROM folders WHERE path = ?1", [&path], |row| row.get(0))?;
+        let prefix = format!("{}{}", path.trim_end_matches(std::path::MAIN_SEPARATOR), std::path::MAIN_SEPARATOR);
+        self.conn.execute(
+            "UPDATE tracks SET folder_id = ?1 WHERE folder_id IS NULL AND file_path LIKE ?2 ESCAPE '\\'",
+            params![folder_id, format!("{}%", escape_like(&prefix))],
+        )?;
+
+        self.folder(&path)?
+            .ok_or_else(|| LibraryError::FolderNotFound(path.to_string()))
+    }
+
+    /// Change whether scans of a registered folder sniff extension-less files
+    pub fn set_include_extensionless(&self, path: &Path, include: bool) -> Result<(), LibraryError> {
+        let updated = self.conn.execute(
+            "UPDATE folders SET include_extensionless = ?1 WHERE path = ?2",
+            params![include, path.to_string_lossy()],
+        )?;
+        if updated == 0 {
+            return Err(LibraryError::FolderNotFound(path.display().to_string()));
+        }
+        Ok(())
+    }
+
+    fn folder(&self, path: &str) -> Result<Option<LibraryFolder>, LibraryError> {
+        Ok(self.conn
+            .query_row(
+                "SELECT f.id, f.path, f.date_added, COUNT(t.id), f.include_extensionless
+                 FROM folders f LEFT JOIN tracks t ON t.folder_id = f.id
+                 WHERE f.path = ?1 GROUP BY f.id",
+                [path],
+                folder_from_row,
+            )
+            .optional()?)
+    }
+
+    pub fn folders(&self) -> Result<Vec<LibraryFolder>, LibraryError> {
+        let mut stmt = self.conn.prepare(
+            "SELECT f.id, f.path, f.date_added, COUNT(t.id), f.include_extensionless
+             FROM folders f LEFT JOIN tracks t ON t.folder_id = f.id
+             GROUP BY f.id ORDER BY f.path",
+        )?;
+        let folders = stmt
+            .query_map([], folder_from_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(folders)
+    }
+
+    /// Forget a library root and every track under it; returns the number of tracks removed
+    pub fn remove_folder(&mut self, path: &Path) -> Result<usize, LibraryError> {
+        let path = path.to_string_lossy();
+        let folder = self.folder(&path)?
+            .ok_or_else(|| LibraryError::FolderNotFound(path.to_string()))?;
+
+        let tx = self.conn.transaction()?;
+        let removed = tx.execute("DELETE FROM tracks WHERE folder_id = ?1", [folder.id])?;
+        tx.execute("DELETE FROM folders WHERE id = ?1", [folder.id])?;
+        prune_orphans(&tx)?;
+        tx.commit()?;
+
+        Ok(removed)
+    }
+
+    /// First half of a rescan: walk the folder with `FileManager::scan_directory`,
+    /// registering it if needed, and work out which files need probing (new
+    /// ones and those whose size or mtime changed). The probing itself needs
+    /// no access to the library.
+    pub fn plan_rescan(&self, file_manager: &FileManager, root: &Path) -> Result<RescanPlan, LibraryError> {
+        let include_extensionless = self.folder(&root.to_string_lossy())?
+            .is_some_and(|folder| folder.include_extensionless);
+        let files = file_manager.clone()
+            .with_extensionless_files(include_extensionless)
+            .scan_directory(root)
+            .map_err(LibraryError::ScanError)?;
+        let folder = self.add_folder(root)?;
+        // Folders registered inside this one own their files and are rescanned on their own
+        let nested: Vec<PathBuf> = self.folders()?
+            .into_iter()
+            .map(|folder| PathBuf::from(folder.path))
+            .filter(|path| path != root && path.starts_with(root))
+            .collect();
+
+        let mut known: HashMap<String, FileStamp> = HashMap::new();
+        {
+            let mut stmt = self.conn.prepare("SELECT file_path, file_size, modified FROM tracks WHERE folder_id = ?1")?;
+            let rows = stmt.query_map([folder.id], |row| {
+                Ok((row.get::<_, String>(0)?, FileStamp { size: row.get(1)?, modified: row.get(2)? }))
+            })?;
+            for row in rows {
+                let (path, stamp) = row?;
+                known.insert(path, stamp);
+            }
+        }
+
+        let mut to_probe = Vec::new();
+        let mut unchanged = 0;
+        for path in files {
+            if nested.iter().any(|folder| path.starts_with(folder)) {
+                continue;
+            }
+            let file_path = path.to_string_lossy().to_string();
+            let previous = known.remove(&file_path);
+            let stamp = match FileStamp::of(&path) {
+                Ok(stamp) => stamp,
+                Err(e) => {
+                    log::warn!("Skipping {}: {}", file_path, e);
+                    continue;
+                }
+            };
+
+            match previous {
+                Some(previous) if previous == stamp => unchanged += 1,
+                _ => to_probe.push((path, stamp)),
+            }
+        }
+
+        Ok(RescanPlan {
+            root: root.to_path_buf(),
+            to_probe,
+            // Whatever is left in `known` was not found on disk
+            missing: known.into_keys().collect(),
+            unchanged,
+        })
+    }
+
+    /// Second half of a rescan: store the probed tracks and drop missing files.
+    /// `probed` may cover only part of the plan if the scan was cancelled; the
+    /// rest keeps its old stamps and is picked up by the next rescan.
+    pub fn apply_rescan(&mut self, plan: RescanPlan, probed: Vec<(TrackMetadata, FileStamp)>) -> Result<ScanSummary, LibraryError> {
+        // Moved files are re-linked by their fingerprint before the rows of
+        // paths that were not found on disk go
+        let (mut added, mut updated, mut moved, mut removed) = (0, 0, 0, 0);
+        let tx = self.conn.transaction()?;
+        for (track, stamp) in &probed {
+            match upsert_track(&tx, track, *stamp)?.1 {
+                Upserted::Added => added += 1,
+                Upserted::Updated => updated += 1,
+                Upserted::Moved => moved += 1,
+            }
+        }
+        for file_path in &plan.missing {
+            removed += tx.execute("DELETE FROM tracks WHERE file_path = ?1", [file_path])? as u32;
+        }
+        prune_orphans(&tx)?;
+        tx.commit()?;
+
+        let root = &plan.root;
+        let folder = self.folder(&root.to_string_lossy())?
+            .ok_or_else(|| LibraryError::FolderNotFound(root.display().to_string()))?;
+        let summary = ScanSummary {
+            folder,
+            added,
+            updated,
+            moved,
+            removed,
+            unchanged: plan.unchanged,
+            cancelled: probed.len() < plan.to_probe.len(),
+        };
+        log::info!(
+            "Rescanned {}: {} added, {} updated, {} moved, {} removed, {} unchanged{}",
+            root.display(), summary.added, summary.updated, summary.moved, summary.removed, summary.unchanged,
+            if summary.cancelled { " (cancelled)" } else { "" }
+        );
+        Ok(summary)
+    }
+
+    /// Insert or refresh a single track; returns the ID it is stored under
+    pub fn upsert_track(&self, track: &TrackMetadata) -> Result<String, LibraryError> {
+        // A file we cannot stat is stored with an empty stamp and re-read next time
+        let stamp = FileStamp::of(Path::new(&track.file_path)).unwrap_or_default();
+        Ok(upsert_track(&self.conn, track, stamp)?.0)
+    }
+
+    /// Cached track for `file_path`, unless the file changed since it was stored
+    pub fn fresh_track_by_path(&self, file_path: &str) -> Result<Option<TrackMetadata>, LibraryError> {
+        let stored: Option<FileStamp> = self.conn
+            .query_row(
+                "SELECT file_size, modified FROM tracks WHERE file_path = ?1",
+                [file_path],
+                |row| Ok(FileStamp { size: row.get(0)?, modified: row.get(1)? }),
+            )
+            .optional()?;
+
+        match stored {
+            Some(stamp) if FileStamp::of(Path::new(file_path)).ok() == Some(stamp) => self.track_by_path(file_path),
+            _ => Ok(None),
+        }
+    }
+
+    /// First half of applying a batch of changed paths reported by the
+    /// filesystem watcher: work out which files need probing and which are
+    /// gone. Paths may be files or whole directories, created, modified or
+    /// deleted; anything outside the registered folders is ignored.
+    pub fn plan_changes(&self, file_manager: &FileManager, paths: &[PathBuf]) -> Result<ChangePlan, LibraryError> {
+        let mut files = Vec::new();
+        let mut gone = Vec::new();
+        for path in paths {
+            if cue::is_sheet(path) {
+                // A sheet changes how the files next to it are split
+                let dir = path.parent().unwrap_or(Path::new(""));
+                files.extend(std::fs::read_dir(dir).into_iter().flatten()
+                    .filter_map(|entry| entry.ok().map(|e| e.path()))
+                    .filter(|p| p.is_file() && !cue::is_sheet(p))
+                    .flat_map(|p| cue::expand(&p)));
+            } else if path.is_dir() {
+                // Each file is checked against its own folder's options below
+                let candidates = file_manager.clone().with_extensionless_files(true).scan_directory(path);
+                files.extend(candidates.unwrap_or_default());
+            } else if path.is_file() {
+                files.extend(cue::expand(path));
+            } else {
+                gone.push(path.to_string_lossy().to_string());
+            }
+        }
+        files.sort();
+        files.dedup();
+
+        let mut to_probe = Vec::new();
+        for path in files {
+            let file_path = path.to_string_lossy().to_string();
+            let Some(folder_id) = folder_for_path(&self.conn, &file_path)? else { continue };
+            let include_extensionless: bool = self.conn.query_row(
+                "SELECT include_extensionless FROM folders WHERE id = ?1",
+                [folder_id],
+                |row| row.get(0),
+            )?;
+            if !file_manager.clone().with_extensionless_files(include_extensionless).is_audio_file(&cue::audio_path(&file_path)) {
+                continue;
+            }
+            let Ok(stamp) = FileStamp::of(&path) else { continue };
+            let stored: Option<FileStamp> = self.conn
+                .query_row(
+                    "SELECT file_size, modified FROM tracks WHERE file_path = ?1",
+                    [&file_path],
+                    |row| Ok(FileStamp { size: row.get(0)?, modified: row.get(1)? }),
+                )
+                .optional()?;
+            if stored != Some(stamp) {
+                to_probe.push((path, stamp));
+            }
+        }
+
+        Ok(ChangePlan { to_probe, gone })
+    }
+
+    /// Second half of applying watcher changes: store the probed tracks and
+    /// drop the rows of gone paths in one transaction
+    pub fn apply_changes(&mut self, plan: ChangePlan, probed: Vec<(TrackMetadata, FileStamp)>) -> Result<LibraryChanges, LibraryError> {
+        let mut changes = LibraryChanges::default();
+
+        // Rows left over from splitting a file differently, e.g. the whole
+        // file once a sheet appears, or its tracks once the sheet is gone
+        let mut split: HashMap<PathBuf, Vec<String>> = HashMap::new();
+        for (track, _) in &probed {
+            split.entry(cue::audio_path(&track.file_path)).or_default().push(track.file_path.clone());
+        }
+
+        // A rename arrives as a new path plus a gone one; the new path is
+        // re-linked to the existing track first, so nothing is removed
+        let tx = self.conn.transaction()?;
+        for (track, stamp) in &probed {
+            match upsert_track(&tx, track, *stamp)? {
+                (id, Upserted::Added) => changes.added.push(id),
+                (id, Upserted::Updated | Upserted::Moved) => changes.updated.push(id),
+            }
+        }
+        for (audio_path, current) in &split {
+            let path = audio_path.to_string_lossy();
+            let mut stmt = tx.prepare(
+                "SELECT id, file_path FROM tracks WHERE file_path = ?1 OR file_path LIKE ?2 ESCAPE '\\'",
+            )?;
+            let rows = stmt
+                .query_map(params![path, format!("{}{}%", escape_like(&path), cue::VIRTUAL_MARKER)], |row| {
+                    Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+                })?
+                .collect::<Result<Vec<_>, _>>()?;
+            drop(stmt);
+            for (id, _) in rows.into_iter().filter(|(_, file_path)| !current.contains(file_path)) {
+                tx.execute("DELETE FROM tracks WHERE id = ?1", [&id])?;
+                changes.updated.retain(|updated| updated != &id);
+                changes.removed.push(id);
+            }
+        }
+        for path in &plan.gone {
+            // A deleted directory takes every track below it along, a
+            // deleted file every CUE track cut from it
+            let mut stmt = tx.prepare(
+                "SELECT id FROM tracks WHERE file_path = ?1 OR file_path LIKE ?2 ESCAPE '\\' OR file_path LIKE ?3 ESCAPE '\\'",
+            )?;
+            let prefix = format!("{}{}%", escape_like(path), escape_like(std::path::MAIN_SEPARATOR_STR));
+            let cue_tracks = format!("{}{}%", escape_like(path), cue::VIRTUAL_MARKER);
+            let ids = stmt
+                .query_map(params![path, prefix, cue_tracks], |row| row.get::<_, String>(0))?
+                .collect::<Result<Vec<_>, _>>()?;
+            drop(stmt);
+            for id in ids {
+                tx.execute("DELETE FROM tracks WHERE id = ?1", [&id])?;
+                changes.removed.push(id);
+            }
+        }
+        if !changes.removed.is_empty() {
+            prune_orphans(&tx)?;
+        }
+        tx.commit()?;
+
+        Ok(changes)
+    }
+
+    /// Acoustic signature cached by duplicate detection, if still valid
+    pub fn acoustic_fingerprint(&self, id: &str) -> Result<Option<Vec<u8>>, LibraryError> {
+        Ok(self.conn
+            .query_row("SELECT acoustic_fingerprint FROM tracks WHERE id = ?1", [id], |row| row.get(0))
+            .optional()?
+            .flatten())
+    }
+
+    pub fn set_acoustic_fingerprint(&self, id: &str, fingerprint: &[u8]) -> Result<(), LibraryError> {
+        self.conn.execute(
+            "UPDATE tracks SET acoustic_fingerprint = ?1 WHERE id = ?2",
+            params![fingerprint, id],
+        )?;
+        Ok(())
+    }
+
+    pub fn track(&self, id: &str) -> Result<Option<TrackMetadata>, LibraryError> {
+        let sql = format!("SELECT {} WHERE t.id = ?1", TRACK_COLUMNS);
+        Ok(self.conn.query_row(&sql, [id], track_from_row).optional()?)
+    }
+
+    pub fn track_by_path(&self, file_path: &str) -> Result<Option<TrackMetadata>, LibraryError> {
+        let sql = format!("SELECT {} WHERE t.file_path = ?1", TRACK_COLUMNS);
+        Ok(self.conn.query_row(&sql, [file_path], track_from_row).optional()?)
+    }
+
+    /// Tracks stored under any folder with this file name, for finding files that moved
+    pub fn tracks_by_file_name(&self, file_name: &str) -> Result<Vec<TrackMetadata>, LibraryError> {
+        let sql = format!("SELECT {} WHERE t.file_path LIKE ?1 ESCAPE '\\'", TRACK_COLUMNS);
+        let pattern = format!("%{}{}", escape_like(std::path::MAIN_SEPARATOR_STR), escape_like(file_name));
+        let mut stmt = self.conn.prepare_cached(&sql)?;
+        let tracks = stmt
+            .query_map([pattern], track_from_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(tracks)
+    }
+
+    pub fn tracks(&self, query: &TrackQuery) -> Result<Vec<TrackMetadata>, LibraryError> {
+        let order = match query.sort {
+            TrackSort::Title => "t.title COLLATE NOCASE",
+            TrackSort::Artist => "ar.name COLLATE NOCASE, al.title COLLATE NOCASE, t.title COLLATE NOCASE",
+            TrackSort::Album => "al.title COLLATE NOCASE, t.disc_number, t.track_number, t.file_path",
+            TrackSort::DateAdded => "t.date_added DESC, t.file_path",
+        };
+        let sql = format!(
+            "SELECT {}
+             WHERE (?1 IS NULL OR t.artist_id = ?1)
+               AND (?2 IS NULL OR t.album_id = ?2)
+               AND (?3 IS NULL OR t.folder_id = ?3)
+               AND (?4 IS NULL OR t.genre = ?4 COLLATE NOCASE)
+               AND (?5 IS NULL OR (t.year >= ?5 AND t.year < ?5 + 10))
+             ORDER BY {}
+             LIMIT ?6 OFFSET ?7",
+            TRACK_COLUMNS, order
+        );
+
+        let mut stmt = self.conn.prepare_cached(&sql)?;
+        let tracks = stmt
+            .query_map(
+                params![
+                    query.artist_id,
+                    query.album_id,
+                    query.folder_id,
+                    query.genre,
+                    query.decade,
+                    query.limit.map(i64::from).unwrap_or(-1),
+                    query.offset.unwrap_or(0),
+                ],
+                track_from_row,
+            )?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(tracks)
+    }
+
+    pub fn artists(&self) -> Result<Vec<Artist>, LibraryError> {
+        let mut stmt = self.conn.prepare(
+            "SELECT ar.id, ar.name, COUNT(DISTINCT t.album_id), COUNT(t.id)
+             FROM artists ar JOIN tracks t ON t.artist_id = ar.id
+             GROUP BY ar.id ORDER BY ar.name COLLATE NOCASE",
+        )?;
+        let artists = stmt
+            .query_map([], |row| Ok(Artist {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                album_count: row.get(2)?,
+                track_count: row.get(3)?,
+            }))?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(artists)
+    }
+
+    pub fn albums(&self, artist_id: Option<i64>) -> Result<Vec<Album>, LibraryError> {
+        let mut stmt = self.conn.prepare(
+            "SELECT al.id, al.title, ar.name, COUNT(t.id), COALESCE(SUM(t.duration), 0)
+             FROM albums al
+             JOIN artists ar ON ar.id = al.artist_id
+             JOIN tracks t ON t.album_id = al.id
+             WHERE ?1 IS NULL OR al.artist_id = ?1
+             GROUP BY al.id ORDER BY al.title COLLATE NOCASE",
+        )?;
+        let albums = stmt
+            .query_map([artist_id], |row| Ok(Album {
+                id: row.get(0)?,
+                title: row.get(1)?,
+                artist: row.get(2)?,
+                track_count: row.get(3)?,
+                duration: row.get(4)?,
+            }))?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(albums)
+    }
+}
+
+pub(crate) fn escape_like(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{rescan, sine_wav, temp_dir};
+
+    fn track(path: &str, title: &str, artist: &str, album: Option<&str>) -> TrackMetadata {
+        TrackMetadata {
+            id: format!("{:x}", md5::compute(path.as_bytes())),
+            title: title.to_string(),
+            artist: artist.to_string(),
+            album: album.map(str::to_string),
+            duration: 180.0,
+            file_path: path.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Plan, probe and apply a batch of watcher paths the way the watcher does
+    fn apply_changes(library: &mut Library, file_manager: &FileManager, paths: &[PathBuf]) -> Result<LibraryChanges, LibraryError> {
+        let plan = library.plan_changes(file_manager, paths)?;
+        let probed = plan.to_probe.iter()
+            .map(|(path, stamp)| (file_manager.metadata_or_fallback(path), *stamp))
+            .collect();
+        library.apply_changes(plan, probed)
+    }
+
+    #[test]
+    fn test_tracks_albums_and_artists() {
+        let library = Library::open_in_memory().unwrap();
+        library.upsert_track(&track("/m/kino/1.mp3", "Группа крови", "Кино", Some("Группа крови"))).unwrap();
+        library.upsert_track(&track("/m/kino/2.mp3", "Закрой за мной дверь", "Кино", Some("Группа крови"))).unwrap();
+        library.upsert_track(&track("/m/ddt/1.mp3", "Осень", "ДДТ", None)).unwrap();
+
+        let artists = library.artists().unwrap();
+        assert_eq!(artists.len(), 2);
+        let kino = artists.iter().find(|a| a.name == "Кино").unwrap();
+        assert_eq!((kino.album_count, kino.track_count), (1, 2));
+
+        let albums = library.albums(Some(kino.id)).unwrap();
+        assert_eq!(albums.len(), 1);
+        assert_eq!(albums[0].duration, 360.0);
+
+        let query = TrackQuery { artist_id: Some(kino.id), limit: Some(1), ..Default::default() };
+        assert_eq!(library.tracks(&query).unwrap()[0].title, "Группа крови");
+
+        // Re-tagging moves the track to another artist and the empty one disappears
+        library.upsert_track(&track("/m/ddt/1.mp3", "Осень", "DDT", None)).unwrap();
+        assert!(library.artists().unwrap().iter().all(|a| a.name != "ДДТ"));
+        assert_eq!(library.track_by_path("/m/ddt/1.mp3").unwrap().unwrap().artist, "DDT");
+
+        // A registered folder claims the tracks below it, not a sibling sharing its name
+        library.upsert_track(&track("/m/kino2/1.mp3", "Кукушка", "Кино", None)).unwrap();
+        assert_eq!(library.add_folder(Path::new("/m/kino")).unwrap().track_count, 2);
+    }
+
+    #[test]
+    fn test_scan_and_remove_folder() {
+        let dir = temp_dir("library");
+        let music = dir.join("music");
+        std::fs::create_dir_all(music.join("album")).unwrap();
+        std::fs::write(music.join("album/one.wav"), sine_wav(0.5, 8000, 440.0)).unwrap();
+        std::fs::write(music.join("two.wav"), sine_wav(0.5, 8000, 440.0)).unwrap();
+        std::fs::write(music.join("notes.txt"), "not audio").unwrap();
+
+        let db_path = dir.join("library.db");
+        let mut library = Library::open(&db_path).unwrap();
+        let summary = rescan(&mut library, &FileManager::new(), &music).unwrap();
+        assert_eq!((summary.added, summary.updated, summary.removed), (2, 0, 0));
+        assert_eq!(summary.folder.track_count, 2);
+        drop(library);
+
+        // Everything survives reopening the database
+        let mut library = Library::open(&db_path).unwrap();
+        assert_eq!(library.folders().unwrap().len(), 1);
+        let tracks = library.tracks(&TrackQuery::default()).unwrap();
+        assert_eq!(tracks.len(), 2);
+        assert!(library.track(&tracks[0].id).unwrap().is_some());
+
+        assert_eq!(library.remove_folder(&music).unwrap(), 2);
+        assert!(library.tracks(&TrackQuery::default()).unwrap().is_empty());
+        assert!(library.artists().unwrap().is_empty());
+        assert!(library.remove_folder(&music).is_err());
+    }
+
+    #[test]
+    fn test_incremental_rescan() {
+        let dir = temp_dir("library-rescan");
+        let one = dir.join("one.wav");
+        let two = dir.join("two.wav");
+        std::fs::write(&one, sine_wav(0.5, 8000, 440.0)).unwrap();
+        std::fs::write(&two, sine_wav(0.5, 8000, 440.0)).unwrap();
+
+        let mut library = Library::open_in_memory().unwrap();
+        let file_manager = FileManager::new();
+        rescan(&mut library, &file_manager, &dir).unwrap();
+
+        let summary = rescan(&mut library, &file_manager, &dir).unwrap();
+        assert_eq!((summary.added, summary.updated, summary.removed, summary.unchanged), (0, 0, 0, 2));
+        assert!(library.fresh_track_by_path(&one.to_string_lossy()).unwrap().is_some());
+
+        std::fs::write(&one, sine_wav(1.0, 8000, 440.0)).unwrap();
+        std::fs::remove_file(&two).unwrap();
+        std::fs::write(dir.join("three.wav"), sine_wav(0.5, 8000, 660.0)).unwrap();
+        assert!(library.fresh_track_by_path(&one.to_string_lossy()).unwrap().is_none());
+
+        let summary = rescan(&mut library, &file_manager, &dir).unwrap();
+        assert_eq!((summary.added, summary.updated, summary.removed, summary.unchanged), (1, 1, 1, 0));
+        assert_eq!(summary.folder.track_count, 2);
+        assert!(library.track_by_path(&two.to_string_lossy()).unwrap().is_none());
+        assert_eq!(library.track_by_path(&one.to_string_lossy()).unwrap().unwrap().duration, 1.0);
+    }
+
+    #[test]
+    fn test_rescan_leaves_nested_folders_alone() {
+        let dir = temp_dir("library-nested");
+        std::fs::create_dir_all(dir.join("inner")).unwrap();
+        std::fs::write(dir.join("outer.wav"), sine_wav(0.5, 8000, 440.0)).unwrap();
+        std::fs::write(dir.join("inner/inner.wav"), sine_wav(0.5, 8000, 660.0)).unwrap();
+
+        let mut library = Library::open_in_memory().unwrap();
+        let file_manager = FileManager::new();
+        let inner = rescan(&mut library, &file_manager, &dir.join("inner")).unwrap();
+        assert_eq!(inner.added, 1);
+
+        let outer = rescan(&mut library, &file_manager, &dir).unwrap();
+        assert_eq!((outer.added, outer.updated, outer.unchanged), (1, 0, 0));
+        let outer = rescan(&mut library, &file_manager, &dir).unwrap();
+        assert_eq!((outer.added, outer.updated, outer.removed, outer.unchanged), (0, 0, 0, 1));
+
+        let track = library.track_by_path(&dir.join("inner/inner.wav").to_string_lossy()).unwrap().unwrap();
+        let inner_tracks = library.tracks(&TrackQuery { folder_id: Some(inner.folder.id), ..Default::default() }).unwrap();
+        assert_eq!(inner_tracks.iter().map(|t| &t.id).collect::<Vec<_>>(), vec![&track.id]);
+    }
+
+    #[test]
+    fn test_rescan_relinks_moved_files() {
+        let dir = temp_dir("library-moves");
+        let one = dir.join("one.wav");
+        std::fs::write(&one, sine_wav(0.5, 8000, 440.0)).unwrap();
+
+        let mut library = Library::open_in_memory().unwrap();
+        let file_manager = FileManager::new();
+        rescan(&mut library, &file_manager, &dir).unwrap();
+        let id = library.track_by_path(&one.to_string_lossy()).unwrap().unwrap().id;
+
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        let moved = dir.join("sub/renamed.wav");
+        std::fs::rename(&one, &moved).unwrap();
+        let summary = rescan(&mut library, &file_manager, &dir).unwrap();
+        assert_eq!((summary.added, summary.moved, summary.removed), (0, 1, 0));
+        assert_eq!(library.track(&id).unwrap().unwrap().file_path, moved.to_string_lossy());
+
+        // A second copy is a track of its own, and does not steal the ID
+        let copy = dir.join("copy.wav");
+        std::fs::copy(&moved, &copy).unwrap();
+        let summary = rescan(&mut library, &file_manager, &dir).unwrap();
+        assert_eq!((summary.added, summary.moved), (1, 0));
+        let copy_id = library.track_by_path(&copy.to_string_lossy()).unwrap().unwrap().id;
+        assert_ne!(copy_id, id);
+        assert_eq!(library.track(&id).unwrap().unwrap().file_path, moved.to_string_lossy());
+
+        // Rows from before fingerprint IDs are re-keyed in place
+        library.conn.execute("UPDATE tracks SET id = 'legacy', modified = 0 WHERE id = ?1", [&id]).unwrap();
+        let summary = rescan(&mut library, &file_manager, &dir).unwrap();
+        assert_eq!((summary.added, summary.updated, summary.removed), (0, 1, 0));
+        assert!(library.track(&id).unwrap().is_some());
+        assert!(library.track("legacy").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_apply_watcher_changes() {
+        let dir = temp_dir("library-watch");
+        let root = dir.join("root");
+        std::fs::create_dir_all(root.join("album")).unwrap();
+        let outside = dir.join("outside.wav");
+        std::fs::write(&outside, sine_wav(0.5, 8000, 440.0)).unwrap();
+
+        let mut library = Library::open_in_memory().unwrap();
+        let file_manager = FileManager::new();
+        library.add_folder(&root).unwrap();
+
+        let one = root.join("album/one.wav");
+        std::fs::write(&one, sine_wav(0.5, 8000, 440.0)).unwrap();
+        let changes = apply_changes(&mut library, &file_manager, &[one.clone(), outside.clone()]).unwrap();
+        assert_eq!((changes.added.len(), changes.updated.len(), changes.removed.len()), (1, 0, 0));
+        let id = changes.added[0].clone();
+
+        // Repeated events for an unchanged file are ignored
+        assert!(apply_changes(&mut library, &file_manager, std::slice::from_ref(&one)).unwrap().is_empty());
+
+        std::fs::write(&one, sine_wav(1.0, 8000, 440.0)).unwrap();
+        // New audio means a new fingerprint; the row is re-keyed rather than duplicated
+        let updated = apply_changes(&mut library, &file_manager, std::slice::from_ref(&one)).unwrap().updated;
+        assert_eq!(updated.len(), 1);
+        assert_ne!(updated[0], id);
+        let id = updated[0].clone();
+        assert_eq!(library.track_by_path(&one.to_string_lossy()).unwrap().unwrap().id, id);
+
+        // Files without an extension are only picked up once the folder opts in
+        let raw = root.join("album/raw");
+        std::fs::write(&raw, sine_wav(0.5, 8000, 440.0)).unwrap();
+        assert!(apply_changes(&mut library, &file_manager, std::slice::from_ref(&raw)).unwrap().is_empty());
+        library.set_include_extensionless(&root, true).unwrap();
+        assert!(library.folders().unwrap()[0].include_extensionless);
+        assert_eq!(apply_changes(&mut library, &file_manager, std::slice::from_ref(&raw)).unwrap().added.len(), 1);
+
+        std::fs::remove_dir_all(root.join("album")).unwrap();
+        let changes = apply_changes(&mut library, &file_manager, &[root.join("album")]).unwrap();
+        assert_eq!(changes.removed.len(), 2);
+        assert!(changes.removed.contains(&id));
+        assert!(library.tracks(&TrackQuery::default()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_cue_sheet_splits_watched_file() {
+        let dir = temp_dir("library-cue");
+        let audio = dir.join("album.wav");
+        std::fs::write(&audio, sine_wav(2.0, 8000, 440.0)).unwrap();
+        let sheet = dir.join("album.cue");
+
+        let mut library = Library::open_in_memory().unwrap();
+        let file_manager = FileManager::new();
+        library.add_folder(&dir).unwrap();
+        let whole = apply_changes(&mut library, &file_manager, std::slice::from_ref(&audio)).unwrap().added;
+        assert_eq!(whole.len(), 1);
+
+        std::fs::write(&sheet, "FILE \"album.wav\" WAVE\n  TRACK 01 AUDIO\n    TITLE \"One\"\n    INDEX 01 00:00:00\n  TRACK 02 AUDIO\n    TITLE \"Two\"\n    INDEX 01 00:01:00\n").unwrap();
+        let changes = apply_changes(&mut library, &file_manager, std::slice::from_ref(&sheet)).unwrap();
+        assert_eq!((changes.added.len(), changes.removed), (2, whole.clone()));
+        let two = library.track_by_path(&cue::virtual_path(&audio, 2).to_string_lossy()).unwrap().unwrap();
+        assert_eq!((two.title.as_str(), two.start_time, two.end_time), ("Two", Some(1.0), None));
+        assert!((two.duration - 1.0).abs() < 0.01);
+
+        std::fs::remove_file(&sheet).unwrap();
+        let changes = apply_changes(&mut library, &file_manager, &[sheet]).unwrap();
+        assert_eq!((changes.added, changes.removed.len()), (whole, 2));
+    }
+}
+
+// Tauri Commands
+
+use crate::scanner::{self, ScanHandle, ScanState};
+use tauri::{AppHandle, State};
+
+pub struct LibraryState {
+    pub library: Arc<Mutex<Library>>,
+}
+
+impl LibraryState {
+    pub fn new(path: &Path) -> Result<Self, LibraryError> {
+        Ok(Self {
+            library: Arc::new(Mutex::new(Library::open(path)?)),
+        })
+    }
+}
+
+/// Rescan one folder, probing changed files on the scanner's worker pool with
+/// the library unlocked in the meantime
+fn rescan_with_progress(
+    library: &Mutex<Library>,
+    root: &Path,
+    handle: &ScanHandle,
+    app: &AppHandle,
+) -> Result<ScanSummary, String> {
+    let file_manager = FileManager::new();
+    let plan = library.lock()
+        .map_err(|e| format!("Failed to lock library: {}", e))?
+        .plan_rescan(&file_manager, root)
+        .map_err(|e| e.to_string())?;
+
+    let paths: Vec<PathBuf> = plan.to_probe.iter().map(|(path, _)| path.clone()).collect();
+    let results = scanner::extract_all(&file_manager, &paths, handle, |progress| {
+        scanner::emit_progress(app, progress)
+    });
+    let probed = results.into_iter()
+        .zip(plan.to_probe.iter())
+        .filter_map(|(track, (_, stamp))| track.map(|track| (track, *stamp)))
+        .collect();
+
+    library.lock()
+        .map_err(|e| format!("Failed to lock library: {}", e))?
+        .apply_rescan(plan, probed)
+        .map_err(|e| e.to_string())
+}
+
+/// Register a folder (if needed) and bring its tracks up to date. Progress is
+/// reported through `library:scan_progress`; its `scan_id` can be passed to
+/// `library_cancel_scan`.
+#[tauri::command]
+pub async fn library_scan_folder(
+    path: String,
+    include_extensionless: Option<bool>,
+    library: State<'_, LibraryState>,
+    scans: State<'_, ScanState>,
+    app: AppHandle,
+) -> Result<ScanSummary, String> {
+    let library = library.library.clone();
+    let root = PathBuf::from(path);
+    let handle = scans.begin();
+
+    let summary = {
+        let root = root.clone();
+        let handle = handle.clone();
+        let app = app.clone();
+        tauri::async_runtime::spawn_blocking(move || {
+            // The option is remembered, so rescans and the watcher follow it too
+            if let Some(include) = include_extensionless {
+                if !root.is_dir() {
+                    return Err(format!("Path is not a directory: {}", root.display()));
+                }
+                let library = library.lock()
+                    .map_err(|e| format!("Failed to lock library: {}", e))?;
+                library.add_folder(&root)
+                    .and_then(|_| library.set_include_extensionless(&root, include))
+                    .map_err(|e| e.to_string())?;
+            }
+
+            rescan_with_progress(&library, &root, &handle, &app)
+        })
+        .await
+        .map_err(|e| format!("Scan task failed: {}", e))
+    };
+    scans.end(&handle);
+
+    let summary = summary??;
+    crate::watcher::watch_folder(&app, &root);
+    crate::smart_playlists::library_changed(&app);
+    Ok(summary)
+}
+
+/// Rescan every registered folder under a single cancellable scan
+#[tauri::command]
+pub async fn library_rescan(
+    library: State<'_, LibraryState>,
+    scans: State<'_, ScanState>,
+    app: AppHandle,
+) -> Result<Vec<ScanSummary>, String> {
+    let library = library.library.clone();
+    let handle = scans.begin();
+
+    let summaries = {
+        let handle = handle.clone();
+        let app = app.clone();
+        tauri::async_runtime::spawn_blocking(move || {
+            let folders = library.lock()
+                .map_err(|e| format!("Failed to lock library: {}", e))?
+                .folders()
+                .map_err(|e| e.to_string())?;
+
+            let mut summaries = Vec::new();
+            for folder in folders {
+                if handle.is_cancelled() {
+                    break;
+                }
+                match rescan_with_progress(&library, Path::new(&folder.path), &handle, &app) {
+                    Ok(summary) => summaries.push(summary),
+                    Err(e) => log::warn!("Failed to rescan {}: {}", folder.path, e),
+                }
+            }
+            Ok(summaries)
+        })
+        .await
+        .map_err(|e| format!("Scan task failed: {}", e))
+    };
+    scans.end(&handle);
+
+    let summaries = summaries?;
+    crate::smart_playlists::library_changed(&app);
+    summaries
+}
+
+#[tauri::command]
+pub fn library_remove_folder(
+    path: String,
+    library: State<'_, LibraryState>,
+    app: AppHandle,
+) -> Result<usize, String> {
+    let mut library = library.library.lock()
+        .map_err(|e| format!("Failed to lock library: {}", e))?;
+
+    let removed = library.remove_folder(Path::new(&path))
+        .map_err(|e| e.to_string())?;
+    drop(library);
+
+    crate::watcher::unwatch_folder(&app, Path::new(&path));
+    crate::smart_playlists::library_changed(&app);
+    Ok(removed)
+}
+
+#[tauri::command]
+pub fn library_get_folders(library: State<'_, LibraryState>) -> Result<Vec<LibraryFolder>, String> {
+    let library = library.library.lock()
+        .map_err(|e| format!("Failed to lock library: {}", e))?;
+
+    library.folders()
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn library_get_tracks(query: Option<TrackQuery>, library: State<'_, LibraryState>) -> Result<Vec<TrackMetadata>, String> {
+    let library = library.library.lock()
+        .map_err(|e| format!("Failed to lock library: {}", e))?;
+
+    library.tracks(&query.unwrap_or_default())
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn library_get_track(id: String, library: State<'_, LibraryState>) -> Result<Option<TrackMetadata>, String> {
+    let library = library.library.lock()
+        .map_err(|e| format!("Failed to lock library: {}", e))?;
+
+    library.track(&id)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn library_get_artists(library: State<'_, LibraryState>) -> Result<Vec<Artist>, String> {
+    let library = library.library.lock()
+        .map_err(|e| format!("Failed to lock library: {}", e))?;
+
+    library.artists()
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn library_get_albums(artist_id: Option<i64>, library: State<'_, LibraryState>) -> Result<Vec<Album>, String> {
+    let library = library.library.lock()
+        .map_err(|e| format!("Failed to lock library: {}", e))?;
+
+    library.albums(artist_id)
+        .map_err(|e| e.to_string())
+}
@@ -0,0 +1,397 @@
+// Lyrics Module
+// Finds a track's lyrics (sidecar LRC files, ID3 SYLT/USLT frames, LYRICS comments),
+// parses LRC timing, and follows playback to announce each line as it is reached
+
+use crate::audio_engine::AudioEngine;
+use crate::cue;
+use crate::file_manager;
+use crate::http_stream;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use symphonia::core::meta::StandardTagKey;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LyricsSource {
+    /// `.lrc` file next to the track
+    Lrc,
+    /// ID3 SYLT frame
+    Synchronised,
+    /// USLT frame, LYRICS comment or MP4 lyrics atom
+    Embedded,
+}
+
+/// Word timing from enhanced LRC (`<mm:ss.xx>`) or karaoke SYLT frames
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LyricWord {
+    pub time: f64,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LyricLine {
+    /// Seconds into the track; `None` for unsynchronised lyrics
+    pub time: Option<f64>,
+    pub text: String,
+    pub words: Vec<LyricWord>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Lyrics {
+    pub source: LyricsSource,
+    pub synced: bool,
+    pub lines: Vec<LyricLine>,
+}
+
+/// `mm:ss.xx` (or `h:mm:ss.xx`); anything else is not a time tag
+fn parse_time(tag: &str) -> Option<f64> {
+    let tag = tag.trim();
+    if !tag.contains(':') || !tag.chars().all(|c| c.is_ascii_digit() || c == ':' || c == '.') {
+        return None;
+    }
+    let mut seconds = 0.0;
+    for part in tag.split(':') {
+        seconds = seconds * 60.0 + part.parse::<f64>().ok()?;
+    }
+    Some(seconds)
+}
+
+/// Strip enhanced LRC word tags from a line, keeping their times
+fn parse_words(line: &str) -> (String, Vec<LyricWord>) {
+    let mut text = String::new();
+    let mut words = Vec::new();
+    let mut current = None;
+    let mut rest = line;
+    loop {
+        let tag = rest.find('<').and_then(|open| {
+            let close = open + rest[open..].find('>')?;
+            Some((open, close, parse_time(&rest[open + 1..close])?))
+        });
+        let before = tag.map_or(rest, |(open, _, _)| &rest[..open]);
+        text.push_str(before);
+        if let Some(time) = current.filter(|_| !before.trim().is_empty()) {
+            words.push(LyricWord { time, text: before.trim().to_string() });
+        }
+        match tag {
+            Some((_, close, time)) => {
+                current = Some(time);
+                rest = &rest[close + 1..];
+            }
+            None => break,
+        }
+    }
+    (text.trim().to_string(), words)
+}
+
+/// Parse LRC text. Lines may carry several timestamps; `[offset:+ms]` moves
+/// every line earlier. Text without any timestamps comes back unsynchronised.
+pub fn parse_lrc(text: &str) -> (bool, Vec<LyricLine>) {
+    let mut offset = 0.0;
+    let mut timed = Vec::new();
+    let mut plain = Vec::new();
+
+    for raw in text.lines() {
+        let mut rest = raw.trim();
+        let mut times = Vec::new();
+        let mut had_tags = false;
+        while let Some(close) = rest.strip_prefix('[').and_then(|r| r.find(']')) {
+            let tag = &rest[1..close + 1];
+            if let Some(time) = parse_time(tag) {
+                times.push(time);
+            } else if let Some((key, value)) = tag.split_once(':') {
+                // ID tags such as [ar:], [ti:] and [length:]
+                if key.trim().eq_ignore_ascii_case("offset") {
+                    offset = value.trim().trim_start_matches('+').parse::<f64>().unwrap_or(0.0) / 1000.0;
+                }
+            } else {
+                // "[Chorus]" and the like are part of the text
+                break;
+            }
+            had_tags = true;
+            rest = &rest[close + 2..];
+        }
+
+        let (line, words) = parse_words(rest);
+        if !times.is_empty() {
+            for time in times {
+                timed.push(LyricLine { time: Some(time), text: line.clone(), words: words.clone() });
+            }
+        } else if !had_tags {
+            plain.push(LyricLine { time: None, text: line, words });
+        }
+    }
+
+    if timed.is_empty() {
+        // Drop the blank lines around the text, keep the ones between verses
+        while plain.last().is_some_and(|l| l.text.is_empty()) {
+            plain.pop();
+        }
+        let first = plain.iter().position(|l| !l.text.is_empty()).unwrap_or(plain.len());
+        return (false, plain.split_off(first));
+    }
+
+    let shift = |time: f64| (time - offset).max(0.0);
+    for line in &mut timed {
+        line.time = line.time.map(shift);
+        for word in &mut line.words {
+            word.time = shift(word.time);
+        }
+    }
+    timed.sort_by(|a, b| a.time.unwrap_or(0.0).total_cmp(&b.time.unwrap_or(0.0)));
+    (true, timed)
+}
+
+fn sidecar_lrc(audio_path: &Path) -> Option<PathBuf> {
+    let stem = audio_path.file_stem()?;
+    std::fs::read_dir(audio_path.parent()?).ok()?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .find(|path| {
+            path.file_stem() == Some(stem)
+                && path.extension().and_then(|e| e.to_str()).is_some_and(|e| e.eq_ignore_ascii_case("lrc"))
+        })
+}
+
+/// SYLT entries are whole lines, or words when some start with a line break
+fn sylt_lines(content: &[(u32, String)]) -> Vec<LyricLine> {
+    let karaoke = content.iter().any(|(_, text)| text.starts_with(['\r', '\n']));
+    let mut lines: Vec<LyricLine> = Vec::new();
+    for (ms, text) in content {
+        let time = f64::from(*ms) / 1000.0;
+        let word = text.trim_matches(['\r', '\n']);
+        match lines.last_mut() {
+            Some(line) if karaoke && !text.starts_with(['\r', '\n']) => {
+                line.text.push_str(word);
+                line.words.push(LyricWord { time, text: word.trim().to_string() });
+            }
+            _ => lines.push(LyricLine {
+                time: Some(time),
+                text: word.to_string(),
+                words: vec![LyricWord { time, text: word.trim().to_string() }],
+            }),
+        }
+    }
+    for line in &mut lines {
+        line.text = line.text.trim().to_string();
+        if line.words.len() < 2 {
+            line.words.clear();
+        }
+    }
+    lines
+}
+
+fn read_lyrics(audio_path: &Path) -> Option<Lyrics> {
+    if let Some(text) = sidecar_lrc(audio_path).and_then(|path| std::fs::read(path).ok()) {
+        let (synced, lines) = parse_lrc(&cue::decode_text(&text));
+        if !lines.is_empty() {
+            return Some(Lyrics { source: LyricsSource::Lrc, synced, lines });
+        }
+    }
+
+    if let Ok(tag) = id3::Tag::read_from_path(audio_path) {
+        let sylt = tag.synchronised_lyrics()
+            .find(|sylt| sylt.timestamp_format == id3::frame::TimestampFormat::Ms && !sylt.content.is_empty());
+        if let Some(sylt) = sylt {
+            return Some(Lyrics { source: LyricsSource::Synchronised, synced: true, lines: sylt_lines(&sylt.content) });
+        }
+    }
+
+    // Taggers often store a whole LRC file in the plain lyrics field
+    let text = file_manager::read_tags(audio_path).into_iter()
+        .find(|tag| {
+            tag.std_key == Some(StandardTagKey::Lyrics)
+                || tag.key.eq_ignore_ascii_case("LYRICS")
+                || tag.key.eq_ignore_ascii_case("UNSYNCEDLYRICS")
+        })?
+        .value
+        .to_string();
+    let (synced, lines) = parse_lrc(&text);
+    (!lines.is_empty()).then_some(Lyrics { source: LyricsSource::Embedded, synced, lines })
+}
+
+/// Lyrics for the track at `file_path`. A CUE track gets the synchronised
+/// lines of its span of the file, with times relative to its own start.
+pub fn for_path(file_path: &str) -> Option<Lyrics> {
+    let (audio_path, cue_number) = cue::split_virtual_path(file_path);
+    let mut lyrics = read_lyrics(&audio_path)?;
+    let Some(number) = cue_number else { return Some(lyrics) };
+
+    let segment = cue::find_segment(&audio_path, number)?;
+    if !lyrics.synced {
+        return None;
+    }
+    let (start, end) = (segment.track.start, segment.end.unwrap_or(f64::INFINITY));
+    lyrics.lines.retain(|line| line.time.is_some_and(|time| time >= start && time < end));
+    for line in &mut lyrics.lines {
+        line.time = line.time.map(|time| time - start);
+        for word in &mut line.words {
+            word.time -= start;
+        }
+    }
+    (!lyrics.lines.is_empty()).then_some(lyrics)
+}
+
+/// Index of the line being sung at `position`
+pub fn line_at(lines: &[LyricLine], position: f64) -> Option<usize> {
+    lines.iter().rposition(|line| line.time.is_some_and(|time| time <= position))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{sine_wav, temp_dir};
+
+    #[test]
+    fn test_parse_lrc() {
+        let text = "[ar:Кино]\n[offset:+500]\n[00:10.00][00:30.50]Chorus line\n\n[00:20.00]<00:20.00>Hello <00:20.75>world\n[Bridge]\n";
+        let (synced, lines) = parse_lrc(text);
+        assert!(synced);
+        let times: Vec<Option<f64>> = lines.iter().map(|l| l.time).collect();
+        assert_eq!(times, vec![Some(9.5), Some(19.5), Some(30.0)]);
+        assert_eq!((lines[1].text.as_str(), lines[2].text.as_str()), ("Hello world", "Chorus line"));
+        assert_eq!(lines[1].words, vec![
+            LyricWord { time: 19.5, text: "Hello".into() },
+            LyricWord { time: 20.25, text: "world".into() },
+        ]);
+        assert_eq!(line_at(&lines, 5.0), None);
+        assert_eq!(line_at(&lines, 25.0), Some(1));
+
+        let (synced, lines) = parse_lrc("\n[Verse]\nFirst\n\nSecond\n\n");
+        assert!(!synced);
+        let texts: Vec<&str> = lines.iter().map(|l| l.text.as_str()).collect();
+        assert_eq!(texts, vec!["[Verse]", "First", "", "Second"]);
+    }
+
+    #[test]
+    fn test_sidecar_lrc_for_cue_track() {
+        let dir = temp_dir("lyrics-cue");
+        let audio = dir.join("album.wav");
+        std::fs::write(&audio, sine_wav(2.0, 8000, 440.0)).unwrap();
+        std::fs::write(dir.join("album.cue"), "FILE \"album.wav\" WAVE\n  TRACK 01 AUDIO\n    INDEX 01 00:00:00\n  TRACK 02 AUDIO\n    INDEX 01 00:01:00\n").unwrap();
+        // Windows-1251, as old rips often are
+        let lrc: Vec<u8> = b"[00:00.20]".iter().copied()
+            .chain([0xCF, 0xE5, 0xF0, 0xE2, 0xE0, 0xFF])
+            .chain(*b"\n[00:01.50]Second track\n")
+            .collect();
+        std::fs::write(dir.join("album.LRC"), lrc).unwrap();
+
+        let whole = for_path(&audio.to_string_lossy()).unwrap();
+        assert_eq!((whole.source, whole.lines[0].text.as_str()), (LyricsSource::Lrc, "Первая"));
+
+        let second = for_path(&cue::virtual_path(&audio, 2).to_string_lossy()).unwrap();
+        assert_eq!(second.lines.len(), 1);
+        assert_eq!(second.lines[0].time, Some(0.5));
+    }
+
+    #[test]
+    fn test_embedded_synchronised_lyrics() {
+        use id3::frame::{SynchronisedLyrics, SynchronisedLyricsType, TimestampFormat};
+        use id3::{Tag, TagLike, Version};
+
+        let path = temp_dir("lyrics-sylt").join("song.mp3");
+        std::fs::write(&path, [0xFF, 0xFB, 0x90, 0x00]).unwrap();
+        let mut tag = Tag::new();
+        tag.add_frame(SynchronisedLyrics {
+            lang: "eng".into(),
+            timestamp_format: TimestampFormat::Ms,
+            content_type: SynchronisedLyricsType::Lyrics,
+            description: String::new(),
+            content: vec![
+                (1000, "Kar".into()),
+                (1250, "a".into()),
+                (1500, "oke".into()),
+                (3000, "\nNext ".into()),
+                (3500, "line".into()),
+            ],
+        });
+        tag.write_to_path(&path, Version::Id3v24).unwrap();
+
+        let lyrics = for_path(&path.to_string_lossy()).unwrap();
+        assert_eq!((lyrics.source, lyrics.synced), (LyricsSource::Synchronised, true));
+        let texts: Vec<&str> = lyrics.lines.iter().map(|l| l.text.as_str()).collect();
+        assert_eq!(texts, vec!["Karaoke", "Next line"]);
+        assert_eq!(lyrics.lines[0].words.len(), 3);
+        assert_eq!(lyrics.lines[1].time, Some(3.0));
+    }
+}
+
+// Tauri Commands
+
+use crate::audio_engine::AudioEngineState;
+use crate::library::LibraryState;
+use tauri::{AppHandle, Emitter, Manager, State};
+
+/// Payload of `audio:lyric_line`; `index` is `None` before the first line
+#[derive(Debug, Clone, Serialize)]
+pub struct LyricLineEvent {
+    pub track: String,
+    pub index: Option<usize>,
+    pub time: Option<f64>,
+    pub text: Option<String>,
+}
+
+fn run_follower(engine: Arc<Mutex<AudioEngine>>, app: AppHandle) {
+    let mut generation = None;
+    let mut lyrics: Option<(String, Lyrics)> = None;
+    let mut current_line = None;
+    loop {
+        std::thread::sleep(POLL_INTERVAL);
+
+        let Ok(engine_guard) = engine.lock() else { return };
+        let track_generation = engine_guard.track_generation();
+        let state = engine_guard.get_state();
+        drop(engine_guard);
+        let Ok(state) = state else { continue };
+
+        if generation != Some(track_generation) {
+            generation = Some(track_generation);
+            current_line = None;
+            lyrics = state.current_track
+                .filter(|track| !http_stream::is_remote_url(track))
+                .and_then(|track| for_path(&track).map(|lyrics| (track, lyrics)))
+                .filter(|(_, lyrics)| lyrics.synced);
+        }
+
+        let Some((track, lyrics)) = &lyrics else { continue };
+        let line = line_at(&lyrics.lines, state.current_time);
+        if line != current_line {
+            current_line = line;
+            let line = line.map(|index| &lyrics.lines[index]);
+            let event = LyricLineEvent {
+                track: track.clone(),
+                index: current_line,
+                time: line.and_then(|l| l.time),
+                text: line.map(|l| l.text.clone()),
+            };
+            if let Err(e) = app.emit("audio:lyric_line", &event) {
+                log::warn!("Failed to emit lyric line: {}", e);
+            }
+        }
+    }
+}
+
+/// Start following playback for `audio:lyric_line` events
+pub fn start(app: &AppHandle) {
+    let engine = app.state::<AudioEngineState>().engine.clone();
+    let app = app.clone();
+    std::thread::spawn(move || run_follower(engine, app));
+}
+
+#[tauri::command]
+pub async fn get_lyrics(
+    track_id: String,
+    library: State<'_, LibraryState>,
+) -> Result<Option<Lyrics>, String> {
+    let track = library.library.lock()
+        .map_err(|e| format!("Failed to lock library: {}", e))?
+        .track(&track_id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Track not found: {}", track_id))?;
+
+    tauri::async_runtime::spawn_blocking(move || for_path(&track.file_path))
+        .await
+        .map_err(|e| format!("Lyrics lookup failed: {}", e))
+}
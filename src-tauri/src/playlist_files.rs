@@ -0,0 +1,655 @@
+// Playlist Files Module
+// Imports and exports M3U/M3U8, PLS and XSPF playlists, resolving entries against the library
+
+use crate::cue;
+use crate::errors::PlaylistError;
+use crate::file_manager::{FileManager, TrackMetadata};
+use crate::library::Library;
+use serde::{Deserialize, Serialize};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Mutex;
+use url::Url;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PlaylistFormat {
+    M3u,
+    M3u8,
+    Pls,
+    Xspf,
+}
+
+impl PlaylistFormat {
+    pub fn from_path(path: &Path) -> Result<Self, PlaylistError> {
+        let extension = path.extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_lowercase())
+            .unwrap_or_default();
+        match extension.as_str() {
+            "m3u" => Ok(Self::M3u),
+            "m3u8" => Ok(Self::M3u8),
+            "pls" => Ok(Self::Pls),
+            "xspf" => Ok(Self::Xspf),
+            _ => Err(PlaylistError::UnsupportedFormat(path.display().to_string())),
+        }
+    }
+
+    /// Guess from the contents, for files with a missing or wrong extension
+    fn sniff(content: &str) -> Option<Self> {
+        let start = content.trim_start();
+        if start.starts_with("#EXTM3U") {
+            Some(Self::M3u8)
+        } else if start.to_lowercase().starts_with("[playlist]") {
+            Some(Self::Pls)
+        } else if start.starts_with("<?xml") || start.starts_with("<playlist") {
+            Some(Self::Xspf)
+        } else {
+            None
+        }
+    }
+}
+
+/// One entry as written in a playlist file
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PlaylistEntry {
+    pub location: String,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub duration: Option<f64>,
+}
+
+/// `#EXTINF:<seconds>,<artist> - <title>` followed by the location line
+pub fn parse_m3u(content: &str) -> Vec<PlaylistEntry> {
+    let mut entries = Vec::new();
+    let mut pending = PlaylistEntry::default();
+
+    for line in content.lines().map(str::trim) {
+        if let Some(info) = line.strip_prefix("#EXTINF:") {
+            let (duration, display) = info.split_once(',').unwrap_or((info, ""));
+            // Attributes such as tvg-id="..." may follow the duration
+            let duration = duration.split_whitespace().next().and_then(|d| d.parse::<f64>().ok());
+            pending.duration = duration.filter(|d| *d > 0.0);
+            match display.split_once(" - ") {
+                Some((artist, title)) => {
+                    pending.artist = Some(artist.trim().to_string());
+                    pending.title = Some(title.trim().to_string());
+                }
+                None if !display.trim().is_empty() => pending.title = Some(display.trim().to_string()),
+                None => {}
+            }
+        } else if !line.is_empty() && !line.starts_with('#') {
+            pending.location = line.to_string();
+            entries.push(std::mem::take(&mut pending));
+        }
+    }
+    entries
+}
+
+/// `FileN=`, `TitleN=` and `LengthN=` keys, in entry-number order
+pub fn parse_pls(content: &str) -> Result<Vec<PlaylistEntry>, PlaylistError> {
+    let mut entries: std::collections::BTreeMap<u32, PlaylistEntry> = std::collections::BTreeMap::new();
+    let mut seen_header = false;
+
+    for line in content.lines().map(str::trim) {
+        if line.eq_ignore_ascii_case("[playlist]") {
+            seen_header = true;
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else { continue };
+        let key = key.trim().to_lowercase();
+        let value = value.trim();
+        let (field, number) = key.split_at(key.find(|c: char| c.is_ascii_digit()).unwrap_or(key.len()));
+        let Ok(number) = number.parse::<u32>() else { continue };
+
+        let entry = entries.entry(number).or_default();
+        match field {
+            "file" => entry.location = value.to_string(),
+            "title" => entry.title = Some(value.to_string()).filter(|t| !t.is_empty()),
+            "length" => entry.duration = value.parse::<f64>().ok().filter(|d| *d > 0.0),
+            _ => {}
+        }
+    }
+
+    if !seen_header {
+        return Err(PlaylistError::ParseError("Missing [playlist] section".to_string()));
+    }
+    Ok(entries.into_values().filter(|e| !e.location.is_empty()).collect())
+}
+
+fn unescape_xml(text: &str) -> String {
+    let text = text.trim();
+    if let Some(cdata) = text.strip_prefix("<![CDATA[").and_then(|t| t.strip_suffix("]]>")) {
+        return cdata.to_string();
+    }
+
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        result.push_str(&rest[..start]);
+        let Some(end) = rest[start..].find(';') else { break };
+        let entity = &rest[start + 1..start + end];
+        let decoded = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => entity.strip_prefix("#x").or_else(|| entity.strip_prefix("#X"))
+                .map(|hex| u32::from_str_radix(hex, 16))
+                .or_else(|| entity.strip_prefix('#').map(|dec| dec.parse::<u32>()))
+                .and_then(|code| code.ok())
+                .and_then(char::from_u32),
+        };
+        match decoded {
+            Some(c) => result.push(c),
+            None => result.push_str(&rest[start..=start + end]),
+        }
+        rest = &rest[start + end + 1..];
+    }
+    result.push_str(rest);
+    result
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Text of the first `<name>` element in `xml`, if any
+fn xml_element<'a>(xml: &'a str, name: &str) -> Option<&'a str> {
+    xml_element_end(xml, name).map(|(body, _)| body)
+}
+
+/// Like `xml_element`, plus the byte offset just past the element's end
+fn xml_element_end<'a>(xml: &'a str, name: &str) -> Option<(&'a str, usize)> {
+    let open = format!("<{}", name);
+    let close_tag = format!("</{}>", name);
+    let mut search = 0;
+    while let Some(found) = xml[search..].find(&open) {
+        let start = search + found + open.len();
+        // Skip longer names sharing the prefix, e.g. <trackList> for <track>
+        match xml[start..].chars().next() {
+            Some('>') => {
+                let body_start = start + 1;
+                let end = xml[body_start..].find(&close_tag)? + body_start;
+                return Some((&xml[body_start..end], end + close_tag.len()));
+            }
+            Some(c) if c.is_whitespace() => {
+                let close = xml[start..].find('>')? + start;
+                if xml[..close].ends_with('/') {
+                    return Some(("", close + 1));
+                }
+                let body_start = close + 1;
+                let end = xml[body_start..].find(&close_tag)? + body_start;
+                return Some((&xml[body_start..end], end + close_tag.len()));
+            }
+            _ => search = start,
+        }
+    }
+    None
+}
+
+/// Just enough XML for XSPF: the playlist title and each track's location and info
+pub fn parse_xspf(content: &str) -> Result<(Option<String>, Vec<PlaylistEntry>), PlaylistError> {
+    let track_list = xml_element(content, "trackList")
+        .ok_or_else(|| PlaylistError::ParseError("Missing <trackList>".to_string()))?;
+    let head = &content[..content.find("<trackList").unwrap_or(0)];
+    let name = xml_element(head, "title").map(unescape_xml).filter(|t| !t.is_empty());
+
+    let mut entries = Vec::new();
+    let mut rest = track_list;
+    while let Some((track, end)) = xml_element_end(rest, "track") {
+        rest = &rest[end..];
+
+        let Some(location) = xml_element(track, "location").map(unescape_xml) else { continue };
+        entries.push(PlaylistEntry {
+            location,
+            title: xml_element(track, "title").map(unescape_xml).filter(|t| !t.is_empty()),
+            artist: xml_element(track, "creator").map(unescape_xml).filter(|t| !t.is_empty()),
+            // XSPF durations are in milliseconds
+            duration: xml_element(track, "duration")
+                .and_then(|d| d.trim().parse::<f64>().ok())
+                .map(|ms| ms / 1000.0)
+                .filter(|d| *d > 0.0),
+        });
+    }
+    Ok((name, entries))
+}
+
+/// Where a playlist entry points
+#[derive(Debug, Clone, PartialEq)]
+pub enum Location {
+    File(PathBuf),
+    Url(String),
+}
+
+/// Turn an entry's location into a local path (relative ones are taken from
+/// the playlist's folder) or a remote URL
+pub fn resolve_location(location: &str, base_dir: &Path, format: PlaylistFormat) -> Option<Location> {
+    let location = location.trim();
+    if location.is_empty() {
+        return None;
+    }
+
+    if let Ok(url) = Url::parse(location) {
+        match url.scheme() {
+            "file" => return url.to_file_path().ok().map(Location::File),
+            // A single letter is a Windows drive, not a scheme
+            scheme if scheme.len() > 1 => return Some(Location::Url(location.to_string())),
+            _ => {}
+        }
+    }
+
+    // XSPF locations are URIs, so relative ones are percent-encoded
+    if format == PlaylistFormat::Xspf {
+        let base = Url::from_directory_path(base_dir).ok()?;
+        return base.join(location).ok()?.to_file_path().ok().map(Location::File);
+    }
+
+    // Playlists written on Windows use backslashes
+    let mut path = PathBuf::from(location);
+    if std::path::MAIN_SEPARATOR != '\\' && location.contains('\\') {
+        path = PathBuf::from(location.replace('\\', "/"));
+    }
+    if path.is_relative() {
+        path = base_dir.join(path);
+    }
+    Some(Location::File(path))
+}
+
+/// `path` relative to `base`, or `None` when they share no root (e.g. other drives)
+fn relative_path(path: &Path, base: &Path) -> Option<PathBuf> {
+    let path: Vec<Component> = path.components().collect();
+    let base: Vec<Component> = base.components().collect();
+    if path.first() != base.first() {
+        return None;
+    }
+
+    let common = path.iter().zip(&base).take_while(|(a, b)| a == b).count();
+    let mut relative = PathBuf::new();
+    for _ in common..base.len() {
+        relative.push("..");
+    }
+    for component in &path[common..] {
+        relative.push(component);
+    }
+    Some(relative)
+}
+
+/// Where a playlist file should point for `track`. Other players cannot open
+/// a track cut from a file by a CUE sheet, so such tracks are written as the
+/// whole audio file; only the title and duration beside it still name the track.
+fn export_location(track: &TrackMetadata, base_dir: &Path, relative: bool, format: PlaylistFormat) -> String {
+    if track.file_path.contains("://") {
+        return track.file_path.clone();
+    }
+    let path = cue::audio_path(&track.file_path);
+    let path = path.as_path();
+    if !path.is_absolute() {
+        return path.to_string_lossy().to_string();
+    }
+
+    if format == PlaylistFormat::Xspf {
+        let Ok(file_url) = Url::from_file_path(path) else { return path.to_string_lossy().to_string() };
+        if relative {
+            let relative = Url::from_directory_path(base_dir).ok()
+                .and_then(|base| base.make_relative(&file_url));
+            if let Some(relative) = relative {
+                return relative;
+            }
+        }
+        return file_url.to_string();
+    }
+
+    match relative.then(|| relative_path(path, base_dir)).flatten() {
+        Some(relative) => relative.to_string_lossy().to_string(),
+        None => path.to_string_lossy().to_string(),
+    }
+}
+
+fn seconds(duration: f64) -> i64 {
+    if duration > 0.0 {
+        duration.round() as i64
+    } else {
+        -1
+    }
+}
+
+/// Playlist file contents for `tracks`; with `relative`, local files are written
+/// relative to `base_dir` where possible
+pub fn render(format: PlaylistFormat, name: &str, tracks: &[TrackMetadata], base_dir: &Path, relative: bool) -> String {
+    let mut out = String::new();
+    match format {
+        PlaylistFormat::M3u | PlaylistFormat::M3u8 => {
+            out.push_str("#EXTM3U\n");
+            if !name.is_empty() {
+                out.push_str(&format!("#PLAYLIST:{}\n", name));
+            }
+            for track in tracks {
+                out.push_str(&format!("#EXTINF:{},{} - {}\n", seconds(track.duration), track.artist, track.title));
+                out.push_str(&export_location(track, base_dir, relative, format));
+                out.push('\n');
+            }
+        }
+        PlaylistFormat::Pls => {
+            out.push_str("[playlist]\n");
+            for (i, track) in tracks.iter().enumerate() {
+                let n = i + 1;
+                out.push_str(&format!("File{}={}\n", n, export_location(track, base_dir, relative, format)));
+                out.push_str(&format!("Title{}={} - {}\n", n, track.artist, track.title));
+                out.push_str(&format!("Length{}={}\n", n, seconds(track.duration)));
+            }
+            out.push_str(&format!("NumberOfEntries={}\nVersion=2\n", tracks.len()));
+        }
+        PlaylistFormat::Xspf => {
+            out.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+            out.push_str("<playlist version=\"1\" xmlns=\"http://xspf.org/ns/0/\">\n");
+            out.push_str(&format!("  <title>{}</title>\n  <trackList>\n", escape_xml(name)));
+            for track in tracks {
+                out.push_str("    <track>\n");
+                out.push_str(&format!(
+                    "      <location>{}</location>\n",
+                    escape_xml(&export_location(track, base_dir, relative, format))
+                ));
+                out.push_str(&format!("      <title>{}</title>\n", escape_xml(&track.title)));
+                out.push_str(&format!("      <creator>{}</creator>\n", escape_xml(&track.artist)));
+                if let Some(album) = &track.album {
+                    out.push_str(&format!("      <album>{}</album>\n", escape_xml(album)));
+                }
+                if track.duration > 0.0 {
+                    out.push_str(&format!("      <duration>{}</duration>\n", (track.duration * 1000.0).round() as u64));
+                }
+                out.push_str("    </track>\n");
+            }
+            out.push_str("  </trackList>\n</playlist>\n");
+        }
+    }
+    out
+}
+
+/// An entry that could not be found, by its 1-based position in the file
+#[derive(Debug, Clone, Serialize)]
+pub struct MissingEntry {
+    pub position: usize,
+    pub location: String,
+    pub title: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportedPlaylist {
+    pub name: String,
+    pub format: PlaylistFormat,
+    pub tracks: Vec<TrackMetadata>,
+    pub missing: Vec<MissingEntry>,
+}
+
+/// Find the library track for a local file, probing (and storing) files the
+/// library does not know yet. A path that no longer exists is looked up by
+/// file name, in case the file was moved within the library.
+fn match_file(library: &Mutex<Library>, file_manager: &FileManager, path: &Path) -> Option<TrackMetadata> {
+    let file_path = path.to_string_lossy();
+
+    if path.is_file() {
+        if let Ok(Some(track)) = library.lock().ok()?.fresh_track_by_path(&file_path) {
+            return Some(track);
+        }
+        // Probing can take a while; the library is only locked to store the result
+        let mut track = file_manager.metadata_or_fallback(path);
+        match library.lock().ok()?.upsert_track(&track) {
+            Ok(id) => track.id = id,
+            Err(e) => log::warn!("Failed to store {} in library: {}", file_path, e),
+        }
+        return Some(track);
+    }
+
+    let file_name = path.file_name()?.to_str()?;
+    let mut candidates = library.lock().ok()?.tracks_by_file_name(file_name).ok()?;
+    candidates.retain(|track| Path::new(&track.file_path).is_file());
+    // Only trust an unambiguous match
+    (candidates.len() == 1).then(|| candidates.remove(0))
+}
+
+/// Read a playlist file and match its entries
+pub fn import(library: &Mutex<Library>, path: &Path) -> Result<ImportedPlaylist, PlaylistError> {
+    let content = cue::decode_text(&std::fs::read(path)?);
+    let format = PlaylistFormat::from_path(path)
+        .or_else(|e| PlaylistFormat::sniff(&content).ok_or(e))?;
+    let base_dir = path.parent().unwrap_or(Path::new(""));
+
+    let (name, entries) = match format {
+        PlaylistFormat::M3u | PlaylistFormat::M3u8 => (None, parse_m3u(&content)),
+        PlaylistFormat::Pls => (None, parse_pls(&content)?),
+        PlaylistFormat::Xspf => parse_xspf(&content)?,
+    };
+
+    let file_manager = FileManager::new();
+    let mut tracks = Vec::new();
+    let mut missing = Vec::new();
+    for (index, entry) in entries.into_iter().enumerate() {
+        let track = match resolve_location(&entry.location, base_dir, format) {
+            Some(Location::File(path)) => match_file(library, &file_manager, &path),
+            // Streams are kept as they are; the player opens them with `audio_load_track`
+            Some(Location::Url(url)) => Some(TrackMetadata {
+                id: format!("{:x}", md5::compute(url.as_bytes())),
+                title: entry.title.clone().unwrap_or_else(|| url.clone()),
+                artist: entry.artist.clone().unwrap_or_else(|| "Unknown Artist".to_string()),
+                duration: entry.duration.unwrap_or(0.0),
+                file_path: url,
+                ..Default::default()
+            }),
+            None => None,
+        };
+
+        match track {
+            Some(track) => tracks.push(track),
+            None => missing.push(MissingEntry {
+                position: index + 1,
+                location: entry.location,
+                title: entry.title,
+            }),
+        }
+    }
+
+    let name = name.unwrap_or_else(|| file_manager.get_file_name(path));
+    log::info!("Imported playlist {}: {} tracks, {} missing", path.display(), tracks.len(), missing.len());
+    Ok(ImportedPlaylist { name, format, tracks, missing })
+}
+
+/// Write `tracks` to `path` in `format` (by default, the one its extension names)
+pub fn export(
+    path: &Path,
+    name: &str,
+    tracks: &[TrackMetadata],
+    format: Option<PlaylistFormat>,
+    relative: bool,
+) -> Result<(), PlaylistError> {
+    let format = match format {
+        Some(format) => format,
+        None => PlaylistFormat::from_path(path)?,
+    };
+    let base_dir = path.parent().unwrap_or(Path::new(""));
+    let content = render(format, name, tracks, base_dir, relative);
+
+    let file_name = path.file_name()
+        .ok_or_else(|| PlaylistError::UnsupportedFormat(path.display().to_string()))?;
+    // Hidden and named after the playlist, so it never clobbers another file
+    let temp_path = path.with_file_name(format!(".{}.tmp", file_name.to_string_lossy()));
+    let result = std::fs::write(&temp_path, content)
+        .and_then(|_| std::fs::rename(&temp_path, path));
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp_path);
+    }
+    Ok(result?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{rescan, sine_wav, temp_dir};
+
+    #[test]
+    fn test_parse_m3u_and_resolve() {
+        let content = "\u{feff}#EXTM3U\n#EXTINF:245,Кино - Кукушка\nКино/01 Кукушка.mp3\n\n#EXTINF:-1 tvg-id=\"x\",Radio\nhttp://radio.example/stream\n/abs/song.flac\nfolder\\win.mp3\n";
+        let entries = parse_m3u(&cue::decode_text(content.as_bytes()));
+        assert_eq!(entries.len(), 4);
+        assert_eq!(entries[0].artist.as_deref(), Some("Кино"));
+        assert_eq!(entries[0].title.as_deref(), Some("Кукушка"));
+        assert_eq!(entries[0].duration, Some(245.0));
+        assert_eq!((entries[1].title.as_deref(), entries[1].duration), (Some("Radio"), None));
+        assert_eq!(entries[2], PlaylistEntry { location: "/abs/song.flac".into(), ..Default::default() });
+
+        let base = Path::new("/music/lists");
+        let resolve = |e: &PlaylistEntry| resolve_location(&e.location, base, PlaylistFormat::M3u8).unwrap();
+        assert_eq!(resolve(&entries[0]), Location::File(base.join("Кино/01 Кукушка.mp3")));
+        assert_eq!(resolve(&entries[1]), Location::Url("http://radio.example/stream".into()));
+        assert_eq!(resolve(&entries[2]), Location::File("/abs/song.flac".into()));
+        assert_eq!(resolve(&entries[3]), Location::File(base.join("folder/win.mp3")));
+
+        // Windows-1251 fallback for legacy files
+        assert_eq!(parse_m3u(&cue::decode_text(b"\xca\xe8\xed\xee.mp3"))[0].location, "Кино.mp3");
+    }
+
+    #[test]
+    fn test_render_and_parse_round_trip() {
+        let base = Path::new("/music/lists");
+        let tracks = vec![
+            TrackMetadata {
+                title: "Звезда & <Солнце>".into(),
+                artist: "Кино".into(),
+                duration: 225.4,
+                file_path: "/music/Кино/02 Звезда по имени Солнце.mp3".into(),
+                ..Default::default()
+            },
+            TrackMetadata {
+                title: "Radio".into(),
+                artist: "Unknown Artist".into(),
+                file_path: "http://radio.example/stream".into(),
+                ..Default::default()
+            },
+        ];
+
+        let m3u = render(PlaylistFormat::M3u8, "Mix", &tracks, base, true);
+        assert!(m3u.contains("#EXTINF:225,Кино - Звезда & <Солнце>\n../Кино/02 Звезда по имени Солнце.mp3\n"));
+        assert!(m3u.contains("#EXTINF:-1,Unknown Artist - Radio\nhttp://radio.example/stream\n"));
+
+        let pls = parse_pls(&render(PlaylistFormat::Pls, "Mix", &tracks, base, false)).unwrap();
+        assert_eq!(pls[0].location, "/music/Кино/02 Звезда по имени Солнце.mp3");
+        assert_eq!(pls[0].duration, Some(225.0));
+        assert_eq!(pls[1].duration, None);
+
+        let xspf = render(PlaylistFormat::Xspf, "Mix & Match", &tracks, base, true);
+        assert!(xspf.contains("<location>../%D0%9A%D0%B8%D0%BD%D0%BE/02%20"));
+        let (name, entries) = parse_xspf(&xspf).unwrap();
+        assert_eq!(name.as_deref(), Some("Mix & Match"));
+        assert_eq!(entries[0].title.as_deref(), Some("Звезда & <Солнце>"));
+        assert_eq!(entries[0].duration, Some(225.4));
+        assert_eq!(
+            resolve_location(&entries[0].location, base, PlaylistFormat::Xspf),
+            Some(Location::File(tracks[0].file_path.clone().into()))
+        );
+
+        // CUE tracks point at their whole audio file
+        let cue_track = TrackMetadata {
+            title: "One".into(),
+            artist: "Кино".into(),
+            duration: 60.0,
+            file_path: "/music/album.flac#cue:1".into(),
+            ..Default::default()
+        };
+        let m3u = render(PlaylistFormat::M3u8, "Mix", &[cue_track], base, true);
+        assert!(m3u.contains("#EXTINF:60,Кино - One\n../album.flac\n"));
+    }
+
+    #[test]
+    fn test_xspf_skips_empty_tracks() {
+        let xspf = "<playlist><trackList>\
+            <track></track>\
+            <track><location>a.mp3</location></track>\
+            <track />\
+            <track><location>a.mp3</location></track>\
+            <track><location>b.mp3</location></track>\
+            </trackList></playlist>";
+        let (_, entries) = parse_xspf(xspf).unwrap();
+        let locations: Vec<&str> = entries.iter().map(|e| e.location.as_str()).collect();
+        assert_eq!(locations, vec!["a.mp3", "a.mp3", "b.mp3"]);
+    }
+
+    #[test]
+    fn test_import_matches_library_and_reports_missing() {
+        let dir = temp_dir("playlist-import");
+        std::fs::create_dir_all(dir.join("music/moved")).unwrap();
+        std::fs::write(dir.join("music/one.wav"), sine_wav(0.2, 8000, 440.0)).unwrap();
+        std::fs::write(dir.join("music/moved/two.wav"), sine_wav(0.2, 8000, 660.0)).unwrap();
+
+        let library = Mutex::new(Library::open_in_memory().unwrap());
+        rescan(&mut library.lock().unwrap(), &FileManager::new(), &dir.join("music")).unwrap();
+
+        let playlist = dir.join("list.m3u");
+        std::fs::write(&playlist, "#EXTM3U\nmusic/one.wav\nmusic/two.wav\n#EXTINF:10,Gone - Missing\nmusic/missing.wav\n").unwrap();
+
+        let imported = import(&library, &playlist).unwrap();
+        assert_eq!(imported.name, "list");
+        assert_eq!(imported.format, PlaylistFormat::M3u);
+        let paths: Vec<&str> = imported.tracks.iter().map(|t| t.file_path.as_str()).collect();
+        assert_eq!(paths, vec![
+            dir.join("music/one.wav").to_string_lossy(),
+            dir.join("music/moved/two.wav").to_string_lossy(),
+        ]);
+        assert_eq!(imported.missing.len(), 1);
+        assert_eq!(imported.missing[0].position, 3);
+        assert_eq!(imported.missing[0].title.as_deref(), Some("Missing"));
+
+        // And back out again, relative to a different folder
+        let exported = dir.join("out/list.xspf");
+        std::fs::create_dir_all(dir.join("out")).unwrap();
+        std::fs::write(dir.join("out/list.tmp"), "keep").unwrap();
+        export(&exported, "Round trip", &imported.tracks, None, true).unwrap();
+        // A user file sharing the playlist's stem is left alone
+        assert_eq!(std::fs::read_to_string(dir.join("out/list.tmp")).unwrap(), "keep");
+        assert!(!dir.join("out/.list.xspf.tmp").exists());
+        let again = import(&library, &exported).unwrap();
+        assert_eq!(again.name, "Round trip");
+        assert_eq!(again.tracks.len(), 2);
+        assert!(again.missing.is_empty());
+    }
+}
+
+// Tauri Commands
+
+use crate::library::LibraryState;
+use tauri::State;
+
+/// Import an M3U/M3U8, PLS or XSPF file. Entries that match nothing on disk
+/// or in the library are returned in `missing` instead of being dropped.
+#[tauri::command]
+pub async fn playlist_import(path: String, library: State<'_, LibraryState>) -> Result<ImportedPlaylist, String> {
+    let library = library.library.clone();
+
+    tauri::async_runtime::spawn_blocking(move || {
+        import(&library, Path::new(&path)).map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| format!("Playlist import failed: {}", e))?
+}
+
+/// Export tracks as a playlist file; paths are relative to the playlist's
+/// folder unless `relative_paths` is false
+#[tauri::command]
+pub async fn playlist_export(
+    path: String,
+    tracks: Vec<TrackMetadata>,
+    name: Option<String>,
+    format: Option<PlaylistFormat>,
+    relative_paths: Option<bool>,
+) -> Result<(), String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let path = PathBuf::from(path);
+        let name = name.unwrap_or_else(|| FileManager::new().get_file_name(&path));
+        export(&path, &name, &tracks, format, relative_paths.unwrap_or(true))
+            .map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| format!("Playlist export failed: {}", e))?
+}
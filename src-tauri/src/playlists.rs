@@ -0,0 +1,462 @@
+// Playlists Module
+// Named, ordered lists of library tracks stored alongside the library database
+
+use crate::errors::PlaylistError;
+use crate::file_manager::TrackMetadata;
+use crate::library::{self, Library, TRACK_COLUMNS};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Playlist {
+    pub id: i64,
+    pub name: String,
+    pub created: i64,
+    pub modified: i64,
+    pub track_count: u32,
+    /// Total of the tracks still in the library, in seconds
+    pub duration: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PlaylistItem {
+    pub position: usize,
+    pub track_id: String,
+    /// `None` while the track is not in the library (e.g. its folder was removed)
+    pub track: Option<TrackMetadata>,
+}
+
+/// Payload of `playlist:changed`; `playlist` is `None` once it has been deleted
+#[derive(Debug, Clone, Serialize)]
+pub struct PlaylistChanged {
+    pub playlist_id: i64,
+    pub playlist: Option<Playlist>,
+}
+
+pub(crate) fn valid_name(name: &str) -> Result<String, PlaylistError> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(PlaylistError::InvalidName(name.to_string()));
+    }
+    Ok(name.to_string())
+}
+
+fn entries(conn: &Connection, id: i64) -> Result<Vec<String>, PlaylistError> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT track_id FROM playlist_tracks WHERE playlist_id = ?1 ORDER BY position",
+    )?;
+    let entries = stmt
+        .query_map([id], |row| row.get(0))?
+        .collect::<Result<Vec<String>, _>>()?;
+    Ok(entries)
+}
+
+fn write_entries(conn: &Connection, id: i64, track_ids: &[String]) -> Result<(), PlaylistError> {
+    conn.execute("DELETE FROM playlist_tracks WHERE playlist_id = ?1", [id])?;
+    let mut stmt = conn.prepare_cached(
+        "INSERT INTO playlist_tracks (playlist_id, position, track_id) VALUES (?1, ?2, ?3)",
+    )?;
+    for (position, track_id) in track_ids.iter().enumerate() {
+        stmt.execute(params![id, position as i64, track_id])?;
+    }
+    conn.execute("UPDATE playlists SET modified = ?1 WHERE id = ?2", params![library::now(), id])?;
+    Ok(())
+}
+
+/// Only tracks the library knows can be added
+fn check_tracks(conn: &Connection, track_ids: &[String]) -> Result<(), PlaylistError> {
+    let mut stmt = conn.prepare_cached("SELECT 1 FROM tracks WHERE id = ?1")?;
+    for track_id in track_ids {
+        if !stmt.exists([track_id])? {
+            return Err(PlaylistError::TrackNotFound(track_id.clone()));
+        }
+    }
+    Ok(())
+}
+
+/// Take the entries at `positions` out and put them back, in their current
+/// order, before the entry that was at `to` (or at the end)
+fn move_entries(entries: &mut Vec<String>, positions: &[usize], to: usize) -> Result<(), PlaylistError> {
+    let mut positions = positions.to_vec();
+    positions.sort_unstable();
+    positions.dedup();
+    if let Some(&bad) = positions.iter().find(|&&p| p >= entries.len()) {
+        return Err(PlaylistError::InvalidPosition(bad));
+    }
+    if to > entries.len() {
+        return Err(PlaylistError::InvalidPosition(to));
+    }
+
+    let target = to - positions.iter().filter(|&&p| p < to).count();
+    let mut moved: Vec<String> = positions.iter().rev().map(|&p| entries.remove(p)).collect();
+    moved.reverse();
+    entries.splice(target..target, moved);
+    Ok(())
+}
+
+impl Library {
+    pub fn playlists(&self) -> Result<Vec<Playlist>, PlaylistError> {
+        self.query_playlists(None)
+    }
+
+    pub fn playlist(&self, id: i64) -> Result<Playlist, PlaylistError> {
+        self.query_playlists(Some(id))?
+            .pop()
+            .ok_or(PlaylistError::NotFound(id))
+    }
+
+    fn query_playlists(&self, id: Option<i64>) -> Result<Vec<Playlist>, PlaylistError> {
+        let mut stmt = self.conn.prepare_cached(
+            "SELECT p.id, p.name, p.created, p.modified, COUNT(pt.track_id), COALESCE(SUM(t.duration), 0)
+             FROM playlists p
+             LEFT JOIN playlist_tracks pt ON pt.playlist_id = p.id
+             LEFT JOIN tracks t ON t.id = pt.track_id
+             WHERE ?1 IS NULL OR p.id = ?1
+             GROUP BY p.id ORDER BY p.name COLLATE NOCASE, p.id",
+        )?;
+        let playlists = stmt
+            .query_map([id], |row| Ok(Playlist {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                created: row.get(2)?,
+                modified: row.get(3)?,
+                track_count: row.get(4)?,
+                duration: row.get(5)?,
+            }))?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(playlists)
+    }
+
+    /// Entries in order, with the library's current metadata for each
+    pub fn playlist_items(&self, id: i64) -> Result<Vec<PlaylistItem>, PlaylistError> {
+        self.playlist(id)?;
+
+        let sql = format!(
+            "SELECT DISTINCT {} JOIN playlist_tracks pt ON pt.track_id = t.id WHERE pt.playlist_id = ?1",
+            TRACK_COLUMNS
+        );
+        let mut stmt = self.conn.prepare_cached(&sql)?;
+        let tracks: HashMap<String, TrackMetadata> = stmt
+            .query_map([id], library::track_from_row)?
+            .map(|track| track.map(|t| (t.id.clone(), t)))
+            .collect::<Result<_, _>>()?;
+
+        let items: Vec<PlaylistItem> = entries(&self.conn, id)?
+            .into_iter()
+            .enumerate()
+            .map(|(position, track_id)| PlaylistItem {
+                position,
+                track: tracks.get(&track_id).cloned(),
+                track_id,
+            })
+            .collect();
+        Ok(items)
+    }
+
+    pub fn create_playlist(&mut self, name: &str, track_ids: &[String]) -> Result<Playlist, PlaylistError> {
+        let name = valid_name(name)?;
+        let tx = self.conn.transaction()?;
+        check_tracks(&tx, track_ids)?;
+        let now = library::now();
+        tx.execute(
+            "INSERT INTO playlists (name, created, modified) VALUES (?1, ?2, ?2)",
+            params![name, now],
+        )?;
+        let id = tx.last_insert_rowid();
+        write_entries(&tx, id, track_ids)?;
+        tx.commit()?;
+        self.playlist(id)
+    }
+
+    pub fn rename_playlist(&self, id: i64, name: &str) -> Result<Playlist, PlaylistError> {
+        let name = valid_name(name)?;
+        let updated = self.conn.execute(
+            "UPDATE playlists SET name = ?1, modified = ?2 WHERE id = ?3",
+            params![name, library::now(), id],
+        )?;
+        if updated == 0 {
+            return Err(PlaylistError::NotFound(id));
+        }
+        self.playlist(id)
+    }
+
+    pub fn delete_playlist(&self, id: i64) -> Result<(), PlaylistError> {
+        match self.conn.execute("DELETE FROM playlists WHERE id = ?1", [id])? {
+            0 => Err(PlaylistError::NotFound(id)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Copy a playlist; the copy is called "<name> copy" unless named
+    pub fn duplicate_playlist(&mut self, id: i64, name: Option<&str>) -> Result<Playlist, PlaylistError> {
+        let original: Option<String> = self.conn
+            .query_row("SELECT name FROM playlists WHERE id = ?1", [id], |row| row.get(0))
+            .optional()?;
+        let original = original.ok_or(PlaylistError::NotFound(id))?;
+        let name = match name {
+            Some(name) => valid_name(name)?,
+            None => format!("{} copy", original),
+        };
+
+        let tx = self.conn.transaction()?;
+        let now = library::now();
+        tx.execute(
+            "INSERT INTO playlists (name, created, modified) VALUES (?1, ?2, ?2)",
+            params![name, now],
+        )?;
+        let copy = tx.last_insert_rowid();
+        tx.execute(
+            "INSERT INTO playlist_tracks (playlist_id, position, track_id)
+             SELECT ?1, position, track_id FROM playlist_tracks WHERE playlist_id = ?2",
+            [copy, id],
+        )?;
+        tx.commit()?;
+        self.playlist(copy)
+    }
+
+    /// Insert tracks before `position`, or append them
+    pub fn add_to_playlist(&mut self, id: i64, track_ids: &[String], position: Option<usize>) -> Result<Playlist, PlaylistError> {
+        self.edit_playlist(id, |conn, entries| {
+            check_tracks(conn, track_ids)?;
+            let position = position.unwrap_or(entries.len());
+            if position > entries.len() {
+                return Err(PlaylistError::InvalidPosition(position));
+            }
+            entries.splice(position..position, track_ids.iter().cloned());
+            Ok(())
+        })
+    }
+
+    pub fn remove_from_playlist(&mut self, id: i64, positions: &[usize]) -> Result<Playlist, PlaylistError> {
+        self.edit_playlist(id, |_, entries| {
+            let mut positions = positions.to_vec();
+            positions.sort_unstable_by(|a, b| b.cmp(a));
+            positions.dedup();
+            if let Some(&bad) = positions.iter().find(|&&p| p >= entries.len()) {
+                return Err(PlaylistError::InvalidPosition(bad));
+            }
+            for position in positions {
+                entries.remove(position);
+            }
+            Ok(())
+        })
+    }
+
+    /// Drag and drop: move the entries at `positions` to before the entry at `to`
+    pub fn move_in_playlist(&mut self, id: i64, positions: &[usize], to: usize) -> Result<Playlist, PlaylistError> {
+        self.edit_playlist(id, |_, entries| move_entries(entries, positions, to))
+    }
+
+    /// Rewrite a playlist's entries in one transaction
+    fn edit_playlist<F>(&mut self, id: i64, edit: F) -> Result<Playlist, PlaylistError>
+    where
+        F: FnOnce(&Connection, &mut Vec<String>) -> Result<(), PlaylistError>,
+    {
+        self.playlist(id)?;
+        let tx = self.conn.transaction()?;
+        let mut track_ids = entries(&tx, id)?;
+        edit(&tx, &mut track_ids)?;
+        write_entries(&tx, id, &track_ids)?;
+        tx.commit()?;
+        self.playlist(id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn library_with_tracks(ids: &[&str]) -> Library {
+        let library = Library::open_in_memory().unwrap();
+        for id in ids {
+            library.upsert_track(&TrackMetadata {
+                id: id.to_string(),
+                title: id.to_uppercase(),
+                artist: "Artist".into(),
+                duration: 60.0,
+                file_path: format!("/nonexistent/{}.mp3", id),
+                ..Default::default()
+            }).unwrap();
+        }
+        library
+    }
+
+    fn ids(library: &Library, playlist: i64) -> Vec<String> {
+        library.playlist_items(playlist).unwrap().into_iter().map(|item| item.track_id).collect()
+    }
+
+    #[test]
+    fn test_playlist_editing() {
+        let mut library = library_with_tracks(&["a", "b", "c", "d"]);
+        let owned = |ids: &[&str]| ids.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+        let playlist = library.create_playlist("  Road trip ", &owned(&["a", "b"])).unwrap();
+        assert_eq!((playlist.name.as_str(), playlist.track_count, playlist.duration), ("Road trip", 2, 120.0));
+        assert!(matches!(library.create_playlist(" ", &[]), Err(PlaylistError::InvalidName(_))));
+        assert!(matches!(
+            library.add_to_playlist(playlist.id, &owned(&["zzz"]), None),
+            Err(PlaylistError::TrackNotFound(_))
+        ));
+
+        library.add_to_playlist(playlist.id, &owned(&["c", "d"]), None).unwrap();
+        library.add_to_playlist(playlist.id, &owned(&["a"]), Some(1)).unwrap();
+        assert_eq!(ids(&library, playlist.id), ["a", "a", "b", "c", "d"]);
+
+        library.remove_from_playlist(playlist.id, &[0, 0]).unwrap();
+        assert_eq!(ids(&library, playlist.id), ["a", "b", "c", "d"]);
+
+        // Move "a" and "c" to the end, then "d" to the front
+        library.move_in_playlist(playlist.id, &[2, 0], 4).unwrap();
+        assert_eq!(ids(&library, playlist.id), ["b", "d", "a", "c"]);
+        library.move_in_playlist(playlist.id, &[1], 0).unwrap();
+        assert_eq!(ids(&library, playlist.id), ["d", "b", "a", "c"]);
+        assert!(matches!(library.move_in_playlist(playlist.id, &[4], 0), Err(PlaylistError::InvalidPosition(4))));
+
+        let copy = library.duplicate_playlist(playlist.id, None).unwrap();
+        assert_eq!(copy.name, "Road trip copy");
+        assert_eq!(ids(&library, copy.id), ["d", "b", "a", "c"]);
+
+        library.rename_playlist(playlist.id, "Commute").unwrap();
+        library.delete_playlist(copy.id).unwrap();
+        let names: Vec<String> = library.playlists().unwrap().into_iter().map(|p| p.name).collect();
+        assert_eq!(names, ["Commute"]);
+        assert!(matches!(library.playlist_items(copy.id), Err(PlaylistError::NotFound(_))));
+    }
+
+    #[test]
+    fn test_entries_follow_track_ids() {
+        let mut library = library_with_tracks(&["a", "b"]);
+        let playlist = library.create_playlist("Mix", &["a".to_string(), "b".to_string()]).unwrap();
+
+        // The file at a's path was re-encoded and got a new fingerprint
+        library.upsert_track(&TrackMetadata {
+            id: "a2".into(),
+            title: "A".into(),
+            artist: "Artist".into(),
+            file_path: "/nonexistent/a.mp3".into(),
+            ..Default::default()
+        }).unwrap();
+        library.conn.execute("DELETE FROM tracks WHERE id = 'b'", []).unwrap();
+
+        let items = library.playlist_items(playlist.id).unwrap();
+        assert_eq!(items[0].track_id, "a2");
+        assert_eq!(items[0].track.as_ref().unwrap().title, "A");
+        assert_eq!(items[1].track_id, "b");
+        assert!(items[1].track.is_none());
+    }
+}
+
+// Tauri Commands
+
+use crate::library::LibraryState;
+use tauri::{AppHandle, Emitter, State};
+
+fn emit_changed(app: &AppHandle, playlist_id: i64, playlist: Option<Playlist>) {
+    if let Err(e) = app.emit("playlist:changed", PlaylistChanged { playlist_id, playlist }) {
+        log::warn!("Failed to emit playlist change: {}", e);
+    }
+}
+
+/// Run a playlist edit with the library locked and announce the result
+fn update<F>(library: &State<'_, LibraryState>, app: &AppHandle, edit: F) -> Result<Playlist, String>
+where
+    F: FnOnce(&mut Library) -> Result<Playlist, PlaylistError>,
+{
+    let playlist = {
+        let mut library = library.library.lock()
+            .map_err(|e| format!("Failed to lock library: {}", e))?;
+        edit(&mut library).map_err(|e| e.to_string())?
+    };
+    emit_changed(app, playlist.id, Some(playlist.clone()));
+    Ok(playlist)
+}
+
+#[tauri::command]
+pub fn playlist_list(library: State<'_, LibraryState>) -> Result<Vec<Playlist>, String> {
+    let library = library.library.lock()
+        .map_err(|e| format!("Failed to lock library: {}", e))?;
+
+    library.playlists()
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn playlist_get_items(id: i64, library: State<'_, LibraryState>) -> Result<Vec<PlaylistItem>, String> {
+    let library = library.library.lock()
+        .map_err(|e| format!("Failed to lock library: {}", e))?;
+
+    library.playlist_items(id)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn playlist_create(
+    name: String,
+    track_ids: Option<Vec<String>>,
+    library: State<'_, LibraryState>,
+    app: AppHandle,
+) -> Result<Playlist, String> {
+    update(&library, &app, |library| library.create_playlist(&name, &track_ids.unwrap_or_default()))
+}
+
+#[tauri::command]
+pub fn playlist_rename(id: i64, name: String, library: State<'_, LibraryState>, app: AppHandle) -> Result<Playlist, String> {
+    update(&library, &app, |library| library.rename_playlist(id, &name))
+}
+
+#[tauri::command]
+pub fn playlist_delete(id: i64, library: State<'_, LibraryState>, app: AppHandle) -> Result<(), String> {
+    library.library.lock()
+        .map_err(|e| format!("Failed to lock library: {}", e))?
+        .delete_playlist(id)
+        .map_err(|e| e.to_string())?;
+
+    emit_changed(&app, id, None);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn playlist_duplicate(
+    id: i64,
+    name: Option<String>,
+    library: State<'_, LibraryState>,
+    app: AppHandle,
+) -> Result<Playlist, String> {
+    update(&library, &app, |library| library.duplicate_playlist(id, name.as_deref()))
+}
+
+/// Add library tracks before `position` (0-based), or at the end
+#[tauri::command]
+pub fn playlist_add_tracks(
+    id: i64,
+    track_ids: Vec<String>,
+    position: Option<usize>,
+    library: State<'_, LibraryState>,
+    app: AppHandle,
+) -> Result<Playlist, String> {
+    update(&library, &app, |library| library.add_to_playlist(id, &track_ids, position))
+}
+
+#[tauri::command]
+pub fn playlist_remove_tracks(
+    id: i64,
+    positions: Vec<usize>,
+    library: State<'_, LibraryState>,
+    app: AppHandle,
+) -> Result<Playlist, String> {
+    update(&library, &app, |library| library.remove_from_playlist(id, &positions))
+}
+
+/// Move the entries at `positions` to before the entry currently at `to`;
+/// `to` equal to the track count moves them to the end
+#[tauri::command]
+pub fn playlist_move_tracks(
+    id: i64,
+    positions: Vec<usize>,
+    to: usize,
+    library: State<'_, LibraryState>,
+    app: AppHandle,
+) -> Result<Playlist, String> {
+    update(&library, &app, |library| library.move_in_playlist(id, &positions, to))
+}
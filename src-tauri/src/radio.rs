@@ -0,0 +1,348 @@
+// Radio Module
+// Shoutcast/Icecast in-band (ICY) metadata parsing and saved radio stations
+
+use crate::errors::RadioError;
+use serde::{Deserialize, Serialize};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Metadata block announced by the server every `icy-metaint` bytes
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IcyMetadata {
+    pub stream_title: Option<String>,
+    pub stream_url: Option<String>,
+}
+
+/// Parse a metadata block such as `StreamTitle='Artist - Song';StreamUrl='';`
+pub fn parse_icy_metadata(block: &[u8]) -> IcyMetadata {
+    // Blocks are padded to a multiple of 16 bytes with NULs
+    let end = block.iter().rposition(|&b| b != 0).map(|i| i + 1).unwrap_or(0);
+    let text = decode_icy_text(&block[..end]);
+
+    let mut metadata = IcyMetadata {
+        stream_title: None,
+        stream_url: None,
+    };
+
+    let mut rest = text.as_str();
+    while let Some(eq) = rest.find("='") {
+        let key = rest[..eq].trim_start_matches(';').trim();
+        let value_start = eq + 2;
+        // Titles may contain quotes or semicolons, so only `';` terminates a value
+        let (value, next) = match rest[value_start..].find("';") {
+            Some(offset) => (&rest[value_start..value_start + offset], value_start + offset + 2),
+            None => {
+                let tail = &rest[value_start..];
+                (tail.strip_suffix('\'').unwrap_or(tail), rest.len())
+            }
+        };
+
+        let value = value.trim();
+        let value = if value.is_empty() { None } else { Some(value.to_string()) };
+        match key {
+            "StreamTitle" => metadata.stream_title = value,
+            "StreamUrl" => metadata.stream_url = value,
+            _ => {}
+        }
+        rest = &rest[next..];
+    }
+
+    metadata
+}
+
+/// Decode metadata text; servers that are not UTF-8 clean are almost
+/// always sending Windows-1251 for our (Russian-speaking) audience
+fn decode_icy_text(bytes: &[u8]) -> String {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => text.to_string(),
+        Err(_) => bytes.iter().map(|&b| decode_cp1251(b)).collect(),
+    }
+}
+
+/// Windows-1251 0x80..=0xBF: punctuation plus Serbian, Macedonian, Ukrainian
+/// and Belarusian letters (0x98 is unassigned)
+const CP1251_HIGH: [char; 64] = [
+    'Ђ', 'Ѓ', '‚', 'ѓ', '„', '…', '†', '‡', '€', '‰', 'Љ', '‹', 'Њ', 'Ќ', 'Ћ', 'Џ',
+    'ђ', '‘', '’', '“', '”', '•', '–', '—', '\u{98}', '™', 'љ', '›', 'њ', 'ќ', 'ћ', 'џ',
+    '\u{A0}', 'Ў', 'ў', 'Ј', '¤', 'Ґ', '¦', '§', 'Ё', '©', 'Є', '«', '¬', '\u{AD}', '®', 'Ї',
+    '°', '±', 'І', 'і', 'ґ', 'µ', '¶', '·', 'ё', '№', 'є', '»', 'ј', 'Ѕ', 'ѕ', 'ї',
+];
+
+pub(crate) fn decode_cp1251(byte: u8) -> char {
+    match byte {
+        0x00..=0x7F => byte as char,
+        0x80..=0xBF => CP1251_HIGH[(byte - 0x80) as usize],
+        0xC0..=0xFF => char::from_u32(0x0410 + (byte - 0xC0) as u32).unwrap_or('\u{FFFD}'),
+    }
+}
+
+/// Strips ICY metadata blocks from a stream body, leaving only audio bytes
+pub struct IcyReader<R, F> {
+    inner: R,
+    metaint: usize,
+    remaining: usize,
+    last_title: Option<String>,
+    on_metadata: F,
+}
+
+impl<R: Read, F: FnMut(IcyMetadata)> IcyReader<R, F> {
+    pub fn new(inner: R, metaint: usize, on_metadata: F) -> Self {
+        Self {
+            inner,
+            metaint,
+            remaining: metaint,
+            last_title: None,
+            on_metadata,
+        }
+    }
+
+    /// Consume one metadata block; returns false at end of stream
+    fn read_metadata(&mut self) -> io::Result<bool> {
+        let mut length = [0u8; 1];
+        if self.inner.read(&mut length)? == 0 {
+            return Ok(false);
+        }
+
+        let length = length[0] as usize * 16;
+        if length > 0 {
+            let mut block = vec![0u8; length];
+            self.inner.read_exact(&mut block)?;
+
+            let metadata = parse_icy_metadata(&block);
+            // Many servers repeat the same block; only report changes
+            if metadata.stream_title.is_some() && metadata.stream_title != self.last_title {
+                self.last_title = metadata.stream_title.clone();
+                (self.on_metadata)(metadata);
+            }
+        }
+
+        Ok(true)
+    }
+}
+
+impl<R: Read, F: FnMut(IcyMetadata)> Read for IcyReader<R, F> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        if self.remaining == 0 {
+            if !self.read_metadata()? {
+                return Ok(0);
+            }
+            self.remaining = self.metaint;
+        }
+
+        let max = buf.len().min(self.remaining);
+        let count = self.inner.read(&mut buf[..max])?;
+        self.remaining -= count;
+        Ok(count)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Station {
+    pub id: String,
+    pub name: String,
+    pub url: String,
+    pub genre: Option<String>,
+}
+
+/// Saved stations, persisted as JSON in the app data directory
+#[derive(Debug)]
+pub struct StationStore {
+    path: PathBuf,
+    stations: Vec<Station>,
+}
+
+impl StationStore {
+    /// Load stations from `path`, starting empty if the file does not exist yet
+    pub fn load(path: &Path) -> Result<Self, RadioError> {
+        let stations = match std::fs::read_to_string(path) {
+            Ok(contents) => serde_json::from_str(&contents)
+                .map_err(|e| RadioError::ParseError(format!("{}: {}", path.display(), e)))?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e.into()),
+        };
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            stations,
+        })
+    }
+
+    fn save(&self) -> Result<(), RadioError> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let contents = serde_json::to_string_pretty(&self.stations)
+            .map_err(|e| RadioError::ParseError(e.to_string()))?;
+        std::fs::write(&self.path, contents)?;
+        Ok(())
+    }
+
+    pub fn list(&self) -> &[Station] {
+        &self.stations
+    }
+
+    /// Add a station, or update the existing one with the same URL
+    pub fn save_station(&mut self, name: &str, url: &str, genre: Option<String>) -> Result<Station, RadioError> {
+        let url = url.trim();
+        if !crate::http_stream::is_remote_url(url) {
+            return Err(RadioError::InvalidUrl(url.to_string()));
+        }
+
+        let station = Station {
+            id: format!("{:x}", md5::compute(url.as_bytes())),
+            name: name.trim().to_string(),
+            url: url.to_string(),
+            genre: genre.map(|g| g.trim().to_string()).filter(|g| !g.is_empty()),
+        };
+
+        match self.stations.iter_mut().find(|s| s.id == station.id) {
+            Some(existing) => *existing = station.clone(),
+            None => self.stations.push(station.clone()),
+        }
+
+        self.save()?;
+        Ok(station)
+    }
+
+    pub fn remove_station(&mut self, id: &str) -> Result<(), RadioError> {
+        let before = self.stations.len();
+        self.stations.retain(|s| s.id != id);
+        if self.stations.len() == before {
+            return Err(RadioError::StationNotFound(id.to_string()));
+        }
+        self.save()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::temp_dir;
+
+    #[test]
+    fn test_parse_icy_metadata() {
+        let mut block = b"StreamTitle='Artist - Song';StreamUrl='http://example.com';".to_vec();
+        block.resize(64, 0);
+        let metadata = parse_icy_metadata(&block);
+        assert_eq!(metadata.stream_title.as_deref(), Some("Artist - Song"));
+        assert_eq!(metadata.stream_url.as_deref(), Some("http://example.com"));
+
+        let metadata = parse_icy_metadata(b"StreamTitle='Guns N' Roses - Don't Cry; Live';");
+        assert_eq!(metadata.stream_title.as_deref(), Some("Guns N' Roses - Don't Cry; Live"));
+        assert_eq!(metadata.stream_url, None);
+
+        let metadata = parse_icy_metadata(b"StreamTitle='';");
+        assert_eq!(metadata.stream_title, None);
+    }
+
+    #[test]
+    fn test_parse_cp1251_metadata() {
+        // "Кино - Группа крови" in Windows-1251
+        let mut block = b"StreamTitle='".to_vec();
+        block.extend_from_slice(&[0xCA, 0xE8, 0xED, 0xEE]);
+        block.extend_from_slice(b" - ");
+        block.extend_from_slice(&[0xC3, 0xF0, 0xF3, 0xEF, 0xEF, 0xE0, 0x20, 0xEA, 0xF0, 0xEE, 0xE2, 0xE8]);
+        block.extend_from_slice(b"';");
+        let metadata = parse_icy_metadata(&block);
+        assert_eq!(metadata.stream_title.as_deref(), Some("Кино - Группа крови"));
+
+        let punctuation: String = [0xAB, 0x96, 0x97, 0xBB, 0xB9, 0xAF, 0xBA].iter().map(|&b| decode_cp1251(b)).collect();
+        assert_eq!(punctuation, "«–—»№Їє");
+    }
+
+    #[test]
+    fn test_icy_reader_strips_metadata() {
+        let mut body = Vec::new();
+        body.extend_from_slice(b"abcd");
+        let meta = b"StreamTitle='One';";
+        body.push(2);
+        body.extend_from_slice(meta);
+        body.extend(std::iter::repeat(0).take(32 - meta.len()));
+        body.extend_from_slice(b"efgh");
+        body.push(0);
+        body.extend_from_slice(b"ij");
+
+        let mut titles = Vec::new();
+        let mut audio = Vec::new();
+        IcyReader::new(&body[..], 4, |m: IcyMetadata| titles.push(m.stream_title.unwrap()))
+            .read_to_end(&mut audio)
+            .unwrap();
+
+        assert_eq!(audio, b"abcdefghij");
+        assert_eq!(titles, vec!["One".to_string()]);
+    }
+
+    #[test]
+    fn test_station_store_roundtrip() {
+        let dir = temp_dir("stations");
+        let path = dir.join("stations.json");
+
+        let mut store = StationStore::load(&path).unwrap();
+        let station = store
+            .save_station("Radio Record", "http://radio.example/record", Some("Dance".to_string()))
+            .unwrap();
+        store.save_station("Radio Record FM", "http://radio.example/record", None).unwrap();
+        assert!(store.save_station("Bad", "ftp://nope", None).is_err());
+
+        let reloaded = StationStore::load(&path).unwrap();
+        assert_eq!(reloaded.list().len(), 1);
+        assert_eq!(reloaded.list()[0].name, "Radio Record FM");
+
+        let mut store = reloaded;
+        store.remove_station(&station.id).unwrap();
+        assert!(store.remove_station(&station.id).is_err());
+        assert!(StationStore::load(&path).unwrap().list().is_empty());
+    }
+}
+
+// Tauri Commands
+
+use tauri::State;
+
+pub struct RadioState {
+    pub stations: Mutex<StationStore>,
+}
+
+impl RadioState {
+    pub fn new(path: &Path) -> Result<Self, RadioError> {
+        Ok(Self {
+            stations: Mutex::new(StationStore::load(path)?),
+        })
+    }
+}
+
+#[tauri::command]
+pub fn radio_get_stations(radio: State<'_, RadioState>) -> Result<Vec<Station>, String> {
+    let store = radio.stations.lock()
+        .map_err(|e| format!("Failed to lock stations: {}", e))?;
+
+    Ok(store.list().to_vec())
+}
+
+#[tauri::command]
+pub fn radio_save_station(
+    name: String,
+    url: String,
+    genre: Option<String>,
+    radio: State<'_, RadioState>,
+) -> Result<Station, String> {
+    let mut store = radio.stations.lock()
+        .map_err(|e| format!("Failed to lock stations: {}", e))?;
+
+    store.save_station(&name, &url, genre)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn radio_remove_station(id: String, radio: State<'_, RadioState>) -> Result<(), String> {
+    let mut store = radio.stations.lock()
+        .map_err(|e| format!("Failed to lock stations: {}", e))?;
+
+    store.remove_station(&id)
+        .map_err(|e| e.to_string())
+}
@@ -0,0 +1,235 @@
+// Scanner Module
+// Extracts metadata on a bounded pool of worker threads, reporting progress in batches
+// and stopping early when a scan is cancelled
+
+use crate::file_manager::{FileManager, TrackMetadata};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Upper bound on worker threads; probing is mostly disk bound past this
+const MAX_WORKERS: usize = 8;
+/// A progress report goes out after this many tracks or this much time, whichever comes first
+const PROGRESS_BATCH_SIZE: usize = 32;
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+fn worker_count(files: usize) -> usize {
+    let cores = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(4);
+    cores.min(MAX_WORKERS).min(files).max(1)
+}
+
+/// Handle to a running scan, shared by the workers and whoever may cancel it
+#[derive(Debug, Clone, Default)]
+pub struct ScanHandle {
+    pub id: u64,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl ScanHandle {
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+/// Payload of `library:scan_progress`: the tracks extracted since the previous report
+#[derive(Debug, Clone, Serialize)]
+pub struct ScanProgress {
+    pub scan_id: u64,
+    pub done: usize,
+    pub total: usize,
+    /// Last file finished
+    pub current_file: Option<String>,
+    pub tracks: Vec<TrackMetadata>,
+    pub finished: bool,
+    pub cancelled: bool,
+}
+
+/// Extract metadata for every path on a pool of worker threads.
+///
+/// `on_progress` gets each batch before it is committed, so it may store the
+/// tracks and rewrite them (e.g. with the ID they were stored under). Results
+/// come back in input order; files skipped because of cancellation are `None`.
+pub fn extract_all<F>(
+    file_manager: &FileManager,
+    paths: &[PathBuf],
+    handle: &ScanHandle,
+    mut on_progress: F,
+) -> Vec<Option<TrackMetadata>>
+where
+    F: FnMut(&mut ScanProgress),
+{
+    let total = paths.len();
+    let mut results: Vec<Option<TrackMetadata>> = vec![None; total];
+    let next = AtomicUsize::new(0);
+
+    std::thread::scope(|scope| {
+        let (tx, rx) = mpsc::channel();
+        for _ in 0..worker_count(total) {
+            let tx = tx.clone();
+            let next = &next;
+            scope.spawn(move || {
+                while !handle.is_cancelled() {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(path) = paths.get(index) else { break };
+                    if tx.send((index, file_manager.metadata_or_fallback(path))).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        // The channel closes once the last worker is done
+        drop(tx);
+
+        let mut done = 0;
+        let mut batch: Vec<(usize, TrackMetadata)> = Vec::new();
+        let mut last_report = Instant::now();
+        let mut flush = |batch: &mut Vec<(usize, TrackMetadata)>, done: usize, finished: bool| {
+            let (indexes, tracks): (Vec<usize>, Vec<TrackMetadata>) = batch.drain(..).unzip();
+            let mut progress = ScanProgress {
+                scan_id: handle.id,
+                done,
+                total,
+                current_file: tracks.last().map(|t| t.file_path.clone()),
+                tracks,
+                finished,
+                cancelled: finished && handle.is_cancelled(),
+            };
+            on_progress(&mut progress);
+            for (index, track) in indexes.into_iter().zip(progress.tracks) {
+                results[index] = Some(track);
+            }
+        };
+
+        for (index, track) in rx {
+            done += 1;
+            batch.push((index, track));
+            if batch.len() >= PROGRESS_BATCH_SIZE || last_report.elapsed() >= PROGRESS_INTERVAL {
+                flush(&mut batch, done, false);
+                last_report = Instant::now();
+            }
+        }
+        flush(&mut batch, done, true);
+    });
+
+    results
+}
+
+/// Scans in flight, so the frontend can cancel them by ID
+#[derive(Default)]
+pub struct ScanState {
+    next_id: AtomicU64,
+    scans: Mutex<HashMap<u64, ScanHandle>>,
+}
+
+impl ScanState {
+    pub fn begin(&self) -> ScanHandle {
+        let handle = ScanHandle {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed) + 1,
+            cancelled: Arc::new(AtomicBool::new(false)),
+        };
+        if let Ok(mut scans) = self.scans.lock() {
+            scans.insert(handle.id, handle.clone());
+        }
+        handle
+    }
+
+    pub fn end(&self, handle: &ScanHandle) {
+        if let Ok(mut scans) = self.scans.lock() {
+            scans.remove(&handle.id);
+        }
+    }
+
+    /// Returns false if no such scan is running
+    pub fn cancel(&self, scan_id: u64) -> bool {
+        match self.scans.lock().ok().and_then(|scans| scans.get(&scan_id).cloned()) {
+            Some(handle) => {
+                handle.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{sine_wav, temp_dir};
+
+    fn wav_files(name: &str, count: usize) -> Vec<PathBuf> {
+        let dir = temp_dir(name);
+        (0..count)
+            .map(|i| {
+                let path = dir.join(format!("{:03}.wav", i));
+                std::fs::write(&path, sine_wav(0.05, 8000, 200.0 + i as f32)).unwrap();
+                path
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_extract_all_in_order_with_progress() {
+        let paths = wav_files("scanner-all", 70);
+        let state = ScanState::default();
+        let handle = state.begin();
+
+        let mut reports = Vec::new();
+        let results = extract_all(&FileManager::new(), &paths, &handle, |progress| {
+            for track in &mut progress.tracks {
+                track.title.push('!');
+            }
+            reports.push((progress.done, progress.tracks.len(), progress.finished));
+        });
+        state.end(&handle);
+
+        assert_eq!(results.len(), 70);
+        for (path, track) in paths.iter().zip(&results) {
+            let track = track.as_ref().unwrap();
+            assert_eq!(track.file_path, path.to_string_lossy());
+            assert!(track.title.ends_with('!'));
+        }
+        assert_eq!(reports.iter().map(|(_, n, _)| n).sum::<usize>(), 70);
+        assert!(reports.iter().all(|(_, n, _)| *n <= PROGRESS_BATCH_SIZE));
+        assert_eq!(reports.last().unwrap(), &(70, reports.last().unwrap().1, true));
+        assert!(!state.cancel(handle.id));
+    }
+
+    #[test]
+    fn test_cancelled_scan_stops() {
+        let paths = wav_files("scanner-cancel", 10);
+        let state = ScanState::default();
+        let handle = state.begin();
+        assert!(state.cancel(handle.id));
+
+        let mut last = None;
+        let results = extract_all(&FileManager::new(), &paths, &handle, |progress| last = Some(progress.clone()));
+        assert!(results.iter().all(Option::is_none));
+        let last = last.unwrap();
+        assert!(last.finished && last.cancelled);
+        assert_eq!((last.done, last.total), (0, 10));
+    }
+}
+
+// Tauri Commands
+
+use tauri::{AppHandle, Emitter, State};
+
+/// Send one progress report to the frontend
+pub fn emit_progress(app: &AppHandle, progress: &ScanProgress) {
+    if let Err(e) = app.emit("library:scan_progress", progress) {
+        log::warn!("Failed to emit scan progress: {}", e);
+    }
+}
+
+#[tauri::command]
+pub fn library_cancel_scan(scan_id: u64, scans: State<'_, ScanState>) -> Result<bool, String> {
+    Ok(scans.cancel(scan_id))
+}
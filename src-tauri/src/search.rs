@@ -0,0 +1,488 @@
+// Search Module
+// Library search over a token index kept next to the tracks: prefix and typo-tolerant
+// matching that ignores case, diacritics and whether Russian was typed in Cyrillic or Latin
+
+use crate::errors::LibraryError;
+use crate::file_manager::TrackMetadata;
+use crate::library::Library;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// Folders above the file that are searched along with its name (album, artist)
+const PATH_COMPONENTS: usize = 3;
+const DEFAULT_LIMIT: u32 = 50;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SearchField {
+    Title,
+    Artist,
+    Album,
+    Path,
+}
+
+impl SearchField {
+    const ALL: [SearchField; 4] = [SearchField::Title, SearchField::Artist, SearchField::Album, SearchField::Path];
+
+    fn weight(self) -> u32 {
+        match self {
+            SearchField::Title => 4,
+            SearchField::Artist => 3,
+            SearchField::Album => 2,
+            SearchField::Path => 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SearchOptions {
+    /// Fields to match in; all of them when empty
+    pub fields: Vec<SearchField>,
+    /// Allow a typo or two in longer words
+    pub fuzzy: bool,
+    pub limit: Option<u32>,
+    pub offset: Option<u32>,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        Self {
+            fields: Vec::new(),
+            fuzzy: true,
+            limit: None,
+            offset: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchHit {
+    pub track: TrackMetadata,
+    pub score: u32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchResults {
+    /// Matches across all pages
+    pub total: usize,
+    pub hits: Vec<SearchHit>,
+}
+
+/// Lowercase `c` and drop its diacritics. Cyrillic ё and й become е and и,
+/// the way they are usually typed.
+fn fold_char(c: char, out: &mut String) {
+    for c in c.to_lowercase() {
+        let folded = match c {
+            '\u{300}'..='\u{36f}' => "",
+            'à'..='å' | 'ā' | 'ă' | 'ą' => "a",
+            'æ' => "ae",
+            'ç' | 'ć' | 'ĉ' | 'ċ' | 'č' => "c",
+            'ď' | 'đ' | 'ð' => "d",
+            'è'..='ë' | 'ē' | 'ĕ' | 'ė' | 'ę' | 'ě' => "e",
+            'ĝ' | 'ğ' | 'ġ' | 'ģ' => "g",
+            'ĥ' | 'ħ' => "h",
+            'ì'..='ï' | 'ĩ' | 'ī' | 'ĭ' | 'į' | 'ı' => "i",
+            'ĵ' => "j",
+            'ķ' => "k",
+            'ĺ' | 'ļ' | 'ľ' | 'ŀ' | 'ł' => "l",
+            'ñ' | 'ń' | 'ņ' | 'ň' => "n",
+            'ò'..='ö' | 'ø' | 'ō' | 'ŏ' | 'ő' => "o",
+            'œ' => "oe",
+            'ŕ' | 'ŗ' | 'ř' => "r",
+            'ś' | 'ŝ' | 'ş' | 'š' => "s",
+            'ß' => "ss",
+            'ţ' | 'ť' | 'ŧ' => "t",
+            'þ' => "th",
+            'ù'..='ü' | 'ũ' | 'ū' | 'ŭ' | 'ů' | 'ű' | 'ų' => "u",
+            'ŵ' => "w",
+            'ý' | 'ÿ' | 'ŷ' => "y",
+            'ź' | 'ż' | 'ž' => "z",
+            'ё' => "е",
+            'й' => "и",
+            c => {
+                out.push(c);
+                continue;
+            }
+        };
+        out.push_str(folded);
+    }
+}
+
+/// Folded words of `text`
+pub fn tokenize(text: &str) -> Vec<String> {
+    let mut folded = String::with_capacity(text.len());
+    for c in text.chars() {
+        fold_char(c, &mut folded);
+    }
+    folded
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|token| !token.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Latin spelling of a folded Cyrillic word, so "kino" finds "Кино"
+fn transliterate(token: &str) -> Option<String> {
+    if !token.chars().any(|c| ('\u{400}'..='\u{4ff}').contains(&c)) {
+        return None;
+    }
+    let mut latin = String::with_capacity(token.len());
+    for c in token.chars() {
+        latin.push_str(match c {
+            'а' => "a",
+            'б' => "b",
+            'в' => "v",
+            'г' | 'ґ' => "g",
+            'д' => "d",
+            'е' | 'є' | 'э' => "e",
+            'ж' => "zh",
+            'з' => "z",
+            'и' | 'і' | 'ї' => "i",
+            'к' => "k",
+            'л' => "l",
+            'м' => "m",
+            'н' => "n",
+            'о' => "o",
+            'п' => "p",
+            'р' => "r",
+            'с' => "s",
+            'т' => "t",
+            'у' | 'ў' => "u",
+            'ф' => "f",
+            'х' => "kh",
+            'ц' => "ts",
+            'ч' => "ch",
+            'ш' => "sh",
+            'щ' => "shch",
+            'ъ' | 'ь' => "",
+            'ы' => "y",
+            'ю' => "yu",
+            'я' => "ya",
+            c => {
+                latin.push(c);
+                continue;
+            }
+        });
+    }
+    Some(latin)
+}
+
+/// Index entry for one field: its tokens plus Latin spellings of Cyrillic ones
+fn index_text(text: &str) -> String {
+    let mut tokens = tokenize(text);
+    let latin: Vec<String> = tokens.iter().filter_map(|token| transliterate(token)).collect();
+    tokens.extend(latin);
+    tokens.dedup();
+    tokens.join(" ")
+}
+
+fn path_text(file_path: &str) -> String {
+    let path = crate::cue::audio_path(file_path);
+    let mut parts: Vec<String> = path
+        .parent()
+        .map(|parent| parent.iter().rev().take(PATH_COMPONENTS - 1).map(|p| p.to_string_lossy().to_string()).collect())
+        .unwrap_or_default();
+    parts.extend(path.file_stem().map(|stem| stem.to_string_lossy().to_string()));
+    parts.join(" ")
+}
+
+/// Write or refresh the index entry of a stored track
+pub(crate) fn index_track(conn: &Connection, id: &str, track: &TrackMetadata) -> rusqlite::Result<()> {
+    let texts = [
+        index_text(&track.title),
+        index_text(&format!("{} {}", track.artist, track.album_artist.as_deref().unwrap_or(""))),
+        index_text(track.album.as_deref().unwrap_or("")),
+        index_text(&path_text(&track.file_path)),
+    ];
+    conn.execute(
+        "INSERT OR REPLACE INTO search_index (track_id, title, artist, album, path) VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![id, texts[0], texts[1], texts[2], texts[3]],
+    )?;
+
+    conn.execute("DELETE FROM search_tokens WHERE track_id = ?1", [id])?;
+    let mut insert = conn.prepare_cached("INSERT OR IGNORE INTO search_tokens (token, track_id) VALUES (?1, ?2)")?;
+    for token in texts.iter().flat_map(|text| text.split(' ')).filter(|token| !token.is_empty()) {
+        insert.execute(params![token, id])?;
+    }
+    Ok(())
+}
+
+/// Index tracks stored before the index existed
+pub(crate) fn index_missing(conn: &Connection) -> rusqlite::Result<()> {
+    let sql = format!(
+        "SELECT {} WHERE t.id NOT IN (SELECT track_id FROM search_index)",
+        crate::library::TRACK_COLUMNS
+    );
+    let mut stmt = conn.prepare(&sql)?;
+    let tracks = stmt
+        .query_map([], crate::library::track_from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+    for track in &tracks {
+        index_track(conn, &track.id, track)?;
+    }
+    Ok(())
+}
+
+/// Edit distance allowing adjacent swaps (optimal string alignment)
+fn edit_distance(a: &[char], b: &[char]) -> usize {
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut before_previous = previous.clone();
+    for i in 1..=a.len() {
+        let mut current = vec![i; b.len() + 1];
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            current[j] = (previous[j] + 1).min(current[j - 1] + 1).min(previous[j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                current[j] = current[j].min(before_previous[j - 2] + 1);
+            }
+        }
+        before_previous = std::mem::replace(&mut previous, current);
+    }
+    previous[b.len()]
+}
+
+/// 3 for the whole word, 2 for its beginning, 1 for a near miss of either
+fn match_score(query: &str, token: &str, fuzzy: bool) -> u32 {
+    if token == query {
+        return 3;
+    }
+    if token.starts_with(query) {
+        return 2;
+    }
+    let query: Vec<char> = query.chars().collect();
+    if !fuzzy || query.len() < 4 {
+        return 0;
+    }
+    let allowed = if query.len() >= 8 { 2 } else { 1 };
+    let token: Vec<char> = token.chars().collect();
+    // Compare against the start of the token too, so a typo still prefix-matches
+    let near = (query.len() - 1..=query.len() + 1)
+        .filter(|&len| len <= token.len())
+        .any(|len| edit_distance(&query, &token[..len]) <= allowed);
+    u32::from(near)
+}
+
+/// Best weighted score of `query` in any of `fields`
+fn token_score(query: &[String], fields: &[(SearchField, Vec<&str>)], fuzzy: bool) -> u32 {
+    fields.iter()
+        .flat_map(|(field, tokens)| {
+            tokens.iter().flat_map(move |token| {
+                query.iter().map(move |q| match_score(q, token, fuzzy) * field.weight())
+            })
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+/// Tracks with a token that any spelling of `word` may match in any field.
+/// Whole words and prefixes are range lookups on the token index; near misses
+/// are found among the distinct tokens, which are far fewer than the tracks.
+fn candidates(conn: &Connection, word: &[String], fuzzy: bool) -> rusqlite::Result<HashSet<String>> {
+    let mut ids = HashSet::new();
+    let mut by_prefix = conn.prepare_cached("SELECT track_id FROM search_tokens WHERE token >= ?1 AND token < ?2")?;
+    for spelling in word {
+        // Every token starting with `spelling` sorts below it followed by the last code point
+        let end = format!("{}{}", spelling, char::MAX);
+        for id in by_prefix.query_map(params![spelling, end], |row| row.get(0))? {
+            ids.insert(id?);
+        }
+    }
+
+    if fuzzy && word.iter().any(|spelling| spelling.chars().count() >= 4) {
+        let mut vocabulary = conn.prepare_cached("SELECT DISTINCT token FROM search_tokens")?;
+        let near: Vec<String> = vocabulary
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter()
+            .filter(|token| word.iter().any(|w| match_score(w, token, true) == 1))
+            .collect();
+        let mut by_token = conn.prepare_cached("SELECT track_id FROM search_tokens WHERE token = ?1")?;
+        for token in near {
+            for id in by_token.query_map([token], |row| row.get(0))? {
+                ids.insert(id?);
+            }
+        }
+    }
+    Ok(ids)
+}
+
+impl Library {
+    /// Tracks matching every word of `query`, best first
+    pub fn search(&self, query: &str, options: &SearchOptions) -> Result<SearchResults, LibraryError> {
+        // Each word is looked for as typed and, when Cyrillic, in Latin spelling
+        let words: Vec<Vec<String>> = tokenize(query)
+            .into_iter()
+            .map(|word| std::iter::once(word.clone()).chain(transliterate(&word)).collect())
+            .collect();
+        if words.is_empty() {
+            return Ok(SearchResults { total: 0, hits: Vec::new() });
+        }
+        let fields = if options.fields.is_empty() { SearchField::ALL.to_vec() } else { options.fields.clone() };
+
+        // Only tracks with a possible match for every word are scored
+        let mut ids: Option<HashSet<String>> = None;
+        for word in &words {
+            let found = candidates(&self.conn, word, options.fuzzy)?;
+            match ids.as_mut() {
+                Some(ids) => ids.retain(|id| found.contains(id)),
+                None => ids = Some(found),
+            }
+        }
+
+        let mut stmt = self.conn.prepare_cached("SELECT title, artist, album, path FROM search_index WHERE track_id = ?1")?;
+        let mut matches: Vec<(u32, String, String)> = Vec::new();
+        for id in ids.unwrap_or_default() {
+            let Some(texts) = stmt
+                .query_row([&id], |row| Ok::<[String; 4], _>([row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?]))
+                .optional()?
+            else {
+                continue;
+            };
+            let indexed: Vec<(SearchField, Vec<&str>)> = SearchField::ALL.iter()
+                .zip(&texts)
+                .filter(|(field, _)| fields.contains(field))
+                .map(|(field, text)| (*field, text.split(' ').collect()))
+                .collect();
+
+            let mut score = 0;
+            for word in &words {
+                match token_score(word, &indexed, options.fuzzy) {
+                    0 => {
+                        score = 0;
+                        break;
+                    }
+                    word_score => score += word_score,
+                }
+            }
+            if score > 0 {
+                let [title, ..] = texts;
+                matches.push((score, title, id));
+            }
+        }
+
+        matches.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)).then_with(|| a.2.cmp(&b.2)));
+        let total = matches.len();
+        let offset = options.offset.unwrap_or(0) as usize;
+        let limit = options.limit.unwrap_or(DEFAULT_LIMIT) as usize;
+        let mut hits = Vec::new();
+        for (score, _, id) in matches.into_iter().skip(offset).take(limit) {
+            if let Some(track) = self.track(&id)? {
+                hits.push(SearchHit { track, score });
+            }
+        }
+        Ok(SearchResults { total, hits })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track(path: &str, title: &str, artist: &str, album: &str) -> TrackMetadata {
+        TrackMetadata {
+            id: format!("{:x}", md5::compute(path.as_bytes())),
+            title: title.to_string(),
+            artist: artist.to_string(),
+            album: Some(album.to_string()),
+            duration: 200.0,
+            file_path: path.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn titles(results: &SearchResults) -> Vec<&str> {
+        results.hits.iter().map(|hit| hit.track.title.as_str()).collect()
+    }
+
+    #[test]
+    fn test_tokenize_folds_case_and_diacritics() {
+        assert_eq!(tokenize("Beyoncé — Crazy in Love (Remix)"), vec!["beyonce", "crazy", "in", "love", "remix"]);
+        assert_eq!(tokenize("Ёлка, Чайф"), vec!["елка", "чаиф"]);
+        assert_eq!(tokenize("Straße Łódź"), vec!["strasse", "lodz"]);
+        assert_eq!(transliterate("цои").as_deref(), Some("tsoi"));
+        assert_eq!(transliterate("kino"), None);
+        assert_eq!(index_text("Звезда по имени Солнце"), "звезда по имени солнце zvezda po imeni solntse");
+        assert_eq!(edit_distance(&['a', 'b', 'c'], &['a', 'c', 'b']), 1);
+    }
+
+    #[test]
+    fn test_search_ranks_prefix_and_fuzzy_matches() {
+        let library = Library::open_in_memory().unwrap();
+        for t in [
+            track("/m/Кино/Группа крови/01.flac", "Группа крови", "Кино", "Группа крови"),
+            track("/m/Кино/Звезда/01.flac", "Звезда по имени Солнце", "Кино", "Звезда по имени Солнце"),
+            track("/m/Beyonce/01.mp3", "Halo", "Beyoncé", "I Am... Sasha Fierce"),
+            track("/m/Misc/kinoteatr.mp3", "Kinoteatr", "Someone", "Misc"),
+        ] {
+            library.upsert_track(&t).unwrap();
+        }
+        let options = SearchOptions::default();
+
+        // Whole words beat prefixes; Latin spelling finds Cyrillic tags
+        let results = library.search("kino", &options).unwrap();
+        assert_eq!(results.total, 3);
+        assert_eq!(titles(&results)[2], "Kinoteatr");
+        assert_eq!(titles(&library.search("ЗВЁЗДА солнц", &options).unwrap()), vec!["Звезда по имени Солнце"]);
+        assert_eq!(titles(&library.search("beyonce halo", &options).unwrap()), vec!["Halo"]);
+
+        // One typo is forgiven unless fuzzy matching is off
+        assert_eq!(titles(&library.search("solnse", &options).unwrap()), vec!["Звезда по имени Солнце"]);
+        let exact = SearchOptions { fuzzy: false, ..Default::default() };
+        assert_eq!(library.search("solnse", &exact).unwrap().total, 0);
+
+        let artists_only = SearchOptions { fields: vec![SearchField::Artist], ..Default::default() };
+        assert_eq!(library.search("крови", &artists_only).unwrap().total, 0);
+
+        let page = SearchOptions { limit: Some(1), offset: Some(1), ..Default::default() };
+        let results = library.search("кино", &page).unwrap();
+        assert_eq!((results.total, results.hits.len()), (3, 1));
+    }
+
+    #[test]
+    fn test_index_follows_library_changes() {
+        let library = Library::open_in_memory().unwrap();
+        let mut song = track("/m/a.mp3", "Old Name", "Artist", "Album");
+        library.upsert_track(&song).unwrap();
+        song.title = "New Name".to_string();
+        library.upsert_track(&song).unwrap();
+        let options = SearchOptions::default();
+        assert_eq!(library.search("old", &options).unwrap().total, 0);
+        assert_eq!(library.search("new", &options).unwrap().total, 1);
+
+        library.conn.execute("DELETE FROM tracks WHERE id = ?1", [&song.id]).unwrap();
+        assert_eq!(library.search("new", &options).unwrap().total, 0);
+        let tokens: i64 = library.conn.query_row("SELECT COUNT(*) FROM search_tokens", [], |row| row.get(0)).unwrap();
+        assert_eq!(tokens, 0);
+
+        // Rows from before the index are picked up when the library opens
+        library.upsert_track(&song).unwrap();
+        library.conn.execute("DELETE FROM search_index", []).unwrap();
+        index_missing(&library.conn).unwrap();
+        assert_eq!(library.search("name", &options).unwrap().total, 1);
+    }
+}
+
+// Tauri Commands
+
+use crate::library::LibraryState;
+use tauri::State;
+
+#[tauri::command]
+pub async fn search_library(
+    query: String,
+    options: Option<SearchOptions>,
+    library: State<'_, LibraryState>,
+) -> Result<SearchResults, String> {
+    let library = library.library.clone();
+
+    tauri::async_runtime::spawn_blocking(move || {
+        library.lock()
+            .map_err(|e| format!("Failed to lock library: {}", e))?
+            .search(&query, &options.unwrap_or_default())
+            .map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| format!("Search failed: {}", e))?
+}
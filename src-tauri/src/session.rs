@@ -0,0 +1,406 @@
+// Session Module
+// Persists the queue and playback position so the app reopens where it was left,
+// plus per-file resume positions for long files such as audiobooks
+
+use crate::audio_engine::{AudioEngine, AudioEngineState};
+use crate::errors::{AudioEngineError, SessionError};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(5);
+/// Position changes smaller than this are not worth rewriting the file for
+const POSITION_TOLERANCE: f64 = 1.0;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RepeatMode {
+    #[default]
+    None,
+    All,
+    One,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Session {
+    pub queue: Vec<String>,
+    pub current_index: Option<usize>,
+    pub position: f64,
+    pub volume: f32,
+    pub repeat_mode: RepeatMode,
+    pub shuffle: bool,
+    /// Files opted into auto-resume, with their last position
+    pub resume_positions: BTreeMap<String, f64>,
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Self {
+            queue: Vec::new(),
+            current_index: None,
+            position: 0.0,
+            volume: 1.0,
+            repeat_mode: RepeatMode::default(),
+            shuffle: false,
+            resume_positions: BTreeMap::new(),
+        }
+    }
+}
+
+impl Session {
+    pub fn current_track(&self) -> Option<&str> {
+        self.current_index
+            .and_then(|index| self.queue.get(index))
+            .map(String::as_str)
+    }
+}
+
+/// Session file in the app data directory, written only when something changed
+#[derive(Debug)]
+pub struct SessionStore {
+    path: PathBuf,
+    session: Session,
+    dirty: bool,
+}
+
+impl SessionStore {
+    /// Load the session from `path`, starting empty if the file does not exist yet
+    pub fn load(path: &Path) -> Result<Self, SessionError> {
+        let session = match std::fs::read_to_string(path) {
+            Ok(contents) => serde_json::from_str(&contents)
+                .map_err(|e| SessionError::ParseError(format!("{}: {}", path.display(), e)))?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Session::default(),
+            Err(e) => return Err(e.into()),
+        };
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            session,
+            dirty: false,
+        })
+    }
+
+    /// Like `load`, but a damaged session file is logged and replaced rather
+    /// than preventing the app from starting
+    pub fn load_or_default(path: &Path) -> Self {
+        Self::load(path).unwrap_or_else(|e| {
+            log::warn!("Discarding saved session: {}", e);
+            Self {
+                path: path.to_path_buf(),
+                session: Session::default(),
+                dirty: false,
+            }
+        })
+    }
+
+    pub fn session(&self) -> &Session {
+        &self.session
+    }
+
+    /// Write the session if it changed since the last save
+    pub fn save(&mut self) -> Result<(), SessionError> {
+        if !self.dirty {
+            return Ok(());
+        }
+
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let contents = serde_json::to_string_pretty(&self.session)
+            .map_err(|e| SessionError::ParseError(e.to_string()))?;
+
+        // Write then rename so a kill mid-save never leaves a truncated file
+        let temp_path = self.path.with_extension("json.tmp");
+        std::fs::write(&temp_path, contents)?;
+        std::fs::rename(&temp_path, &self.path)?;
+
+        self.dirty = false;
+        Ok(())
+    }
+
+    /// Replace the queue and modes reported by the frontend
+    pub fn set_queue(&mut self, queue: Vec<String>, current_index: Option<usize>, repeat_mode: RepeatMode, shuffle: bool) {
+        let current_index = current_index.filter(|&index| index < queue.len());
+        if current_index != self.session.current_index {
+            self.session.position = 0.0;
+        }
+
+        self.session.queue = queue;
+        self.session.current_index = current_index;
+        self.session.repeat_mode = repeat_mode;
+        self.session.shuffle = shuffle;
+        self.dirty = true;
+    }
+
+    /// Record what the engine is doing right now
+    pub fn record_playback(&mut self, track: Option<&str>, position: f64, volume: f32, finished: bool) {
+        if (volume - self.session.volume).abs() > f32::EPSILON {
+            self.session.volume = volume;
+            self.dirty = true;
+        }
+
+        let Some(track) = track else { return };
+
+        // The frontend may have moved on before reporting its queue
+        if self.session.current_track() != Some(track) {
+            if let Some(index) = self.session.queue.iter().position(|t| t == track) {
+                self.session.current_index = Some(index);
+                self.dirty = true;
+            }
+        }
+
+        // A finished file starts over next time
+        let position = if finished { 0.0 } else { position.max(0.0) };
+        if (position - self.session.position).abs() >= POSITION_TOLERANCE
+            || (position == 0.0 && self.session.position != 0.0)
+        {
+            self.session.position = position;
+            self.dirty = true;
+        }
+
+        if let Some(saved) = self.session.resume_positions.get_mut(track) {
+            if (position - *saved).abs() >= POSITION_TOLERANCE || (position == 0.0 && *saved != 0.0) {
+                *saved = position;
+                self.dirty = true;
+            }
+        }
+    }
+
+    /// Opt a file in or out of auto-resume
+    pub fn set_auto_resume(&mut self, file_path: &str, enabled: bool, position: f64) {
+        if enabled {
+            self.session.resume_positions.insert(file_path.to_string(), position.max(0.0));
+        } else {
+            self.session.resume_positions.remove(file_path);
+        }
+        self.dirty = true;
+    }
+
+    /// Saved position for an auto-resume file, if there is one worth seeking to
+    pub fn resume_position(&self, file_path: &str) -> Option<f64> {
+        self.session.resume_positions
+            .get(file_path)
+            .copied()
+            .filter(|&position| position > 0.0)
+    }
+}
+
+/// Load the last track paused at its saved position; remote streams are left
+/// in the queue but not reopened, so startup never waits on the network
+pub fn restore(engine: &AudioEngine, session: &Session) -> Result<bool, AudioEngineError> {
+    engine.set_volume(session.volume)?;
+
+    let Some(track) = session.current_track() else {
+        return Ok(false);
+    };
+    if crate::http_stream::is_remote_url(track) || !Path::new(track).exists() {
+        return Ok(false);
+    }
+
+    engine.load_track(Path::new(track))?;
+    if session.position > 0.0 {
+        engine.seek(session.position)?;
+    }
+
+    Ok(true)
+}
+
+/// Periodically copy playback progress into the session file
+fn run_autosave(store: Arc<Mutex<SessionStore>>, engine: Arc<Mutex<AudioEngine>>) {
+    loop {
+        std::thread::sleep(AUTOSAVE_INTERVAL);
+
+        let Ok(engine_guard) = engine.lock() else { return };
+        let snapshot = engine_guard.get_state()
+            .and_then(|state| Ok((state, engine_guard.is_finished()?)));
+        drop(engine_guard);
+
+        let (state, finished) = match snapshot {
+            Ok(snapshot) => snapshot,
+            Err(e) => {
+                log::warn!("Failed to read playback state for session: {}", e);
+                continue;
+            }
+        };
+
+        let Ok(mut store) = store.lock() else { return };
+        store.record_playback(state.current_track.as_deref(), state.current_time, state.volume, finished);
+        if let Err(e) = store.save() {
+            log::warn!("Failed to save session: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::temp_dir;
+
+    fn queue() -> Vec<String> {
+        vec!["/music/a.mp3".to_string(), "/books/b.m4b".to_string()]
+    }
+
+    #[test]
+    fn test_session_roundtrip() {
+        let dir = temp_dir("session");
+        let path = dir.join("session.json");
+
+        let mut store = SessionStore::load(&path).unwrap();
+        assert_eq!(store.session(), &Session::default());
+
+        store.set_queue(queue(), Some(1), RepeatMode::All, true);
+        store.record_playback(Some("/books/b.m4b"), 1234.5, 0.4, false);
+        store.save().unwrap();
+        assert!(!path.with_extension("json.tmp").exists());
+
+        let reloaded = SessionStore::load(&path).unwrap();
+        let session = reloaded.session();
+        assert_eq!(session.current_track(), Some("/books/b.m4b"));
+        assert_eq!(session.position, 1234.5);
+        assert_eq!(session.volume, 0.4);
+        assert_eq!(session.repeat_mode, RepeatMode::All);
+        assert!(session.shuffle);
+
+        std::fs::write(&path, "{ not json").unwrap();
+        assert!(SessionStore::load(&path).is_err());
+        assert_eq!(SessionStore::load_or_default(&path).session(), &Session::default());
+    }
+
+    #[test]
+    fn test_record_playback_follows_queue() {
+        let mut store = SessionStore::load_or_default(Path::new("/nonexistent/session.json"));
+        store.set_queue(queue(), Some(0), RepeatMode::None, false);
+        store.record_playback(Some("/music/a.mp3"), 42.0, 1.0, false);
+        assert_eq!(store.session().position, 42.0);
+
+        // The engine moved to the next track before the frontend reported it
+        store.record_playback(Some("/books/b.m4b"), 3.0, 1.0, false);
+        assert_eq!(store.session().current_index, Some(1));
+        assert_eq!(store.session().position, 3.0);
+
+        store.record_playback(Some("/books/b.m4b"), 3.0, 1.0, true);
+        assert_eq!(store.session().position, 0.0);
+    }
+
+    #[test]
+    fn test_auto_resume_positions() {
+        let mut store = SessionStore::load_or_default(Path::new("/nonexistent/session.json"));
+        store.set_auto_resume("/books/b.m4b", true, 0.0);
+        assert_eq!(store.resume_position("/books/b.m4b"), None);
+
+        store.record_playback(Some("/books/b.m4b"), 600.0, 1.0, false);
+        store.record_playback(Some("/music/a.mp3"), 30.0, 1.0, false);
+        assert_eq!(store.resume_position("/books/b.m4b"), Some(600.0));
+        assert_eq!(store.resume_position("/music/a.mp3"), None);
+
+        store.record_playback(Some("/books/b.m4b"), 7200.0, 1.0, true);
+        assert_eq!(store.resume_position("/books/b.m4b"), None);
+
+        store.set_auto_resume("/books/b.m4b", false, 0.0);
+        assert!(store.session().resume_positions.is_empty());
+    }
+}
+
+// Tauri Commands
+
+use tauri::{AppHandle, Emitter, Manager, State};
+
+pub struct SessionState {
+    pub store: Arc<Mutex<SessionStore>>,
+}
+
+impl SessionState {
+    pub fn new(path: &Path) -> Self {
+        Self {
+            store: Arc::new(Mutex::new(SessionStore::load_or_default(path))),
+        }
+    }
+
+    /// Saved position to seek to when `file_path` is loaded, for auto-resume files
+    pub fn resume_position(&self, file_path: &str) -> Option<f64> {
+        self.store.lock().ok()?.resume_position(file_path)
+    }
+}
+
+/// Restore the previous session into the engine and start the autosave thread
+pub fn start(app: &AppHandle) {
+    let session = app.state::<SessionState>();
+    let engine = app.state::<AudioEngineState>();
+
+    let saved = match session.store.lock() {
+        Ok(store) => store.session().clone(),
+        Err(e) => {
+            log::warn!("Failed to lock session: {}", e);
+            return;
+        }
+    };
+
+    match engine.engine.lock() {
+        Ok(engine_guard) => match restore(&engine_guard, &saved) {
+            Ok(true) => {
+                if let Ok(state) = engine_guard.get_state() {
+                    let _ = app.emit("audio:state_changed", &state);
+                }
+            }
+            Ok(false) => {}
+            Err(e) => log::warn!("Failed to restore session: {}", e),
+        },
+        Err(e) => log::warn!("Failed to lock engine: {}", e),
+    }
+
+    let store = session.store.clone();
+    let engine = engine.engine.clone();
+    std::thread::spawn(move || run_autosave(store, engine));
+}
+
+#[tauri::command]
+pub fn session_get(session: State<'_, SessionState>) -> Result<Session, String> {
+    let store = session.store.lock()
+        .map_err(|e| format!("Failed to lock session: {}", e))?;
+
+    Ok(store.session().clone())
+}
+
+#[tauri::command]
+pub fn session_set_queue(
+    queue: Vec<String>,
+    current_index: Option<usize>,
+    repeat_mode: RepeatMode,
+    shuffle: bool,
+    session: State<'_, SessionState>,
+) -> Result<(), String> {
+    let mut store = session.store.lock()
+        .map_err(|e| format!("Failed to lock session: {}", e))?;
+
+    store.set_queue(queue, current_index, repeat_mode, shuffle);
+    store.save()
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn session_set_auto_resume(
+    file_path: String,
+    enabled: bool,
+    session: State<'_, SessionState>,
+    engine: State<'_, AudioEngineState>,
+) -> Result<(), String> {
+    // Start from the current position if the file is already playing
+    let position = {
+        let engine_guard = engine.engine.lock()
+            .map_err(|e| format!("Failed to lock engine: {}", e))?;
+        let state = engine_guard.get_state()
+            .map_err(|e| e.to_string())?;
+        if state.current_track.as_deref() == Some(file_path.as_str()) { state.current_time } else { 0.0 }
+    };
+
+    let mut store = session.store.lock()
+        .map_err(|e| format!("Failed to lock session: {}", e))?;
+
+    store.set_auto_resume(&file_path, enabled, position);
+    store.save()
+        .map_err(|e| e.to_string())
+}
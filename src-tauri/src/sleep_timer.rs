@@ -0,0 +1,502 @@
+// Sleep Timer Module
+// Stops playback after a time or a number of tracks, fading out first.
+// Runs on a backend thread so it keeps working while the webview is suspended.
+
+use crate::audio_engine::{AudioEngine, AudioEngineState};
+use crate::errors::AudioEngineError;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+const TICK_INTERVAL: Duration = Duration::from_secs(1);
+const DEFAULT_FADE_SECS: f64 = 30.0;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum SleepTimerMode {
+    /// Stop after a number of minutes of wall-clock time
+    Duration { minutes: f64 },
+    /// Stop when the current track ends
+    EndOfTrack,
+    /// Stop when the given number of tracks (including the current one) have ended
+    AfterTracks { count: u32 },
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SleepTimerAction {
+    #[default]
+    Pause,
+    Stop,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SleepTimerStatus {
+    pub active: bool,
+    pub mode: Option<SleepTimerMode>,
+    pub action: SleepTimerAction,
+    pub fade_secs: f64,
+    /// Seconds until playback stops, when known
+    pub remaining_secs: Option<f64>,
+    /// Tracks left to play, for track-based modes
+    pub tracks_remaining: Option<u32>,
+    pub fading: bool,
+}
+
+impl SleepTimerStatus {
+    fn inactive() -> Self {
+        Self {
+            active: false,
+            mode: None,
+            action: SleepTimerAction::default(),
+            fade_secs: 0.0,
+            remaining_secs: None,
+            tracks_remaining: None,
+            fading: false,
+        }
+    }
+}
+
+/// What the timer needs to know about playback on each poll
+#[derive(Debug, Clone, Copy)]
+struct PlaybackSnapshot {
+    generation: u64,
+    position: f64,
+    duration: f64,
+    finished: bool,
+}
+
+impl PlaybackSnapshot {
+    fn capture(engine: &AudioEngine) -> Result<Self, AudioEngineError> {
+        let state = engine.get_state()?;
+        Ok(Self {
+            generation: engine.track_generation(),
+            position: state.current_time,
+            duration: state.duration,
+            finished: engine.is_finished()?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Decision {
+    Running {
+        remaining_secs: Option<f64>,
+        tracks_remaining: Option<u32>,
+        fade_level: f32,
+    },
+    Expired,
+}
+
+#[derive(Debug, Clone)]
+struct Timer {
+    mode: SleepTimerMode,
+    action: SleepTimerAction,
+    fade_secs: f64,
+    deadline: Instant,
+    /// Last track generation that may play to its end
+    last_generation: u64,
+}
+
+impl Timer {
+    fn new(mode: SleepTimerMode, action: SleepTimerAction, fade_secs: f64, now: Instant, generation: u64) -> Self {
+        let (deadline, tracks) = match &mode {
+            SleepTimerMode::Duration { minutes } => (now + Duration::from_secs_f64(minutes.max(0.0) * 60.0), 1),
+            SleepTimerMode::EndOfTrack => (now, 1),
+            SleepTimerMode::AfterTracks { count } => (now, (*count).max(1)),
+        };
+
+        Self {
+            mode,
+            action,
+            fade_secs: fade_secs.max(0.0),
+            deadline,
+            last_generation: generation + tracks as u64 - 1,
+        }
+    }
+
+    fn is_track_based(&self) -> bool {
+        !matches!(self.mode, SleepTimerMode::Duration { .. })
+    }
+
+    /// Push the stop point back by minutes (time mode) or tracks (track modes)
+    fn extend(&mut self, minutes: Option<f64>, tracks: Option<u32>) {
+        if self.is_track_based() {
+            self.last_generation += tracks.unwrap_or(1) as u64;
+        } else {
+            self.deadline += Duration::from_secs_f64(minutes.unwrap_or(5.0).max(0.0) * 60.0);
+        }
+    }
+
+    fn fade_level(&self, remaining_secs: f64) -> f32 {
+        if self.fade_secs <= 0.0 {
+            return 1.0;
+        }
+        (remaining_secs / self.fade_secs).clamp(0.0, 1.0) as f32
+    }
+
+    fn evaluate(&self, now: Instant, playback: &PlaybackSnapshot) -> Decision {
+        if !self.is_track_based() {
+            let remaining = self.deadline.saturating_duration_since(now).as_secs_f64();
+            if remaining <= 0.0 {
+                return Decision::Expired;
+            }
+            return Decision::Running {
+                remaining_secs: Some(remaining),
+                tracks_remaining: None,
+                fade_level: self.fade_level(remaining),
+            };
+        }
+
+        // Anything loaded after the last allowed track is stopped straight away
+        if playback.generation > self.last_generation {
+            return Decision::Expired;
+        }
+
+        let tracks_remaining = (self.last_generation - playback.generation + 1) as u32;
+        if tracks_remaining == 1 && playback.finished {
+            return Decision::Expired;
+        }
+
+        let remaining_in_track = (playback.duration > 0.0).then(|| (playback.duration - playback.position).max(0.0));
+        let fade_level = match remaining_in_track {
+            Some(remaining) if tracks_remaining == 1 => self.fade_level(remaining),
+            _ => 1.0,
+        };
+
+        Decision::Running {
+            remaining_secs: if tracks_remaining == 1 { remaining_in_track } else { None },
+            tracks_remaining: Some(tracks_remaining),
+            fade_level,
+        }
+    }
+
+    fn status(&self, decision: &Decision) -> SleepTimerStatus {
+        let (remaining_secs, tracks_remaining, fading) = match decision {
+            Decision::Running {
+                remaining_secs,
+                tracks_remaining,
+                fade_level,
+            } => (*remaining_secs, *tracks_remaining, *fade_level < 1.0),
+            Decision::Expired => (Some(0.0), Some(0), true),
+        };
+
+        SleepTimerStatus {
+            active: true,
+            mode: Some(self.mode.clone()),
+            action: self.action,
+            fade_secs: self.fade_secs,
+            remaining_secs,
+            tracks_remaining,
+            fading,
+        }
+    }
+}
+
+struct RunningTimer {
+    timer: Arc<Mutex<Timer>>,
+    cancelled: Arc<AtomicBool>,
+}
+
+#[derive(Default)]
+pub struct SleepTimerState {
+    running: Mutex<Option<RunningTimer>>,
+}
+
+/// Poll playback until the timer expires or is cancelled
+fn run_timer(
+    timer: Arc<Mutex<Timer>>,
+    cancelled: Arc<AtomicBool>,
+    engine: Arc<Mutex<AudioEngine>>,
+    app: AppHandle,
+) {
+    let mut last_tick: Option<Instant> = None;
+
+    loop {
+        std::thread::sleep(POLL_INTERVAL);
+        if cancelled.load(Ordering::SeqCst) {
+            return;
+        }
+
+        let Ok(engine_guard) = engine.lock() else { return };
+        let playback = match PlaybackSnapshot::capture(&engine_guard) {
+            Ok(playback) => playback,
+            Err(e) => {
+                log::warn!("Sleep timer could not read playback state: {}", e);
+                continue;
+            }
+        };
+
+        let Ok(timer_guard) = timer.lock() else { return };
+        let now = Instant::now();
+        let decision = timer_guard.evaluate(now, &playback);
+        let status = timer_guard.status(&decision);
+        let action = timer_guard.action;
+        drop(timer_guard);
+
+        match decision {
+            Decision::Running { fade_level, .. } => {
+                if let Err(e) = engine_guard.set_fade_level(fade_level) {
+                    log::warn!("Sleep timer failed to fade: {}", e);
+                }
+                drop(engine_guard);
+
+                if last_tick.map_or(true, |tick| now.duration_since(tick) >= TICK_INTERVAL) {
+                    last_tick = Some(now);
+                    let _ = app.emit("sleep_timer:tick", &status);
+                }
+            }
+            Decision::Expired => {
+                log::info!("Sleep timer expired, {:?} playback", action);
+                let result = match action {
+                    SleepTimerAction::Pause => engine_guard.pause(),
+                    SleepTimerAction::Stop => engine_guard.stop(),
+                };
+                if let Err(e) = result {
+                    log::warn!("Sleep timer failed to stop playback: {}", e);
+                }
+                let _ = engine_guard.set_fade_level(1.0);
+                let state = engine_guard.get_state();
+                drop(engine_guard);
+
+                // Only clear the slot if nobody started a new timer meanwhile
+                if let Some(sleep_timer) = app.try_state::<SleepTimerState>() {
+                    if let Ok(mut running) = sleep_timer.running.lock() {
+                        if running.as_ref().is_some_and(|r| Arc::ptr_eq(&r.cancelled, &cancelled)) {
+                            *running = None;
+                        }
+                    }
+                }
+
+                let _ = app.emit("sleep_timer:finished", &status);
+                if let Ok(state) = state {
+                    let _ = app.emit("audio:state_changed", &state);
+                }
+                return;
+            }
+        }
+    }
+}
+
+/// Read playback without holding the engine afterwards. The timer thread locks
+/// the engine before the timer, so commands must not hold a timer while locking it.
+fn capture_playback(engine: &Mutex<AudioEngine>) -> Result<PlaybackSnapshot, String> {
+    let engine_guard = engine.lock()
+        .map_err(|e| format!("Failed to lock engine: {}", e))?;
+    PlaybackSnapshot::capture(&engine_guard)
+        .map_err(|e| e.to_string())
+}
+
+/// Stop a running timer and undo any fade in progress
+fn cancel_running(state: &SleepTimerState, engine: &Arc<Mutex<AudioEngine>>) -> Result<bool, String> {
+    let mut running = state.running.lock()
+        .map_err(|e| format!("Failed to lock sleep timer: {}", e))?;
+
+    let Some(previous) = running.take() else {
+        return Ok(false);
+    };
+    previous.cancelled.store(true, Ordering::SeqCst);
+
+    let engine_guard = engine.lock()
+        .map_err(|e| format!("Failed to lock engine: {}", e))?;
+    engine_guard.set_fade_level(1.0)
+        .map_err(|e| e.to_string())?;
+
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn playback(generation: u64, position: f64, duration: f64, finished: bool) -> PlaybackSnapshot {
+        PlaybackSnapshot {
+            generation,
+            position,
+            duration,
+            finished,
+        }
+    }
+
+    #[test]
+    fn test_duration_mode_fades_then_expires() {
+        let start = Instant::now();
+        let mut timer = Timer::new(SleepTimerMode::Duration { minutes: 1.0 }, SleepTimerAction::Pause, 10.0, start, 0);
+        let idle = playback(0, 0.0, 0.0, false);
+
+        assert_eq!(
+            timer.evaluate(start + Duration::from_secs(30), &idle),
+            Decision::Running {
+                remaining_secs: Some(30.0),
+                tracks_remaining: None,
+                fade_level: 1.0
+            }
+        );
+        match timer.evaluate(start + Duration::from_secs(55), &idle) {
+            Decision::Running { fade_level, .. } => assert!((fade_level - 0.5).abs() < 1e-6),
+            other => panic!("unexpected {:?}", other),
+        }
+        assert_eq!(timer.evaluate(start + Duration::from_secs(60), &idle), Decision::Expired);
+
+        timer.extend(Some(2.0), None);
+        assert!(matches!(timer.evaluate(start + Duration::from_secs(60), &idle), Decision::Running { .. }));
+        assert_eq!(timer.evaluate(start + Duration::from_secs(180), &idle), Decision::Expired);
+    }
+
+    #[test]
+    fn test_end_of_track_mode() {
+        let now = Instant::now();
+        let timer = Timer::new(SleepTimerMode::EndOfTrack, SleepTimerAction::Stop, 4.0, now, 7);
+
+        match timer.evaluate(now, &playback(7, 198.0, 200.0, false)) {
+            Decision::Running {
+                remaining_secs,
+                tracks_remaining,
+                fade_level,
+            } => {
+                assert_eq!(remaining_secs, Some(2.0));
+                assert_eq!(tracks_remaining, Some(1));
+                assert!((fade_level - 0.5).abs() < 1e-6);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        assert_eq!(timer.evaluate(now, &playback(7, 200.0, 200.0, true)), Decision::Expired);
+        // The frontend already advanced to the next track
+        assert_eq!(timer.evaluate(now, &playback(8, 0.0, 180.0, false)), Decision::Expired);
+    }
+
+    #[test]
+    fn test_after_tracks_mode_counts_and_extends() {
+        let now = Instant::now();
+        let mut timer = Timer::new(SleepTimerMode::AfterTracks { count: 2 }, SleepTimerAction::Pause, 5.0, now, 3);
+
+        match timer.evaluate(now, &playback(3, 199.0, 200.0, false)) {
+            Decision::Running {
+                tracks_remaining,
+                fade_level,
+                ..
+            } => {
+                assert_eq!(tracks_remaining, Some(2));
+                assert_eq!(fade_level, 1.0);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        assert!(matches!(timer.evaluate(now, &playback(4, 10.0, 200.0, false)), Decision::Running { .. }));
+        assert_eq!(timer.evaluate(now, &playback(4, 200.0, 200.0, true)), Decision::Expired);
+
+        timer.extend(None, Some(1));
+        assert!(matches!(
+            timer.evaluate(now, &playback(4, 200.0, 200.0, true)),
+            Decision::Running { tracks_remaining: Some(2), .. }
+        ));
+    }
+}
+
+// Tauri Commands
+
+use tauri::{AppHandle, Emitter, Manager, State};
+
+#[tauri::command]
+pub fn sleep_timer_start(
+    mode: SleepTimerMode,
+    fade_secs: Option<f64>,
+    action: Option<SleepTimerAction>,
+    sleep_timer: State<'_, SleepTimerState>,
+    engine: State<'_, AudioEngineState>,
+    app: AppHandle,
+) -> Result<SleepTimerStatus, String> {
+    cancel_running(&sleep_timer, &engine.engine)?;
+
+    let generation = engine.engine.lock()
+        .map_err(|e| format!("Failed to lock engine: {}", e))?
+        .track_generation();
+
+    let timer = Timer::new(
+        mode,
+        action.unwrap_or_default(),
+        fade_secs.unwrap_or(DEFAULT_FADE_SECS),
+        Instant::now(),
+        generation,
+    );
+    let status = timer.status(&Decision::Running {
+        remaining_secs: None,
+        tracks_remaining: None,
+        fade_level: 1.0,
+    });
+
+    let timer = Arc::new(Mutex::new(timer));
+    let cancelled = Arc::new(AtomicBool::new(false));
+    {
+        let timer = timer.clone();
+        let cancelled = cancelled.clone();
+        let engine = engine.engine.clone();
+        let app = app.clone();
+        std::thread::spawn(move || run_timer(timer, cancelled, engine, app));
+    }
+
+    let mut running = sleep_timer.running.lock()
+        .map_err(|e| format!("Failed to lock sleep timer: {}", e))?;
+    *running = Some(RunningTimer { timer, cancelled });
+
+    app.emit("sleep_timer:started", &status)
+        .map_err(|e| format!("Failed to emit event: {}", e))?;
+
+    Ok(status)
+}
+
+#[tauri::command]
+pub fn sleep_timer_cancel(
+    sleep_timer: State<'_, SleepTimerState>,
+    engine: State<'_, AudioEngineState>,
+    app: AppHandle,
+) -> Result<SleepTimerStatus, String> {
+    if cancel_running(&sleep_timer, &engine.engine)? {
+        app.emit("sleep_timer:cancelled", SleepTimerStatus::inactive())
+            .map_err(|e| format!("Failed to emit event: {}", e))?;
+    }
+
+    Ok(SleepTimerStatus::inactive())
+}
+
+/// Add minutes to a time-based timer, or tracks to a track-based one
+#[tauri::command]
+pub fn sleep_timer_extend(
+    minutes: Option<f64>,
+    tracks: Option<u32>,
+    sleep_timer: State<'_, SleepTimerState>,
+    engine: State<'_, AudioEngineState>,
+) -> Result<SleepTimerStatus, String> {
+    let playback = capture_playback(&engine.engine)?;
+    let running = sleep_timer.running.lock()
+        .map_err(|e| format!("Failed to lock sleep timer: {}", e))?;
+
+    let running = running.as_ref()
+        .ok_or_else(|| "No sleep timer is running".to_string())?;
+
+    let mut timer = running.timer.lock()
+        .map_err(|e| format!("Failed to lock sleep timer: {}", e))?;
+    timer.extend(minutes, tracks);
+
+    Ok(timer.status(&timer.evaluate(Instant::now(), &playback)))
+}
+
+#[tauri::command]
+pub fn sleep_timer_get_status(
+    sleep_timer: State<'_, SleepTimerState>,
+    engine: State<'_, AudioEngineState>,
+) -> Result<SleepTimerStatus, String> {
+    let playback = capture_playback(&engine.engine)?;
+    let running = sleep_timer.running.lock()
+        .map_err(|e| format!("Failed to lock sleep timer: {}", e))?;
+
+    let Some(running) = running.as_ref() else {
+        return Ok(SleepTimerStatus::inactive());
+    };
+
+    let timer = running.timer.lock()
+        .map_err(|e| format!("Failed to lock sleep timer: {}", e))?;
+
+    Ok(timer.status(&timer.evaluate(Instant::now(), &playback)))
+}
@@ -0,0 +1,510 @@
+// Smart Playlists Module
+// Rule-based playlists evaluated against the library and refreshed as it changes
+
+use crate::errors::PlaylistError;
+use crate::file_manager::TrackMetadata;
+use crate::library::{self, Library, TRACK_COLUMNS};
+use crate::playlists::valid_name;
+use rusqlite::types::Value;
+use rusqlite::{params, params_from_iter, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+/// A condition on library tracks; `all`/`any` nest other rules
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Rule {
+    All { rules: Vec<Rule> },
+    Any { rules: Vec<Rule> },
+    /// Case-insensitive match on the whole genre tag
+    Genre { genre: String },
+    /// Inclusive; an open end is unbounded
+    YearBetween { from: Option<i32>, to: Option<i32> },
+    /// Star rating from 1 to 5
+    RatingAtLeast { rating: u8 },
+    PlayCount { min: Option<u32>, max: Option<u32> },
+    /// Not played in the last `days` days, including never played
+    LastPlayedBefore { days: u32 },
+    AddedInLastDays { days: u32 },
+    PathContains { text: String },
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SmartSort {
+    #[default]
+    Title,
+    Artist,
+    Album,
+    Year,
+    DateAdded,
+    PlayCount,
+    LastPlayed,
+    Rating,
+    Random,
+}
+
+/// Everything that defines a smart playlist's contents; stored as JSON
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SmartQuery {
+    pub rule: Rule,
+    #[serde(default)]
+    pub sort: SmartSort,
+    #[serde(default)]
+    pub descending: bool,
+    #[serde(default)]
+    pub limit: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SmartPlaylist {
+    pub id: i64,
+    pub name: String,
+    pub query: SmartQuery,
+    pub created: i64,
+    pub modified: i64,
+}
+
+/// Payload of `smart_playlist:changed`: a definition was edited or deleted
+/// (`playlist` is `None`), or a library change altered its tracks
+#[derive(Debug, Clone, Serialize)]
+pub struct SmartPlaylistChanged {
+    pub playlist_id: i64,
+    pub playlist: Option<SmartPlaylist>,
+}
+
+impl Rule {
+    fn validate(&self) -> Result<(), PlaylistError> {
+        match self {
+            Rule::All { rules } | Rule::Any { rules } => rules.iter().try_for_each(Rule::validate),
+            Rule::RatingAtLeast { rating } if !(1..=5).contains(rating) => {
+                Err(PlaylistError::InvalidRules(format!("Rating must be 1 to 5, got {}", rating)))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// SQL condition over the `t` alias of `TRACK_COLUMNS`, with its values appended to `values`
+    fn to_sql(&self, now: i64, values: &mut Vec<Value>) -> String {
+        let mut bind = |value: Value| {
+            values.push(value);
+            format!("?{}", values.len())
+        };
+
+        match self {
+            Rule::All { rules } | Rule::Any { rules } if rules.is_empty() => {
+                // Nothing to satisfy for `all`, nothing satisfied for `any`
+                (if matches!(self, Rule::All { .. }) { "1" } else { "0" }).to_string()
+            }
+            Rule::All { rules } | Rule::Any { rules } => {
+                let joiner = if matches!(self, Rule::All { .. }) { " AND " } else { " OR " };
+                let parts: Vec<String> = rules.iter().map(|rule| rule.to_sql(now, values)).collect();
+                format!("({})", parts.join(joiner))
+            }
+            Rule::Genre { genre } => format!("t.genre = {} COLLATE NOCASE", bind(Value::Text(genre.trim().to_string()))),
+            Rule::YearBetween { from, to } => format!(
+                "(t.year >= COALESCE({}, t.year) AND t.year <= COALESCE({}, t.year))",
+                bind(from.map_or(Value::Null, |y| Value::Integer(y.into()))),
+                bind(to.map_or(Value::Null, |y| Value::Integer(y.into()))),
+            ),
+            Rule::RatingAtLeast { rating } => format!("t.rating >= {}", bind(Value::Integer((*rating).into()))),
+            Rule::PlayCount { min, max } => format!(
+                "(t.play_count >= {} AND t.play_count <= COALESCE({}, t.play_count))",
+                bind(Value::Integer(min.unwrap_or(0).into())),
+                bind(max.map_or(Value::Null, |n| Value::Integer(n.into()))),
+            ),
+            Rule::LastPlayedBefore { days } => format!(
+                "(t.last_played IS NULL OR t.last_played < {})",
+                bind(Value::Integer(now - i64::from(*days) * SECONDS_PER_DAY)),
+            ),
+            Rule::AddedInLastDays { days } => format!(
+                "t.date_added >= {}",
+                bind(Value::Integer(now - i64::from(*days) * SECONDS_PER_DAY)),
+            ),
+            Rule::PathContains { text } => format!(
+                "t.file_path LIKE {} ESCAPE '\\'",
+                bind(Value::Text(format!("%{}%", library::escape_like(text)))),
+            ),
+        }
+    }
+}
+
+impl SmartQuery {
+    fn order_sql(&self) -> String {
+        let direction = if self.descending { "DESC" } else { "ASC" };
+        let column = match self.sort {
+            SmartSort::Title => "t.title COLLATE NOCASE",
+            SmartSort::Artist => "ar.name COLLATE NOCASE",
+            SmartSort::Album => "al.title COLLATE NOCASE",
+            SmartSort::Year => "t.year",
+            SmartSort::DateAdded => "t.date_added",
+            SmartSort::PlayCount => "t.play_count",
+            SmartSort::LastPlayed => "t.last_played",
+            SmartSort::Rating => "t.rating",
+            SmartSort::Random => return "RANDOM()".to_string(),
+        };
+        // The path keeps ties in a stable order
+        format!("{} {}, t.file_path", column, direction)
+    }
+}
+
+fn smart_playlist_from_row(row: &Row) -> rusqlite::Result<SmartPlaylist> {
+    let json: String = row.get(2)?;
+    let query = serde_json::from_str(&json).map_err(|e| {
+        rusqlite::Error::FromSqlConversionFailure(2, rusqlite::types::Type::Text, Box::new(e))
+    })?;
+    Ok(SmartPlaylist {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        query,
+        created: row.get(3)?,
+        modified: row.get(4)?,
+    })
+}
+
+fn query_json(query: &SmartQuery) -> Result<String, PlaylistError> {
+    query.rule.validate()?;
+    serde_json::to_string(query).map_err(|e| PlaylistError::InvalidRules(e.to_string()))
+}
+
+impl Library {
+    pub fn smart_playlists(&self) -> Result<Vec<SmartPlaylist>, PlaylistError> {
+        let mut stmt = self.conn.prepare_cached(
+            "SELECT id, name, query, created, modified FROM smart_playlists ORDER BY name COLLATE NOCASE, id",
+        )?;
+        let playlists = stmt
+            .query_map([], smart_playlist_from_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(playlists)
+    }
+
+    pub fn smart_playlist(&self, id: i64) -> Result<SmartPlaylist, PlaylistError> {
+        self.conn
+            .query_row(
+                "SELECT id, name, query, created, modified FROM smart_playlists WHERE id = ?1",
+                [id],
+                smart_playlist_from_row,
+            )
+            .optional()?
+            .ok_or(PlaylistError::NotFound(id))
+    }
+
+    pub fn create_smart_playlist(&self, name: &str, query: &SmartQuery) -> Result<SmartPlaylist, PlaylistError> {
+        let name = valid_name(name)?;
+        self.conn.execute(
+            "INSERT INTO smart_playlists (name, query, created, modified) VALUES (?1, ?2, ?3, ?3)",
+            params![name, query_json(query)?, library::now()],
+        )?;
+        self.smart_playlist(self.conn.last_insert_rowid())
+    }
+
+    pub fn update_smart_playlist(&self, id: i64, name: &str, query: &SmartQuery) -> Result<SmartPlaylist, PlaylistError> {
+        let name = valid_name(name)?;
+        let updated = self.conn.execute(
+            "UPDATE smart_playlists SET name = ?1, query = ?2, modified = ?3 WHERE id = ?4",
+            params![name, query_json(query)?, library::now(), id],
+        )?;
+        if updated == 0 {
+            return Err(PlaylistError::NotFound(id));
+        }
+        self.smart_playlist(id)
+    }
+
+    pub fn delete_smart_playlist(&self, id: i64) -> Result<(), PlaylistError> {
+        match self.conn.execute("DELETE FROM smart_playlists WHERE id = ?1", [id])? {
+            0 => Err(PlaylistError::NotFound(id)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Tracks matching `query` right now
+    pub fn evaluate_smart_query(&self, query: &SmartQuery) -> Result<Vec<TrackMetadata>, PlaylistError> {
+        query.rule.validate()?;
+        let mut values = Vec::new();
+        let condition = query.rule.to_sql(library::now(), &mut values);
+        values.push(Value::Integer(query.limit.map(i64::from).unwrap_or(-1)));
+        let sql = format!(
+            "SELECT {} WHERE {} ORDER BY {} LIMIT ?{}",
+            TRACK_COLUMNS,
+            condition,
+            query.order_sql(),
+            values.len()
+        );
+
+        let mut stmt = self.conn.prepare(&sql)?;
+        let tracks = stmt
+            .query_map(params_from_iter(values), library::track_from_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(tracks)
+    }
+}
+
+/// Last known track IDs of each smart playlist, to tell which ones a library
+/// change actually affected
+#[derive(Default)]
+pub struct SmartPlaylistState {
+    results: Mutex<HashMap<i64, Vec<String>>>,
+}
+
+impl SmartPlaylistState {
+    fn remember(&self, id: i64, tracks: &[TrackMetadata]) {
+        if let Ok(mut results) = self.results.lock() {
+            results.insert(id, tracks.iter().map(|t| t.id.clone()).collect());
+        }
+    }
+
+    fn forget(&self, id: i64) {
+        if let Ok(mut results) = self.results.lock() {
+            results.remove(&id);
+        }
+    }
+
+    /// Re-evaluate every smart playlist and return those whose tracks changed.
+    /// Random order only counts as a change when the set of tracks differs.
+    pub fn refresh(&self, library: &Library) -> Result<Vec<SmartPlaylist>, PlaylistError> {
+        // Only a cache, so a poisoned lock is still usable
+        let mut results = self.results.lock().unwrap_or_else(|e| e.into_inner());
+
+        let mut changed = Vec::new();
+        for playlist in library.smart_playlists()? {
+            let ids: Vec<String> = library.evaluate_smart_query(&playlist.query)?
+                .into_iter()
+                .map(|t| t.id)
+                .collect();
+            let same = match results.get(&playlist.id) {
+                Some(previous) if playlist.query.sort == SmartSort::Random => {
+                    previous.iter().collect::<HashSet<_>>() == ids.iter().collect::<HashSet<_>>()
+                }
+                Some(previous) => *previous == ids,
+                // Nobody has seen this one yet, so there is nothing to update
+                None => true,
+            };
+            results.insert(playlist.id, ids);
+            if !same {
+                changed.push(playlist);
+            }
+        }
+        Ok(changed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn add_track(library: &Library, id: &str, genre: &str, year: i32, play_count: u32, last_played: Option<i64>) {
+        library.upsert_track(&TrackMetadata {
+            id: id.to_string(),
+            title: id.to_string(),
+            artist: "Artist".into(),
+            genre: Some(genre.to_string()),
+            year: Some(year),
+            file_path: format!("/music/{}/{}.mp3", genre, id),
+            ..Default::default()
+        }).unwrap();
+        library.conn.execute(
+            "UPDATE tracks SET play_count = ?1, last_played = ?2 WHERE id = ?3",
+            params![play_count, last_played, id],
+        ).unwrap();
+    }
+
+    fn titles(tracks: Vec<TrackMetadata>) -> Vec<String> {
+        tracks.into_iter().map(|t| t.title).collect()
+    }
+
+    #[test]
+    fn test_rules_round_trip_as_json() {
+        let json = r#"{"rule": {"type": "any", "rules": [
+            {"type": "genre", "genre": "Rock"},
+            {"type": "year_between", "from": 1980, "to": null}
+        ]}, "sort": "play_count", "descending": true, "limit": 25}"#;
+        let query: SmartQuery = serde_json::from_str(json).unwrap();
+        assert_eq!(query.rule, Rule::Any { rules: vec![
+            Rule::Genre { genre: "Rock".into() },
+            Rule::YearBetween { from: Some(1980), to: None },
+        ] });
+        assert_eq!((query.sort, query.descending, query.limit), (SmartSort::PlayCount, true, Some(25)));
+        assert_eq!(serde_json::from_str::<SmartQuery>(&serde_json::to_string(&query).unwrap()).unwrap(), query);
+
+        let minimal: SmartQuery = serde_json::from_str(r#"{"rule": {"type": "all", "rules": []}}"#).unwrap();
+        assert_eq!((minimal.sort, minimal.limit), (SmartSort::Title, None));
+    }
+
+    #[test]
+    fn test_evaluate_rules() {
+        let library = Library::open_in_memory().unwrap();
+        let now = library::now();
+        add_track(&library, "a", "Rock", 1975, 10, Some(now - 2 * SECONDS_PER_DAY));
+        add_track(&library, "b", "rock", 1985, 3, Some(now - 40 * SECONDS_PER_DAY));
+        add_track(&library, "c", "Jazz", 1990, 0, None);
+        add_track(&library, "d", "Jazz", 2001, 7, None);
+
+        let query = |rule: Rule| SmartQuery { rule, sort: SmartSort::Title, descending: false, limit: None };
+        let eval = |query: SmartQuery| titles(library.evaluate_smart_query(&query).unwrap());
+
+        assert_eq!(eval(query(Rule::Genre { genre: "ROCK".into() })), ["a", "b"]);
+        assert_eq!(eval(query(Rule::YearBetween { from: Some(1980), to: Some(2000) })), ["b", "c"]);
+        assert_eq!(eval(query(Rule::LastPlayedBefore { days: 30 })), ["b", "c", "d"]);
+        assert_eq!(eval(query(Rule::AddedInLastDays { days: 1 })), ["a", "b", "c", "d"]);
+        assert_eq!(eval(query(Rule::PathContains { text: "/Jazz/".into() })), ["c", "d"]);
+        assert_eq!(eval(query(Rule::Any { rules: vec![] })), Vec::<String>::new());
+
+        // Jazz, or anything played at least 5 times; the two most played first
+        let mut nested = query(Rule::Any { rules: vec![
+            Rule::Genre { genre: "Jazz".into() },
+            Rule::PlayCount { min: Some(5), max: None },
+        ] });
+        nested.sort = SmartSort::PlayCount;
+        nested.descending = true;
+        nested.limit = Some(2);
+        assert_eq!(eval(nested), ["a", "d"]);
+
+        assert!(matches!(
+            library.evaluate_smart_query(&query(Rule::RatingAtLeast { rating: 9 })),
+            Err(PlaylistError::InvalidRules(_))
+        ));
+    }
+
+    #[test]
+    fn test_refresh_reports_changed_playlists() {
+        let library = Library::open_in_memory().unwrap();
+        add_track(&library, "a", "Rock", 1975, 0, None);
+        let rock = SmartQuery { rule: Rule::Genre { genre: "Rock".into() }, sort: SmartSort::Title, descending: false, limit: None };
+        let jazz = SmartQuery { rule: Rule::Genre { genre: "Jazz".into() }, ..rock.clone() };
+        let rock = library.create_smart_playlist("Rock", &rock).unwrap();
+        library.create_smart_playlist("Jazz", &jazz).unwrap();
+
+        let state = SmartPlaylistState::default();
+        assert!(state.refresh(&library).unwrap().is_empty());
+
+        add_track(&library, "b", "Rock", 1980, 0, None);
+        let changed = state.refresh(&library).unwrap();
+        assert_eq!(changed.iter().map(|p| p.id).collect::<Vec<_>>(), [rock.id]);
+        assert!(state.refresh(&library).unwrap().is_empty());
+    }
+}
+
+// Tauri Commands
+
+use crate::library::LibraryState;
+use tauri::{AppHandle, Emitter, Manager, State};
+
+fn emit_changed(app: &AppHandle, playlist_id: i64, playlist: Option<SmartPlaylist>) {
+    if let Err(e) = app.emit("smart_playlist:changed", SmartPlaylistChanged { playlist_id, playlist }) {
+        log::warn!("Failed to emit smart playlist change: {}", e);
+    }
+}
+
+/// Re-evaluate smart playlists after the library changed and announce those
+/// whose tracks differ. Call with the library unlocked.
+pub fn library_changed(app: &AppHandle) {
+    let (Some(library), Some(state)) = (app.try_state::<LibraryState>(), app.try_state::<SmartPlaylistState>()) else {
+        return;
+    };
+    let changed = match library.library.lock() {
+        Ok(library) => state.refresh(&library),
+        Err(e) => {
+            log::warn!("Failed to lock library: {}", e);
+            return;
+        }
+    };
+
+    match changed {
+        Ok(changed) => {
+            for playlist in changed {
+                emit_changed(app, playlist.id, Some(playlist));
+            }
+        }
+        Err(e) => log::warn!("Failed to refresh smart playlists: {}", e),
+    }
+}
+
+#[tauri::command]
+pub fn smart_playlist_list(library: State<'_, LibraryState>) -> Result<Vec<SmartPlaylist>, String> {
+    let library = library.library.lock()
+        .map_err(|e| format!("Failed to lock library: {}", e))?;
+
+    library.smart_playlists()
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn smart_playlist_create(
+    name: String,
+    query: SmartQuery,
+    library: State<'_, LibraryState>,
+    app: AppHandle,
+) -> Result<SmartPlaylist, String> {
+    let playlist = library.library.lock()
+        .map_err(|e| format!("Failed to lock library: {}", e))?
+        .create_smart_playlist(&name, &query)
+        .map_err(|e| e.to_string())?;
+
+    emit_changed(&app, playlist.id, Some(playlist.clone()));
+    Ok(playlist)
+}
+
+#[tauri::command]
+pub fn smart_playlist_update(
+    id: i64,
+    name: String,
+    query: SmartQuery,
+    library: State<'_, LibraryState>,
+    smart: State<'_, SmartPlaylistState>,
+    app: AppHandle,
+) -> Result<SmartPlaylist, String> {
+    let playlist = library.library.lock()
+        .map_err(|e| format!("Failed to lock library: {}", e))?
+        .update_smart_playlist(id, &name, &query)
+        .map_err(|e| e.to_string())?;
+
+    smart.forget(id);
+    emit_changed(&app, id, Some(playlist.clone()));
+    Ok(playlist)
+}
+
+#[tauri::command]
+pub fn smart_playlist_delete(
+    id: i64,
+    library: State<'_, LibraryState>,
+    smart: State<'_, SmartPlaylistState>,
+    app: AppHandle,
+) -> Result<(), String> {
+    library.library.lock()
+        .map_err(|e| format!("Failed to lock library: {}", e))?
+        .delete_smart_playlist(id)
+        .map_err(|e| e.to_string())?;
+
+    smart.forget(id);
+    emit_changed(&app, id, None);
+    Ok(())
+}
+
+/// Current tracks of a saved smart playlist
+#[tauri::command]
+pub fn smart_playlist_get_tracks(
+    id: i64,
+    library: State<'_, LibraryState>,
+    smart: State<'_, SmartPlaylistState>,
+) -> Result<Vec<TrackMetadata>, String> {
+    let library = library.library.lock()
+        .map_err(|e| format!("Failed to lock library: {}", e))?;
+
+    let playlist = library.smart_playlist(id).map_err(|e| e.to_string())?;
+    let tracks = library.evaluate_smart_query(&playlist.query).map_err(|e| e.to_string())?;
+    smart.remember(id, &tracks);
+    Ok(tracks)
+}
+
+/// Evaluate rules that are still being edited, without saving them
+#[tauri::command]
+pub fn smart_playlist_preview(query: SmartQuery, library: State<'_, LibraryState>) -> Result<Vec<TrackMetadata>, String> {
+    let library = library.library.lock()
+        .map_err(|e| format!("Failed to lock library: {}", e))?;
+
+    library.evaluate_smart_query(&query)
+        .map_err(|e| e.to_string())
+}
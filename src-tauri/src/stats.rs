@@ -0,0 +1,471 @@
+// Stats Module
+// Play counts, skips, last-played times and star ratings. A background tracker
+// follows the engine and counts a play once enough of a track was heard, or a
+// skip when it is left early, logging each listen to the history; ratings can
+// also be written to the file's tags.
+
+use crate::audio_engine::{AudioEngine, AudioEngineState, PlaybackState};
+use crate::errors::LibraryError;
+use crate::file_manager::TrackMetadata;
+use crate::history::Listened;
+use crate::http_stream;
+use crate::library::{now, Library, LibraryState};
+use rusqlite::{params, OptionalExtension};
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// A play counts once half the track, or this many seconds, has been heard
+const PLAY_FRACTION: f64 = 0.5;
+const PLAY_SECONDS: f64 = 240.0;
+
+/// Position jumps larger than this between polls are seeks, not listening
+const MAX_STEP: f64 = 2.0;
+
+/// Leaving a track this close to its end is finishing it, not skipping it
+const END_SLACK: f64 = 1.0;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TrackStats {
+    pub track_id: String,
+    pub play_count: u32,
+    pub skip_count: u32,
+    pub last_played: Option<i64>,
+    /// Stars from 1 to 5
+    pub rating: Option<u8>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TopTrack {
+    pub track: TrackMetadata,
+    /// Plays within the requested range
+    pub plays: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Outcome {
+    Played,
+    Skipped,
+}
+
+/// Listening time of the track being played
+struct Listen {
+    track_id: String,
+    started_at: i64,
+    source: Option<String>,
+    duration: f64,
+    position: f64,
+    listened: f64,
+    counted: bool,
+    /// History entry, logged once the play counted
+    history_id: Option<i64>,
+}
+
+impl Listen {
+    fn new(track_id: String, state: &PlaybackState) -> Self {
+        Self {
+            track_id,
+            started_at: now(),
+            source: state.source.clone(),
+            duration: state.duration,
+            position: state.current_time,
+            listened: 0.0,
+            counted: false,
+            history_id: None,
+        }
+    }
+
+    fn to_history(&self, skipped: bool) -> Listened<'_> {
+        Listened {
+            track_id: &self.track_id,
+            started_at: self.started_at,
+            listened: self.listened,
+            source: self.source.as_deref(),
+            completed: self.counted,
+            skipped,
+        }
+    }
+
+    fn threshold(&self) -> f64 {
+        if self.duration > 0.0 { (self.duration * PLAY_FRACTION).min(PLAY_SECONDS) } else { PLAY_SECONDS }
+    }
+
+    /// Account for the time played since the last poll; a play is reported
+    /// the moment the threshold is crossed
+    fn advance(&mut self, state: &PlaybackState) -> Option<Outcome> {
+        let step = state.current_time - self.position;
+        if state.is_playing && step > 0.0 && step <= MAX_STEP {
+            self.listened += step;
+        }
+        self.position = state.current_time;
+        if state.duration > 0.0 {
+            self.duration = state.duration;
+        }
+
+        if !self.counted && self.listened >= self.threshold() {
+            self.counted = true;
+            return Some(Outcome::Played);
+        }
+        None
+    }
+
+    /// Outcome once the track was left: a skip if it was heard but neither
+    /// counted as played nor run to its end
+    fn finish(&self) -> Option<Outcome> {
+        let at_end = self.duration > 0.0 && self.position >= self.duration - END_SLACK;
+        (!self.counted && self.listened > 0.0 && !at_end).then_some(Outcome::Skipped)
+    }
+}
+
+impl Library {
+    fn track_id_by_path(&self, file_path: &str) -> Result<Option<String>, LibraryError> {
+        Ok(self.conn
+            .query_row("SELECT id FROM tracks WHERE file_path = ?1", [file_path], |row| row.get(0))
+            .optional()?)
+    }
+
+    pub fn record_play(&self, track_id: &str, played_at: i64) -> Result<(), LibraryError> {
+        self.conn.execute(
+            "UPDATE tracks SET play_count = play_count + 1, last_played = MAX(COALESCE(last_played, 0), ?1) WHERE id = ?2",
+            params![played_at, track_id],
+        )?;
+        Ok(())
+    }
+
+    pub fn record_skip(&self, track_id: &str) -> Result<(), LibraryError> {
+        self.conn.execute("UPDATE tracks SET skip_count = skip_count + 1 WHERE id = ?1", [track_id])?;
+        Ok(())
+    }
+
+    pub fn track_stats(&self, track_id: &str) -> Result<Option<TrackStats>, LibraryError> {
+        Ok(self.conn
+            .query_row(
+                "SELECT id, play_count, skip_count, last_played, rating FROM tracks WHERE id = ?1",
+                [track_id],
+                |row| Ok(TrackStats {
+                    track_id: row.get(0)?,
+                    play_count: row.get(1)?,
+                    skip_count: row.get(2)?,
+                    last_played: row.get(3)?,
+                    rating: row.get(4)?,
+                }),
+            )
+            .optional()?)
+    }
+
+    /// Set or clear (`None` or 0) a track's star rating
+    pub fn set_rating(&self, track_id: &str, rating: Option<u8>) -> Result<Option<TrackStats>, LibraryError> {
+        let rating = rating.filter(|r| *r > 0);
+        if let Some(rating) = rating.filter(|r| *r > 5) {
+            return Err(LibraryError::InvalidValue(format!("Rating must be 0 to 5, got {}", rating)));
+        }
+        self.conn.execute("UPDATE tracks SET rating = ?1 WHERE id = ?2", params![rating, track_id])?;
+        self.track_stats(track_id)
+    }
+
+    /// Tracks with the most counted plays in the history that started between
+    /// two Unix times (either end open), most recently played first among ties
+    pub fn top_played(&self, from: Option<i64>, to: Option<i64>, limit: u32) -> Result<Vec<TopTrack>, LibraryError> {
+        let mut stmt = self.conn.prepare(
+            "SELECT track_id, COUNT(*) AS plays FROM history
+             WHERE completed AND (?1 IS NULL OR started_at >= ?1) AND (?2 IS NULL OR started_at < ?2)
+             GROUP BY track_id
+             ORDER BY plays DESC, MAX(started_at) DESC
+             LIMIT ?3",
+        )?;
+        let counts = stmt
+            .query_map(params![from, to, limit], |row| Ok((row.get::<_, String>(0)?, row.get::<_, u32>(1)?)))?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut tracks = Vec::with_capacity(counts.len());
+        for (track_id, plays) in counts {
+            if let Some(track) = self.track(&track_id)? {
+                tracks.push(TopTrack { track, plays });
+            }
+        }
+        Ok(tracks)
+    }
+}
+
+/// Follow the engine in the background and record plays, skips and history
+pub fn start(app: &AppHandle) {
+    let engine = app.state::<AudioEngineState>().engine.clone();
+    let library = app.state::<LibraryState>().library.clone();
+    let app = app.clone();
+    std::thread::spawn(move || run_tracker(engine, library, app));
+}
+
+fn run_tracker(engine: Arc<Mutex<AudioEngine>>, library: Arc<Mutex<Library>>, app: AppHandle) {
+    let mut loaded: Option<(u64, Option<String>)> = None;
+    let mut listen: Option<Listen> = None;
+    loop {
+        std::thread::sleep(POLL_INTERVAL);
+
+        let Ok(engine_guard) = engine.lock() else { return };
+        let generation = engine_guard.track_generation();
+        let state = engine_guard.get_state();
+        drop(engine_guard);
+        let Ok(state) = state else { continue };
+
+        let key = (generation, state.current_track.clone());
+        if loaded.as_ref() != Some(&key) {
+            loaded = Some(key);
+            if let Some(mut left) = listen.take() {
+                let outcome = left.finish();
+                if outcome.is_some() || left.listened > 0.0 {
+                    record(&library, &app, &mut left, outcome);
+                }
+            }
+            listen = state.current_track.as_deref()
+                .filter(|track| !http_stream::is_remote_url(track))
+                .and_then(|track| library.lock().ok()?.track_id_by_path(track).ok()?)
+                .map(|track_id| Listen::new(track_id, &state));
+            continue;
+        }
+
+        if let Some(listen) = listen.as_mut() {
+            if let Some(outcome) = listen.advance(&state) {
+                record(&library, &app, listen, Some(outcome));
+            }
+        }
+    }
+}
+
+/// Store an outcome of `listen`; without one, the listen has ended and only
+/// its history entry is brought up to date
+fn record(library: &Mutex<Library>, app: &AppHandle, listen: &mut Listen, outcome: Option<Outcome>) {
+    let result = match library.lock() {
+        Ok(library) => store(&library, listen, outcome),
+        Err(e) => {
+            log::warn!("Failed to lock library: {}", e);
+            return;
+        }
+    };
+
+    match result {
+        Ok(Some(stats)) => {
+            if let Err(e) = app.emit("library:track_stats", &stats) {
+                log::warn!("Failed to emit track stats: {}", e);
+            }
+            // Play counts and last played feed smart playlist rules
+            crate::smart_playlists::library_changed(app);
+        }
+        Ok(None) => {}
+        Err(e) => log::warn!("Failed to record play of {}: {}", listen.track_id, e),
+    }
+}
+
+/// Write an outcome of `listen` to the library; returns the track's new stats
+/// when its counts changed
+fn store(library: &Library, listen: &mut Listen, outcome: Option<Outcome>) -> Result<Option<TrackStats>, LibraryError> {
+    match (outcome, listen.history_id) {
+        (Some(Outcome::Played), _) => {
+            library.record_play(&listen.track_id, now())?;
+            listen.history_id = library.append_history(&listen.to_history(false))?;
+        }
+        (Some(Outcome::Skipped), _) => {
+            library.record_skip(&listen.track_id)?;
+            library.append_history(&listen.to_history(true))?;
+        }
+        (None, Some(id)) => library.set_history_listened(id, listen.listened)?,
+        (None, None) => {
+            library.append_history(&listen.to_history(false))?;
+        }
+    }
+
+    match outcome {
+        Some(_) => library.track_stats(&listen.track_id),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn playing(current_time: f64, duration: f64) -> PlaybackState {
+        PlaybackState { is_playing: true, current_time, duration, ..Default::default() }
+    }
+
+    #[test]
+    fn test_listen_counts_heard_time_only() {
+        let mut listen = Listen::new("a".into(), &playing(0.0, 600.0));
+        let mut outcomes = Vec::new();
+        // Seeking ahead does not count as listening
+        for time in [0.5, 1.0, 300.0, 300.5] {
+            outcomes.extend(listen.advance(&playing(time, 600.0)));
+        }
+        assert!(outcomes.is_empty());
+        assert_eq!(listen.finish(), Some(Outcome::Skipped));
+
+        // Long tracks count after four minutes, heard in 0.5 s steps
+        for step in 1..=480 {
+            outcomes.extend(listen.advance(&playing(300.5 + step as f64 * 0.5, 600.0)));
+        }
+        assert_eq!(outcomes, vec![Outcome::Played]);
+        assert_eq!(listen.finish(), None);
+
+        // Run to the end without enough heard: neither played nor skipped
+        let mut listen = Listen::new("b".into(), &playing(0.0, 200.0));
+        listen.advance(&playing(1.0, 200.0));
+        listen.advance(&playing(199.5, 200.0));
+        assert_eq!(listen.finish(), None);
+    }
+
+    #[test]
+    fn test_plays_ratings_and_top_played() {
+        let library = Library::open_in_memory().unwrap();
+        let mut ids = Vec::new();
+        for title in ["One", "Two", "Three"] {
+            let file_path = format!("/music/{}.mp3", title);
+            let track = TrackMetadata {
+                id: format!("{:x}", md5::compute(file_path.as_bytes())),
+                title: title.to_string(),
+                artist: "Artist".to_string(),
+                file_path,
+                ..Default::default()
+            };
+            ids.push(library.upsert_track(&track).unwrap());
+        }
+
+        let listen = |index: usize, started_at: i64, completed: bool| Listened {
+            track_id: &ids[index],
+            started_at,
+            listened: 120.0,
+            source: None,
+            completed,
+            skipped: !completed,
+        };
+        for (index, at) in [(0, 100), (0, 200), (1, 150), (1, 250), (1, 260), (2, 1000)] {
+            library.record_play(&ids[index], at).unwrap();
+            library.append_history(&listen(index, at, true)).unwrap();
+        }
+        library.record_skip(&ids[0]).unwrap();
+        for at in [300, 310, 320] {
+            library.append_history(&listen(0, at, false)).unwrap();
+        }
+
+        let stats = library.track_stats(&ids[0]).unwrap().unwrap();
+        assert_eq!((stats.play_count, stats.skip_count, stats.last_played), (2, 1, Some(200)));
+
+        let top = library.top_played(None, Some(500), 10).unwrap();
+        let top: Vec<(&str, u32)> = top.iter().map(|t| (t.track.title.as_str(), t.plays)).collect();
+        assert_eq!(top, vec![("Two", 3), ("One", 2)]);
+        let recent = library.top_played(Some(200), None, 1).unwrap();
+        assert_eq!(recent[0].track.title, "Two");
+
+        assert_eq!(library.set_rating(&ids[2], Some(4)).unwrap().unwrap().rating, Some(4));
+        assert_eq!(library.set_rating(&ids[2], Some(0)).unwrap().unwrap().rating, None);
+        assert!(library.set_rating(&ids[2], Some(6)).is_err());
+    }
+}
+
+// Tauri Commands
+
+use crate::cue;
+use crate::file_manager::FileManager;
+use crate::library::LibraryChanges;
+use crate::tag_writer::{self, TagChanges};
+use std::path::Path;
+use tauri::State;
+
+#[tauri::command]
+pub fn get_track_stats(track_id: String, library: State<'_, LibraryState>) -> Result<Option<TrackStats>, String> {
+    let library = library.library.lock()
+        .map_err(|e| format!("Failed to lock library: {}", e))?;
+
+    library.track_stats(&track_id)
+        .map_err(|e| e.to_string())
+}
+
+/// Rate a track from 1 to 5 stars (0 or none clears it). With `write_tag` the
+/// rating is also written to the file, unless it is playing or a CUE track;
+/// the library keeps the rating either way.
+#[tauri::command]
+pub async fn set_rating(
+    track_id: String,
+    rating: Option<u8>,
+    write_tag: Option<bool>,
+    library: State<'_, LibraryState>,
+    engine: State<'_, AudioEngineState>,
+    app: AppHandle,
+) -> Result<Option<TrackStats>, String> {
+    let library = library.library.clone();
+    let engine = engine.engine.clone();
+
+    let (stats, updated) = tauri::async_runtime::spawn_blocking(move || {
+        let (stats, track) = {
+            let library = library.lock()
+                .map_err(|e| format!("Failed to lock library: {}", e))?;
+            let stats = library.set_rating(&track_id, rating)
+                .map_err(|e| e.to_string())?;
+            let track = match stats {
+                Some(_) if write_tag.unwrap_or(false) => library.track(&track_id).map_err(|e| e.to_string())?,
+                _ => None,
+            };
+            (stats, track)
+        };
+        let Some(track) = track else { return Ok((stats, None)) };
+        let path = Path::new(&track.file_path);
+
+        // Hold the engine so playback cannot switch to this file mid-write
+        let engine_guard = engine.lock()
+            .map_err(|e| format!("Failed to lock engine: {}", e))?;
+        let is_playing = engine_guard.get_state()
+            .map(|state| state.current_track.as_deref() == Some(track.file_path.as_str()))
+            .map_err(|e| e.to_string())?;
+        if cue::is_virtual(&track.file_path) || is_playing {
+            log::warn!("Not writing rating to {} while it is in use", track.file_path);
+            return Ok((stats, None));
+        }
+
+        let changes = TagChanges { rating: Some(rating.unwrap_or(0)), ..Default::default() };
+        let written = tag_writer::write_to_file(path, &changes);
+        drop(engine_guard);
+        if let Err(e) = written {
+            log::warn!("Failed to write rating to {}: {}", track.file_path, e);
+            return Ok((stats, None));
+        }
+
+        // The file changed, so its stored stamp and tags are refreshed
+        let metadata = FileManager::new().metadata_or_fallback(path);
+        let stored = library.lock()
+            .map_err(|e| e.to_string())
+            .and_then(|library| library.upsert_track(&metadata).map_err(|e| e.to_string()));
+        match stored {
+            Ok(id) => Ok::<_, String>((stats, Some(id))),
+            Err(e) => {
+                log::warn!("Failed to update library for {}: {}", track.file_path, e);
+                Ok((stats, None))
+            }
+        }
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+
+    if let Some(id) = updated {
+        let changes = LibraryChanges { updated: vec![id], ..Default::default() };
+        let _ = app.emit("library:changed", &changes);
+    }
+    if stats.is_some() {
+        crate::smart_playlists::library_changed(&app);
+    }
+    Ok(stats)
+}
+
+#[tauri::command]
+pub fn get_top_played(
+    from: Option<i64>,
+    to: Option<i64>,
+    limit: Option<u32>,
+    library: State<'_, LibraryState>,
+) -> Result<Vec<TopTrack>, String> {
+    let library = library.library.lock()
+        .map_err(|e| format!("Failed to lock library: {}", e))?;
+
+    library.top_played(from, to, limit.unwrap_or(50))
+        .map_err(|e| e.to_string())
+}
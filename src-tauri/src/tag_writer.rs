@@ -0,0 +1,1082 @@
+// Tag Writer Module
+// Writes tag edits back to files: ID3v2 (MP3), Vorbis comments (FLAC, Ogg Vorbis, Opus)
+// and MP4 metadata atoms. Every write goes to a temp copy that is renamed into place,
+// and anything we do not edit (other frames, comments, atoms, pictures) is kept as is.
+
+use crate::errors::TagWriteError;
+use ogg::{PacketReader, PacketWriteEndInfo, PacketWriter};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// Edits to apply; `None` leaves a field alone, an empty string or 0 removes it
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TagChanges {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub album_artist: Option<String>,
+    pub genre: Option<String>,
+    pub composer: Option<String>,
+    pub comment: Option<String>,
+    pub date: Option<String>,
+    pub track_number: Option<u32>,
+    pub track_total: Option<u32>,
+    pub disc_number: Option<u32>,
+    pub disc_total: Option<u32>,
+    pub bpm: Option<u32>,
+    pub compilation: Option<bool>,
+    /// Stars from 1 to 5, as POPM (ID3) or RATING (Vorbis); MP4 has no common
+    /// rating atom, so it is left alone there
+    pub rating: Option<u8>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Title,
+    Artist,
+    Album,
+    AlbumArtist,
+    Genre,
+    Composer,
+    Comment,
+    Date,
+    TrackNumber,
+    TrackTotal,
+    DiscNumber,
+    DiscTotal,
+    Bpm,
+    Compilation,
+    Rating,
+}
+
+/// One field edit; `None` removes the field
+type Edit = (Field, Option<String>);
+
+impl TagChanges {
+    fn edits(&self) -> Vec<Edit> {
+        let text = |value: &Option<String>| value.as_ref().map(|v| {
+            let v = v.trim();
+            if v.is_empty() { None } else { Some(v.to_string()) }
+        });
+        let number = |value: Option<u32>| value.map(|n| if n == 0 { None } else { Some(n.to_string()) });
+
+        [
+            (Field::Title, text(&self.title)),
+            (Field::Artist, text(&self.artist)),
+            (Field::Album, text(&self.album)),
+            (Field::AlbumArtist, text(&self.album_artist)),
+            (Field::Genre, text(&self.genre)),
+            (Field::Composer, text(&self.composer)),
+            (Field::Comment, text(&self.comment)),
+            (Field::Date, text(&self.date)),
+            (Field::TrackNumber, number(self.track_number)),
+            (Field::TrackTotal, number(self.track_total)),
+            (Field::DiscNumber, number(self.disc_number)),
+            (Field::DiscTotal, number(self.disc_total)),
+            (Field::Bpm, number(self.bpm)),
+            (Field::Compilation, self.compilation.map(|c| c.then(|| "1".to_string()))),
+            (Field::Rating, self.rating.map(|r| if r == 0 { None } else { Some(r.to_string()) })),
+        ]
+        .into_iter()
+        .filter_map(|(field, edit)| edit.map(|value| (field, value)))
+        .collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.edits().is_empty()
+    }
+}
+
+fn parse_number(value: &str) -> Result<u32, TagWriteError> {
+    value.parse().map_err(|_| TagWriteError::InvalidValue(value.to_string()))
+}
+
+/// POPM byte for a star rating, using the steps Windows Media Player reads
+fn popm_rating(stars: u32) -> u8 {
+    match stars {
+        1 => 1,
+        2 => 64,
+        3 => 128,
+        4 => 196,
+        _ => 255,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TagFormat {
+    Id3,
+    Flac,
+    Ogg,
+    Mp4,
+}
+
+/// Decide how to write tags from the file contents, using the extension only for MP3
+fn detect_format(path: &Path) -> Result<TagFormat, TagWriteError> {
+    let mut header = [0u8; 12];
+    let count = File::open(path)?.read(&mut header)?;
+    let header = &header[..count];
+
+    if header.starts_with(b"fLaC") {
+        return Ok(TagFormat::Flac);
+    }
+    if header.starts_with(b"OggS") {
+        return Ok(TagFormat::Ogg);
+    }
+    if header.len() >= 8 && &header[4..8] == b"ftyp" {
+        return Ok(TagFormat::Mp4);
+    }
+    // MPEG audio: an existing ID3v2 tag or a frame sync
+    let is_mpeg = header.starts_with(b"ID3") || (header.len() >= 2 && header[0] == 0xFF && header[1] & 0xE0 == 0xE0);
+    if is_mpeg {
+        return Ok(TagFormat::Id3);
+    }
+
+    Err(TagWriteError::UnsupportedFormat(path.display().to_string()))
+}
+
+/// Apply `changes` to the file at `path`
+pub fn write_to_file(path: &Path, changes: &TagChanges) -> Result<(), TagWriteError> {
+    let edits = changes.edits();
+    if edits.is_empty() {
+        return Ok(());
+    }
+    if let Some(rating) = changes.rating.filter(|r| *r > 5) {
+        return Err(TagWriteError::InvalidValue(format!("Rating must be 0 to 5, got {}", rating)));
+    }
+
+    let format = detect_format(path)?;
+    let file_name = path.file_name()
+        .ok_or_else(|| TagWriteError::InvalidFile(path.display().to_string()))?;
+    // Same directory, so the final rename never crosses filesystems
+    let temp_path = path.with_file_name(format!(".{}.tagtmp", file_name.to_string_lossy()));
+
+    let result = match format {
+        TagFormat::Id3 => write_id3(path, &temp_path, &edits),
+        TagFormat::Flac => write_flac(path, &temp_path, &edits),
+        TagFormat::Ogg => write_ogg(path, &temp_path, &edits),
+        TagFormat::Mp4 => write_mp4(path, &temp_path, &edits),
+    }
+    .and_then(|_| {
+        std::fs::set_permissions(&temp_path, std::fs::metadata(path)?.permissions())?;
+        std::fs::rename(&temp_path, path)?;
+        Ok(())
+    });
+
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp_path);
+    }
+    result
+}
+
+// ID3v2
+
+fn write_id3(path: &Path, temp_path: &Path, edits: &[Edit]) -> Result<(), TagWriteError> {
+    use id3::{Tag, TagLike};
+
+    let mut tag = match Tag::read_from_path(path) {
+        Ok(tag) => tag,
+        Err(e) if matches!(e.kind, id3::ErrorKind::NoTag) => Tag::new(),
+        Err(e) => return Err(TagWriteError::InvalidFile(e.to_string())),
+    };
+
+    for (field, value) in edits {
+        match (field, value.as_deref()) {
+            (Field::Title, Some(v)) => tag.set_title(v),
+            (Field::Title, None) => tag.remove_title(),
+            (Field::Artist, Some(v)) => tag.set_artist(v),
+            (Field::Artist, None) => tag.remove_artist(),
+            (Field::Album, Some(v)) => tag.set_album(v),
+            (Field::Album, None) => tag.remove_album(),
+            (Field::AlbumArtist, Some(v)) => tag.set_album_artist(v),
+            (Field::AlbumArtist, None) => tag.remove_album_artist(),
+            (Field::Genre, Some(v)) => tag.set_genre(v),
+            (Field::Genre, None) => tag.remove_genre(),
+            (Field::Composer, Some(v)) => tag.set_text("TCOM", v),
+            (Field::Composer, None) => {
+                tag.remove("TCOM");
+            }
+            (Field::Comment, value) => {
+                tag.remove_comment(Some(""), None);
+                if let Some(v) = value {
+                    tag.add_frame(id3::frame::Comment {
+                        lang: "eng".to_string(),
+                        description: String::new(),
+                        text: v.to_string(),
+                    });
+                }
+            }
+            (Field::Date, Some(v)) => {
+                let timestamp: id3::Timestamp = v.parse()
+                    .map_err(|_| TagWriteError::InvalidValue(v.to_string()))?;
+                tag.remove_year();
+                tag.remove("TDAT");
+                tag.remove_date_recorded();
+                if tag.version() == id3::Version::Id3v23 {
+                    // v2.3 has no TDRC: the year goes in TYER, day and month in TDAT as DDMM
+                    tag.set_year(timestamp.year);
+                    if let (Some(month), Some(day)) = (timestamp.month, timestamp.day) {
+                        tag.set_text("TDAT", format!("{:02}{:02}", day, month));
+                    }
+                } else {
+                    tag.set_date_recorded(timestamp);
+                }
+            }
+            (Field::Date, None) => {
+                tag.remove_year();
+                tag.remove("TDAT");
+                tag.remove_date_recorded();
+            }
+            (Field::TrackNumber, Some(v)) => tag.set_track(parse_number(v)?),
+            (Field::TrackNumber, None) => tag.remove_track(),
+            (Field::TrackTotal, Some(v)) => tag.set_total_tracks(parse_number(v)?),
+            (Field::TrackTotal, None) => tag.remove_total_tracks(),
+            (Field::DiscNumber, Some(v)) => tag.set_disc(parse_number(v)?),
+            (Field::DiscNumber, None) => tag.remove_disc(),
+            (Field::DiscTotal, Some(v)) => tag.set_total_discs(parse_number(v)?),
+            (Field::DiscTotal, None) => tag.remove_total_discs(),
+            (Field::Bpm, Some(v)) => tag.set_text("TBPM", v),
+            (Field::Bpm, None) => {
+                tag.remove("TBPM");
+            }
+            (Field::Compilation, Some(v)) => tag.set_text("TCMP", v),
+            (Field::Compilation, None) => {
+                tag.remove("TCMP");
+            }
+            (Field::Rating, value) => {
+                tag.remove("POPM");
+                if let Some(v) = value {
+                    tag.add_frame(id3::frame::Popularimeter {
+                        user: String::new(),
+                        rating: popm_rating(parse_number(v)?),
+                        counter: 0,
+                    });
+                }
+            }
+        }
+    }
+
+    std::fs::copy(path, temp_path)?;
+    // Keep the version the file already uses (new tags default to ID3v2.4)
+    tag.write_to_path(temp_path, tag.version())
+        .map_err(|e| TagWriteError::InvalidFile(e.to_string()))
+}
+
+// Vorbis comments, shared by FLAC and Ogg
+
+/// Comment keys per field; the first is written, all are removed
+fn vorbis_keys(field: Field) -> &'static [&'static str] {
+    match field {
+        Field::Title => &["TITLE"],
+        Field::Artist => &["ARTIST"],
+        Field::Album => &["ALBUM"],
+        Field::AlbumArtist => &["ALBUMARTIST", "ALBUM ARTIST"],
+        Field::Genre => &["GENRE"],
+        Field::Composer => &["COMPOSER"],
+        Field::Comment => &["COMMENT", "DESCRIPTION"],
+        Field::Date => &["DATE", "YEAR"],
+        Field::TrackNumber => &["TRACKNUMBER"],
+        Field::TrackTotal => &["TRACKTOTAL", "TOTALTRACKS"],
+        Field::DiscNumber => &["DISCNUMBER"],
+        Field::DiscTotal => &["DISCTOTAL", "TOTALDISCS"],
+        Field::Bpm => &["BPM"],
+        Field::Compilation => &["COMPILATION"],
+        Field::Rating => &["RATING"],
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct VorbisComments {
+    vendor: String,
+    comments: Vec<String>,
+}
+
+impl VorbisComments {
+    fn new() -> Self {
+        Self {
+            vendor: concat!("gemini-audio-player ", env!("CARGO_PKG_VERSION")).to_string(),
+            comments: Vec::new(),
+        }
+    }
+
+    /// Parse a comment block; returns the comments and the number of bytes used
+    fn parse(data: &[u8]) -> Result<(Self, usize), TagWriteError> {
+        let invalid = || TagWriteError::InvalidFile("Malformed Vorbis comment block".to_string());
+        let mut pos = 0;
+        let read_u32 = |pos: &mut usize| -> Result<usize, TagWriteError> {
+            let bytes = data.get(*pos..*pos + 4).ok_or_else(invalid)?;
+            *pos += 4;
+            Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize)
+        };
+
+        let vendor_len = read_u32(&mut pos)?;
+        let vendor = data.get(pos..pos + vendor_len).ok_or_else(invalid)?;
+        let vendor = String::from_utf8_lossy(vendor).to_string();
+        pos += vendor_len;
+
+        let count = read_u32(&mut pos)?;
+        let mut comments = Vec::with_capacity(count.min(1024));
+        for _ in 0..count {
+            let len = read_u32(&mut pos)?;
+            let comment = data.get(pos..pos + len).ok_or_else(invalid)?;
+            comments.push(String::from_utf8_lossy(comment).to_string());
+            pos += len;
+        }
+
+        Ok((Self { vendor, comments }, pos))
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&(self.vendor.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(self.vendor.as_bytes());
+        bytes.extend_from_slice(&(self.comments.len() as u32).to_le_bytes());
+        for comment in &self.comments {
+            bytes.extend_from_slice(&(comment.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(comment.as_bytes());
+        }
+        bytes
+    }
+
+    fn apply(&mut self, edits: &[Edit]) {
+        for (field, value) in edits {
+            let keys = vorbis_keys(*field);
+            self.comments.retain(|comment| {
+                let key = comment.split('=').next().unwrap_or("");
+                !keys.iter().any(|k| k.eq_ignore_ascii_case(key))
+            });
+            if let Some(value) = value {
+                self.comments.push(format!("{}={}", keys[0], value));
+            }
+        }
+    }
+}
+
+// FLAC
+
+const FLAC_VORBIS_COMMENT: u8 = 4;
+
+fn write_flac(path: &Path, temp_path: &Path, edits: &[Edit]) -> Result<(), TagWriteError> {
+    let mut reader = BufReader::new(File::open(path)?);
+
+    let mut magic = [0u8; 4];
+    reader.read_exact(&mut magic)?;
+    if &magic != b"fLaC" {
+        return Err(TagWriteError::InvalidFile("Missing fLaC marker".to_string()));
+    }
+
+    // Metadata blocks as (type, body); audio frames follow the last one
+    let mut blocks: Vec<(u8, Vec<u8>)> = Vec::new();
+    loop {
+        let mut header = [0u8; 4];
+        reader.read_exact(&mut header)?;
+        let is_last = header[0] & 0x80 != 0;
+        let block_type = header[0] & 0x7F;
+        let len = u32::from_be_bytes([0, header[1], header[2], header[3]]) as usize;
+        let mut body = vec![0u8; len];
+        reader.read_exact(&mut body)?;
+        blocks.push((block_type, body));
+        if is_last {
+            break;
+        }
+    }
+
+    let mut comments = match blocks.iter().find(|(t, _)| *t == FLAC_VORBIS_COMMENT) {
+        Some((_, body)) => VorbisComments::parse(body)?.0,
+        None => VorbisComments::new(),
+    };
+    comments.apply(edits);
+    let body = comments.to_bytes();
+    if body.len() >= 1 << 24 {
+        return Err(TagWriteError::InvalidValue("Vorbis comment block too large".to_string()));
+    }
+
+    match blocks.iter_mut().find(|(t, _)| *t == FLAC_VORBIS_COMMENT) {
+        Some(block) => block.1 = body,
+        // STREAMINFO must stay first
+        None => blocks.insert(1.min(blocks.len()), (FLAC_VORBIS_COMMENT, body)),
+    }
+
+    let mut writer = BufWriter::new(File::create(temp_path)?);
+    writer.write_all(b"fLaC")?;
+    let last_index = blocks.len() - 1;
+    for (index, (block_type, body)) in blocks.iter().enumerate() {
+        let flag = if index == last_index { 0x80 } else { 0 };
+        let len = (body.len() as u32).to_be_bytes();
+        writer.write_all(&[flag | block_type, len[1], len[2], len[3]])?;
+        writer.write_all(body)?;
+    }
+    io::copy(&mut reader, &mut writer)?;
+    writer.flush()?;
+    Ok(())
+}
+
+// Ogg Vorbis / Opus
+
+/// Rewrite the comment header packet of a Vorbis or Opus stream
+fn rewrite_comment_packet(packet: &[u8], edits: &[Edit]) -> Result<Vec<u8>, TagWriteError> {
+    let (prefix, framing) = if packet.starts_with(b"\x03vorbis") {
+        (&b"\x03vorbis"[..], true)
+    } else if packet.starts_with(b"OpusTags") {
+        (&b"OpusTags"[..], false)
+    } else {
+        return Err(TagWriteError::UnsupportedFormat("Ogg stream without Vorbis or Opus comments".to_string()));
+    };
+
+    let (mut comments, used) = VorbisComments::parse(&packet[prefix.len()..])?;
+    comments.apply(edits);
+
+    let mut rewritten = prefix.to_vec();
+    rewritten.extend_from_slice(&comments.to_bytes());
+    if framing {
+        rewritten.push(1);
+    } else {
+        // Opus allows binary data after the comments; keep it
+        rewritten.extend_from_slice(&packet[prefix.len() + used..]);
+    }
+    Ok(rewritten)
+}
+
+fn write_ogg(path: &Path, temp_path: &Path, edits: &[Edit]) -> Result<(), TagWriteError> {
+    let ogg_error = |e: ogg::OggReadError| TagWriteError::InvalidFile(e.to_string());
+
+    let mut reader = PacketReader::new(BufReader::new(File::open(path)?));
+    let mut writer = PacketWriter::new(BufWriter::new(File::create(temp_path)?));
+
+    let mut first_serial = None;
+    let mut packets_in_first_stream = 0;
+    while let Some(packet) = reader.read_packet().map_err(ogg_error)? {
+        let serial = packet.stream_serial();
+        // Page boundaries and granule positions are reproduced exactly
+        let end_info = if packet.last_in_stream() {
+            PacketWriteEndInfo::EndStream
+        } else if packet.last_in_page() {
+            PacketWriteEndInfo::EndPage
+        } else {
+            PacketWriteEndInfo::NormalPacket
+        };
+        let absgp = packet.absgp_page();
+
+        let mut data = packet.data;
+        if *first_serial.get_or_insert(serial) == serial {
+            packets_in_first_stream += 1;
+            // The second packet of a Vorbis or Opus stream holds the comments
+            if packets_in_first_stream == 2 {
+                data = rewrite_comment_packet(&data, edits)?;
+            }
+        }
+
+        writer.write_packet(data.into_boxed_slice(), serial, end_info, absgp)?;
+    }
+
+    if packets_in_first_stream < 2 {
+        return Err(TagWriteError::InvalidFile("Ogg stream has no comment header".to_string()));
+    }
+    writer.into_inner().flush()?;
+    Ok(())
+}
+
+// MP4
+
+#[derive(Debug, Clone)]
+pub(crate) struct Atom {
+    pub(crate) kind: [u8; 4],
+    pub(crate) body: Vec<u8>,
+}
+
+impl Atom {
+    pub(crate) fn new(kind: &[u8; 4], body: Vec<u8>) -> Self {
+        Self { kind: *kind, body }
+    }
+
+    pub(crate) fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.body.len() + 8);
+        bytes.extend_from_slice(&((self.body.len() + 8) as u32).to_be_bytes());
+        bytes.extend_from_slice(&self.kind);
+        bytes.extend_from_slice(&self.body);
+        bytes
+    }
+}
+
+pub(crate) fn parse_atoms(mut data: &[u8]) -> Result<Vec<Atom>, TagWriteError> {
+    let invalid = || TagWriteError::InvalidFile("Malformed MP4 atom".to_string());
+    let mut atoms = Vec::new();
+    while data.len() >= 8 {
+        let size = u32::from_be_bytes([data[0], data[1], data[2], data[3]]) as usize;
+        let kind = [data[4], data[5], data[6], data[7]];
+        let (header, size) = match size {
+            0 => (8, data.len()),
+            1 => {
+                let large = data.get(8..16).ok_or_else(invalid)?;
+                (16, u64::from_be_bytes(large.try_into().map_err(|_| invalid())?) as usize)
+            }
+            size => (8, size),
+        };
+        if size < header || size > data.len() {
+            return Err(invalid());
+        }
+        atoms.push(Atom { kind, body: data[header..size].to_vec() });
+        data = &data[size..];
+    }
+    Ok(atoms)
+}
+
+fn serialize_atoms(atoms: &[Atom]) -> Vec<u8> {
+    atoms.iter().flat_map(Atom::to_bytes).collect()
+}
+
+fn child_mut<'a>(atoms: &'a mut Vec<Atom>, kind: &[u8; 4], create: impl FnOnce() -> Atom) -> &'a mut Atom {
+    match atoms.iter().position(|a| &a.kind == kind) {
+        Some(index) => &mut atoms[index],
+        None => {
+            atoms.push(create());
+            atoms.last_mut().unwrap()
+        }
+    }
+}
+
+/// `data` atom payload of an ilst item
+fn ilst_data(item: &Atom) -> Option<&[u8]> {
+    parse_atoms(&item.body)
+        .ok()?
+        .into_iter()
+        .find(|a| &a.kind == b"data")
+        .and_then(|_| item.body.get(16..))
+}
+
+fn ilst_item(kind: &[u8; 4], data_type: u32, payload: &[u8]) -> Atom {
+    let mut data = Vec::with_capacity(payload.len() + 8);
+    data.extend_from_slice(&data_type.to_be_bytes());
+    data.extend_from_slice(&[0, 0, 0, 0]); // locale
+    data.extend_from_slice(payload);
+    Atom::new(kind, Atom::new(b"data", data).to_bytes())
+}
+
+/// Update a `trkn`/`disk` style number pair, keeping the half not being edited
+fn set_pair(items: &mut Vec<Atom>, kind: &[u8; 4], number: Option<Option<u16>>, total: Option<Option<u16>>, trailing: usize) {
+    let (old_number, old_total) = items.iter()
+        .find(|a| &a.kind == kind)
+        .and_then(ilst_data)
+        .filter(|d| d.len() >= 6)
+        .map(|d| (u16::from_be_bytes([d[2], d[3]]), u16::from_be_bytes([d[4], d[5]])))
+        .unwrap_or((0, 0));
+
+    let number = number.map(|n| n.unwrap_or(0)).unwrap_or(old_number);
+    let total = total.map(|t| t.unwrap_or(0)).unwrap_or(old_total);
+    items.retain(|a| &a.kind != kind);
+    if number == 0 && total == 0 {
+        return;
+    }
+
+    let mut payload = vec![0, 0];
+    payload.extend_from_slice(&number.to_be_bytes());
+    payload.extend_from_slice(&total.to_be_bytes());
+    payload.resize(payload.len() + trailing, 0);
+    items.push(ilst_item(kind, 0, &payload));
+}
+
+fn apply_ilst_edits(items: &mut Vec<Atom>, edits: &[Edit]) -> Result<(), TagWriteError> {
+    let pair_value = |value: &Option<String>| -> Result<Option<Option<u16>>, TagWriteError> {
+        match value {
+            Some(v) => {
+                let n = parse_number(v)?;
+                u16::try_from(n).map(|n| Some(Some(n))).map_err(|_| TagWriteError::InvalidValue(v.clone()))
+            }
+            None => Ok(Some(None)),
+        }
+    };
+
+    for (field, value) in edits {
+        let text_kind: Option<&[u8; 4]> = match field {
+            Field::Title => Some(b"\xa9nam"),
+            Field::Artist => Some(b"\xa9ART"),
+            Field::Album => Some(b"\xa9alb"),
+            Field::AlbumArtist => Some(b"aART"),
+            Field::Genre => Some(b"\xa9gen"),
+            Field::Composer => Some(b"\xa9wrt"),
+            Field::Comment => Some(b"\xa9cmt"),
+            Field::Date => Some(b"\xa9day"),
+            _ => None,
+        };
+
+        if let Some(kind) = text_kind {
+            items.retain(|a| &a.kind != kind);
+            if *field == Field::Genre {
+                // Numeric ID3-style genre would otherwise shadow the text one
+                items.retain(|a| &a.kind != b"gnre");
+            }
+            if let Some(v) = value {
+                items.push(ilst_item(kind, 1, v.as_bytes()));
+            }
+            continue;
+        }
+
+        match field {
+            Field::TrackNumber => set_pair(items, b"trkn", pair_value(value)?, None, 2),
+            Field::TrackTotal => set_pair(items, b"trkn", None, pair_value(value)?, 2),
+            Field::DiscNumber => set_pair(items, b"disk", pair_value(value)?, None, 0),
+            Field::DiscTotal => set_pair(items, b"disk", None, pair_value(value)?, 0),
+            Field::Bpm => {
+                items.retain(|a| &a.kind != b"tmpo");
+                if let Some(v) = value {
+                    let bpm = u16::try_from(parse_number(v)?).map_err(|_| TagWriteError::InvalidValue(v.clone()))?;
+                    items.push(ilst_item(b"tmpo", 21, &bpm.to_be_bytes()));
+                }
+            }
+            Field::Compilation => {
+                items.retain(|a| &a.kind != b"cpil");
+                if value.is_some() {
+                    items.push(ilst_item(b"cpil", 21, &[1]));
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Shift every chunk offset in the sample tables by `delta`
+fn shift_chunk_offsets(moov: &mut [Atom], delta: i64) -> Result<(), TagWriteError> {
+    let overflow = || TagWriteError::InvalidFile("Chunk offset out of range".to_string());
+    for trak in moov.iter_mut().filter(|a| &a.kind == b"trak") {
+        let mut trak_children = parse_atoms(&trak.body)?;
+        for mdia in trak_children.iter_mut().filter(|a| &a.kind == b"mdia") {
+            let mut mdia_children = parse_atoms(&mdia.body)?;
+            for minf in mdia_children.iter_mut().filter(|a| &a.kind == b"minf") {
+                let mut minf_children = parse_atoms(&minf.body)?;
+                for stbl in minf_children.iter_mut().filter(|a| &a.kind == b"stbl") {
+                    let mut stbl_children = parse_atoms(&stbl.body)?;
+                    for table in stbl_children.iter_mut() {
+                        let width = match &table.kind {
+                            b"stco" => 4,
+                            b"co64" => 8,
+                            _ => continue,
+                        };
+                        let count = table.body.get(4..8)
+                            .map(|c| u32::from_be_bytes([c[0], c[1], c[2], c[3]]) as usize)
+                            .ok_or_else(overflow)?;
+                        for i in 0..count {
+                            let start = 8 + i * width;
+                            let entry = table.body.get_mut(start..start + width).ok_or_else(overflow)?;
+                            if width == 4 {
+                                let offset = u32::from_be_bytes([entry[0], entry[1], entry[2], entry[3]]) as i64 + delta;
+                                let offset = u32::try_from(offset).map_err(|_| overflow())?;
+                                entry.copy_from_slice(&offset.to_be_bytes());
+                            } else {
+                                let mut raw = [0u8; 8];
+                                raw.copy_from_slice(entry);
+                                let offset = u64::from_be_bytes(raw) as i64 + delta;
+                                let offset = u64::try_from(offset).map_err(|_| overflow())?;
+                                entry.copy_from_slice(&offset.to_be_bytes());
+                            }
+                        }
+                    }
+                    stbl.body = serialize_atoms(&stbl_children);
+                }
+                minf.body = serialize_atoms(&minf_children);
+            }
+            mdia.body = serialize_atoms(&mdia_children);
+        }
+        trak.body = serialize_atoms(&trak_children);
+    }
+    Ok(())
+}
+
+/// Top-level atom positions as (kind, offset, size)
+pub(crate) fn top_level_atoms(file: &mut File) -> Result<Vec<([u8; 4], u64, u64)>, TagWriteError> {
+    let file_len = file.metadata()?.len();
+    let mut atoms = Vec::new();
+    let mut offset = 0;
+    while offset + 8 <= file_len {
+        file.seek(SeekFrom::Start(offset))?;
+        let mut header = [0u8; 16];
+        let count = file.read(&mut header)?;
+        if count < 8 {
+            break;
+        }
+        let kind = [header[4], header[5], header[6], header[7]];
+        let size = match u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as u64 {
+            0 => file_len - offset,
+            1 if count >= 16 => u64::from_be_bytes(header[8..16].try_into().unwrap()),
+            size => size,
+        };
+        if size < 8 || offset + size > file_len {
+            return Err(TagWriteError::InvalidFile("Malformed MP4 atom".to_string()));
+        }
+        atoms.push((kind, offset, size));
+        offset += size;
+    }
+    Ok(atoms)
+}
+
+fn write_mp4(path: &Path, temp_path: &Path, edits: &[Edit]) -> Result<(), TagWriteError> {
+    let mut file = File::open(path)?;
+    let atoms = top_level_atoms(&mut file)?;
+    if atoms.iter().any(|(kind, _, _)| kind == b"moof") {
+        return Err(TagWriteError::UnsupportedFormat("fragmented MP4".to_string()));
+    }
+    let &(_, moov_offset, moov_size) = atoms.iter()
+        .find(|(kind, _, _)| kind == b"moov")
+        .ok_or_else(|| TagWriteError::InvalidFile("No moov atom".to_string()))?;
+    let mdat_after_moov = atoms.iter().any(|(kind, offset, _)| kind == b"mdat" && *offset > moov_offset);
+
+    let mut moov_bytes = vec![0u8; moov_size as usize];
+    file.seek(SeekFrom::Start(moov_offset))?;
+    file.read_exact(&mut moov_bytes)?;
+    let mut moov = parse_atoms(&moov_bytes)?.pop()
+        .ok_or_else(|| TagWriteError::InvalidFile("Empty moov atom".to_string()))?;
+
+    // moov > udta > meta (full box) > ilst
+    let mut moov_children = parse_atoms(&moov.body)?;
+    {
+        let udta = child_mut(&mut moov_children, b"udta", || Atom::new(b"udta", Vec::new()));
+        let mut udta_children = parse_atoms(&udta.body)?;
+        let meta = child_mut(&mut udta_children, b"meta", || {
+            let mut hdlr = vec![0u8; 8];
+            hdlr.extend_from_slice(b"mdirappl");
+            hdlr.extend_from_slice(&[0u8; 9]);
+            let mut body = vec![0, 0, 0, 0];
+            body.extend_from_slice(&Atom::new(b"hdlr", hdlr).to_bytes());
+            Atom::new(b"meta", body)
+        });
+        if meta.body.len() < 4 {
+            return Err(TagWriteError::InvalidFile("Malformed meta atom".to_string()));
+        }
+        let mut meta_children = parse_atoms(&meta.body[4..])?;
+        let ilst = child_mut(&mut meta_children, b"ilst", || Atom::new(b"ilst", Vec::new()));
+        let mut items = parse_atoms(&ilst.body)?;
+        apply_ilst_edits(&mut items, edits)?;
+        ilst.body = serialize_atoms(&items);
+
+        let mut meta_body = meta.body[..4].to_vec();
+        meta_body.extend_from_slice(&serialize_atoms(&meta_children));
+        meta.body = meta_body;
+        udta.body = serialize_atoms(&udta_children);
+    }
+
+    let new_size = serialize_atoms(&moov_children).len() as i64 + 8;
+    if mdat_after_moov {
+        // Audio data moves by however much moov grew or shrank
+        shift_chunk_offsets(&mut moov_children, new_size - moov_size as i64)?;
+    }
+    moov.body = serialize_atoms(&moov_children);
+
+    let mut writer = BufWriter::new(File::create(temp_path)?);
+    for (kind, offset, size) in &atoms {
+        if kind == b"moov" {
+            writer.write_all(&moov.to_bytes())?;
+        } else {
+            file.seek(SeekFrom::Start(*offset))?;
+            io::copy(&mut (&mut file).take(*size), &mut writer)?;
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::temp_dir;
+
+    fn changes() -> TagChanges {
+        TagChanges {
+            title: Some("Звезда по имени Солнце".to_string()),
+            album: Some(String::new()),
+            track_number: Some(3),
+            track_total: Some(12),
+            ..Default::default()
+        }
+    }
+
+    fn flac_block(block_type: u8, last: bool, body: &[u8]) -> Vec<u8> {
+        let len = (body.len() as u32).to_be_bytes();
+        let mut block = vec![block_type | if last { 0x80 } else { 0 }, len[1], len[2], len[3]];
+        block.extend_from_slice(body);
+        block
+    }
+
+    #[test]
+    fn test_write_flac_vorbis_comments() {
+        let dir = temp_dir("tags-flac");
+        let path = dir.join("song.flac");
+
+        let mut original = VorbisComments::new();
+        original.comments = vec!["TITLE=Old".into(), "ALBUM=Gone".into(), "REPLAYGAIN_TRACK_GAIN=-3 dB".into()];
+        let mut file = b"fLaC".to_vec();
+        file.extend(flac_block(0, false, &[7u8; 34]));
+        file.extend(flac_block(FLAC_VORBIS_COMMENT, false, &original.to_bytes()));
+        file.extend(flac_block(1, true, &[0u8; 64]));
+        file.extend_from_slice(b"AUDIOFRAMES");
+        std::fs::write(&path, &file).unwrap();
+
+        write_to_file(&path, &changes()).unwrap();
+
+        let written = std::fs::read(&path).unwrap();
+        assert!(written.ends_with(b"AUDIOFRAMES"));
+        assert_eq!(&written[8..42], &[7u8; 34]);
+        let len = u32::from_be_bytes([0, written[43], written[44], written[45]]) as usize;
+        assert_eq!(written[42] & 0x7F, FLAC_VORBIS_COMMENT);
+        let (comments, _) = VorbisComments::parse(&written[46..46 + len]).unwrap();
+        assert_eq!(comments.comments, vec![
+            "REPLAYGAIN_TRACK_GAIN=-3 dB".to_string(),
+            "TITLE=Звезда по имени Солнце".to_string(),
+            "TRACKNUMBER=3".to_string(),
+            "TRACKTOTAL=12".to_string(),
+        ]);
+        assert!(!dir.join(".song.flac.tagtmp").exists());
+    }
+
+    #[test]
+    fn test_write_id3_keeps_other_frames() {
+        use id3::TagLike;
+
+        let dir = temp_dir("tags-id3");
+        let path = dir.join("song.mp3");
+        let mut tag = id3::Tag::new();
+        tag.set_title("Old");
+        tag.set_album("Gone");
+        tag.add_frame(id3::frame::ExtendedText { description: "MusicBrainz Album Id".into(), value: "abc".into() });
+        let mut bytes = Vec::new();
+        tag.write_to(&mut bytes, id3::Version::Id3v23).unwrap();
+        bytes.extend_from_slice(&[0xFF, 0xFB, 0x90, 0x00, 1, 2, 3, 4]);
+        std::fs::write(&path, &bytes).unwrap();
+
+        write_to_file(&path, &changes()).unwrap();
+
+        let tag = id3::Tag::read_from_path(&path).unwrap();
+        assert_eq!(tag.version(), id3::Version::Id3v23);
+        assert_eq!(tag.title(), Some("Звезда по имени Солнце"));
+        assert_eq!(tag.album(), None);
+        assert_eq!((tag.track(), tag.total_tracks()), (Some(3), Some(12)));
+        assert_eq!(tag.extended_texts().next().unwrap().value, "abc");
+        assert!(std::fs::read(&path).unwrap().ends_with(&[0xFF, 0xFB, 0x90, 0x00, 1, 2, 3, 4]));
+
+        // ID3v2.3 has no TDRC, so dates go to TYER and TDAT
+        write_to_file(&path, &TagChanges { date: Some("1989-03-21".to_string()), ..Default::default() }).unwrap();
+        let tag = id3::Tag::read_from_path(&path).unwrap();
+        assert_eq!(tag.year(), Some(1989));
+        assert_eq!(tag.get("TDAT").and_then(|frame| frame.content().text()), Some("2103"));
+        assert!(tag.date_recorded().is_none());
+        let bytes = std::fs::read(&path).unwrap();
+        assert!(bytes.windows(4).any(|w| w == b"TYER") && !bytes.windows(4).any(|w| w == b"TDRC"));
+
+        write_to_file(&path, &TagChanges { rating: Some(4), ..Default::default() }).unwrap();
+        let tag = id3::Tag::read_from_path(&path).unwrap();
+        let popm = tag.get("POPM").and_then(|frame| frame.content().popularimeter()).unwrap();
+        assert_eq!(popm.rating, 196);
+        assert!(write_to_file(&path, &TagChanges { rating: Some(6), ..Default::default() }).is_err());
+    }
+
+    #[test]
+    fn test_write_ogg_comment_packet() {
+        let dir = temp_dir("tags-ogg");
+        let path = dir.join("song.opus");
+
+        let mut head = b"OpusHead".to_vec();
+        head.extend_from_slice(&[1, 2, 0, 0, 0x80, 0xBB, 0, 0, 0, 0, 0]);
+        let mut tags = b"OpusTags".to_vec();
+        tags.extend_from_slice(&VorbisComments { vendor: "test".into(), comments: vec!["TITLE=Old".into()] }.to_bytes());
+        tags.extend_from_slice(&[1, 0xAB]); // preserved binary tail
+
+        let mut source = Vec::new();
+        {
+            let mut writer = PacketWriter::new(&mut source);
+            writer.write_packet(head.clone().into_boxed_slice(), 7, PacketWriteEndInfo::EndPage, 0).unwrap();
+            writer.write_packet(tags.into_boxed_slice(), 7, PacketWriteEndInfo::EndPage, 0).unwrap();
+            writer.write_packet(vec![1u8; 100].into_boxed_slice(), 7, PacketWriteEndInfo::NormalPacket, 960).unwrap();
+            writer.write_packet(vec![2u8; 100].into_boxed_slice(), 7, PacketWriteEndInfo::EndStream, 1920).unwrap();
+        }
+        std::fs::write(&path, &source).unwrap();
+
+        write_to_file(&path, &changes()).unwrap();
+
+        let mut reader = PacketReader::new(File::open(&path).unwrap());
+        let mut packets = Vec::new();
+        while let Some(packet) = reader.read_packet().unwrap() {
+            packets.push(packet);
+        }
+        assert_eq!(packets.len(), 4);
+        assert_eq!(packets[0].data, head);
+        let (comments, used) = VorbisComments::parse(&packets[1].data[8..]).unwrap();
+        assert_eq!(comments.vendor, "test");
+        assert!(comments.comments.contains(&"TITLE=Звезда по имени Солнце".to_string()));
+        assert_eq!(&packets[1].data[8 + used..], &[1, 0xAB]);
+        assert_eq!(packets[3].data, vec![2u8; 100]);
+        assert_eq!(packets[3].absgp_page(), 1920);
+        assert!(packets[3].last_in_stream());
+    }
+
+    #[test]
+    fn test_write_mp4_ilst_and_chunk_offsets() {
+        let dir = temp_dir("tags-mp4");
+        let path = dir.join("song.m4a");
+
+        let ftyp = Atom::new(b"ftyp", b"M4A \0\0\0\0M4A isom".to_vec());
+        let mdat_payload = b"AACFRAMES".to_vec();
+        let build = |chunk_offset: u32| {
+            let mut stco = vec![0, 0, 0, 0, 0, 0, 0, 1];
+            stco.extend_from_slice(&chunk_offset.to_be_bytes());
+            let stbl = Atom::new(b"stbl", Atom::new(b"stco", stco).to_bytes());
+            let minf = Atom::new(b"minf", stbl.to_bytes());
+            let mdia = Atom::new(b"mdia", minf.to_bytes());
+            let trak = Atom::new(b"trak", mdia.to_bytes());
+            let mut moov_body = Atom::new(b"mvhd", vec![0u8; 100]).to_bytes();
+            moov_body.extend_from_slice(&trak.to_bytes());
+            Atom::new(b"moov", moov_body)
+        };
+        let moov_len = build(0).to_bytes().len() as u32;
+        let audio_offset = ftyp.to_bytes().len() as u32 + moov_len + 8;
+        let mut file = ftyp.to_bytes();
+        file.extend_from_slice(&build(audio_offset).to_bytes());
+        file.extend_from_slice(&Atom::new(b"mdat", mdat_payload.clone()).to_bytes());
+        std::fs::write(&path, &file).unwrap();
+
+        write_to_file(&path, &changes()).unwrap();
+
+        let written = std::fs::read(&path).unwrap();
+        let atoms = parse_atoms(&written).unwrap();
+        let kinds: Vec<&[u8; 4]> = atoms.iter().map(|a| &a.kind).collect();
+        assert_eq!(kinds, vec![b"ftyp", b"moov", b"mdat"]);
+
+        let moov = parse_atoms(&atoms[1].body).unwrap();
+        let udta = parse_atoms(&moov.iter().find(|a| &a.kind == b"udta").unwrap().body).unwrap();
+        let meta = parse_atoms(&udta[0].body[4..]).unwrap();
+        let items = parse_atoms(&meta.iter().find(|a| &a.kind == b"ilst").unwrap().body).unwrap();
+        let title = items.iter().find(|a| &a.kind == b"\xa9nam").unwrap();
+        assert_eq!(ilst_data(title).unwrap(), "Звезда по имени Солнце".as_bytes());
+        let trkn = ilst_data(items.iter().find(|a| &a.kind == b"trkn").unwrap()).unwrap();
+        assert_eq!(trkn, &[0, 0, 0, 3, 0, 12, 0, 0]);
+
+        // The chunk offset still points at the audio data
+        let trak = parse_atoms(&moov.iter().find(|a| &a.kind == b"trak").unwrap().body).unwrap();
+        let minf = parse_atoms(&parse_atoms(&trak[0].body).unwrap()[0].body).unwrap();
+        let stco = &parse_atoms(&minf[0].body).unwrap()[0];
+        let offset = u32::from_be_bytes(stco.body[8..12].try_into().unwrap()) as usize;
+        assert_eq!(&written[offset..offset + mdat_payload.len()], &mdat_payload[..]);
+    }
+}
+
+// Tauri Commands
+
+use crate::audio_engine::AudioEngineState;
+use crate::cue;
+use crate::file_manager::{FileManager, TrackMetadata};
+use crate::library::{LibraryChanges, LibraryState};
+use tauri::{AppHandle, Emitter, State};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TagWriteFailure {
+    pub track_id: String,
+    pub error: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct TagWriteReport {
+    pub updated: Vec<TrackMetadata>,
+    pub failed: Vec<TagWriteFailure>,
+}
+
+/// Write the same changes to one or more library tracks. A track that is
+/// currently playing is skipped unless `reopen_playing` is set, in which case
+/// the engine releases the file, and reopens it at the same position afterwards.
+#[tauri::command]
+pub async fn write_tags(
+    track_ids: Vec<String>,
+    changes: TagChanges,
+    reopen_playing: Option<bool>,
+    library: State<'_, LibraryState>,
+    engine: State<'_, AudioEngineState>,
+    app: AppHandle,
+) -> Result<TagWriteReport, String> {
+    let library = library.library.clone();
+    let engine = engine.engine.clone();
+    let reopen_playing = reopen_playing.unwrap_or(false);
+    if changes.is_empty() {
+        return Ok(TagWriteReport::default());
+    }
+
+    let (report, reopened) = tauri::async_runtime::spawn_blocking(move || {
+        let file_manager = FileManager::new();
+        let mut report = TagWriteReport::default();
+        let mut reopened = None;
+
+        for track_id in track_ids {
+            let track = library.lock()
+                .map_err(|e| format!("Failed to lock library: {}", e))?
+                .track(&track_id)
+                .map_err(|e| e.to_string())?;
+            let Some(track) = track else {
+                report.failed.push(TagWriteFailure { track_id, error: "Track not found".to_string() });
+                continue;
+            };
+            // Tags belong to the whole file, not one track of its CUE sheet
+            if cue::is_virtual(&track.file_path) {
+                let error = TagWriteError::UnsupportedFormat(format!("CUE sheet track {}", track.file_path));
+                report.failed.push(TagWriteFailure { track_id, error: error.to_string() });
+                continue;
+            }
+            let path = PathBuf::from(&track.file_path);
+
+            // Hold the engine so playback cannot switch to this file mid-write
+            let engine_guard = engine.lock()
+                .map_err(|e| format!("Failed to lock engine: {}", e))?;
+            let state = engine_guard.get_state()
+                .map_err(|e| e.to_string())?;
+            let is_current = state.current_track.as_deref() == Some(track.file_path.as_str());
+
+            let result = if is_current && !reopen_playing {
+                Err(TagWriteError::FileInUse(track.file_path.clone()))
+            } else if is_current {
+                engine_guard.stop().map_err(|e| e.to_string())?;
+                let result = write_to_file(&path, &changes);
+                let reload = engine_guard.load_track(&path)
+                    .and_then(|_| engine_guard.seek(state.current_time))
+                    .and_then(|_| if state.is_playing { engine_guard.play() } else { Ok(()) });
+                match reload {
+                    Ok(()) => reopened = engine_guard.get_state().ok(),
+                    Err(e) => log::warn!("Failed to reopen {} after writing tags: {}", track.file_path, e),
+                }
+                result
+            } else {
+                write_to_file(&path, &changes)
+            };
+            drop(engine_guard);
+
+            match result {
+                Ok(()) => {
+                    let mut metadata = file_manager.metadata_or_fallback(&path);
+                    let stored = library.lock()
+                        .map_err(|e| e.to_string())
+                        .and_then(|library| library.upsert_track(&metadata).map_err(|e| e.to_string()));
+                    match stored {
+                        Ok(id) => metadata.id = id,
+                        Err(e) => log::warn!("Failed to update library for {}: {}", track.file_path, e),
+                    }
+                    report.updated.push(metadata);
+                }
+                Err(e) => report.failed.push(TagWriteFailure { track_id, error: e.to_string() }),
+            }
+        }
+
+        Ok::<_, String>((report, reopened))
+    })
+    .await
+    .map_err(|e| format!("Tag writing task failed: {}", e))??;
+
+    if let Some(state) = reopened {
+        let _ = app.emit("audio:state_changed", &state);
+    }
+    if !report.updated.is_empty() {
+        let changes = LibraryChanges {
+            updated: report.updated.iter().map(|t| t.id.clone()).collect(),
+            ..Default::default()
+        };
+        let _ = app.emit("library:changed", &changes);
+        crate::smart_playlists::library_changed(&app);
+    }
+
+    Ok(report)
+}
@@ -0,0 +1,223 @@
+// Test Support Module
+// Minimal local HTTP server and fixture helpers shared by the unit tests
+
+use crate::errors::LibraryError;
+use crate::file_manager::FileManager;
+use crate::library::{Library, ScanSummary};
+use crate::scanner::{self, ScanHandle};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+
+#[derive(Debug, Clone)]
+pub struct TestRequest {
+    pub path: String,
+    pub headers: Vec<(String, String)>,
+}
+
+impl TestRequest {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Parse a `Range: bytes=start-[end]` header
+    pub fn range(&self) -> Option<(u64, Option<u64>)> {
+        let spec = self.header("Range")?.strip_prefix("bytes=")?;
+        let (start, end) = spec.split_once('-')?;
+        let start = start.trim().parse().ok()?;
+        let end = end.trim().parse().ok();
+        Some((start, end))
+    }
+}
+
+type Handler = dyn Fn(&TestRequest, &mut TcpStream) + Send + Sync + 'static;
+
+/// HTTP/1.0 server bound to a random localhost port; every connection is
+/// handled on its own thread so long-lived streams do not block others
+pub struct TestServer {
+    addr: SocketAddr,
+    shutdown: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl TestServer {
+    pub fn start<F>(handler: F) -> Self
+    where
+        F: Fn(&TestRequest, &mut TcpStream) + Send + Sync + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("Failed to bind test server");
+        let addr = listener.local_addr().expect("Failed to read test server address");
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let handler: Arc<Handler> = Arc::new(handler);
+
+        let shutdown_flag = shutdown.clone();
+        let handle = std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                if shutdown_flag.load(Ordering::SeqCst) {
+                    break;
+                }
+                let Ok(mut stream) = stream else { continue };
+                let handler = handler.clone();
+                std::thread::spawn(move || {
+                    if let Some(request) = read_request(&stream) {
+                        handler(&request, &mut stream);
+                    }
+                });
+            }
+        });
+
+        Self {
+            addr,
+            shutdown,
+            handle: Some(handle),
+        }
+    }
+
+    /// Serve files from a directory, honouring range requests
+    pub fn serve_dir(root: &Path) -> Self {
+        let root = root.to_path_buf();
+        Self::start(move |request, stream| {
+            let relative = request.path.trim_start_matches('/');
+            match std::fs::read(root.join(relative)) {
+                Ok(bytes) => serve_bytes(request, stream, &bytes, "application/octet-stream"),
+                Err(_) => {
+                    let _ = stream.write_all(b"HTTP/1.0 404 Not Found\r\nContent-Length: 0\r\n\r\n");
+                }
+            }
+        })
+    }
+
+    pub fn url(&self, path: &str) -> String {
+        format!("http://{}/{}", self.addr, path.trim_start_matches('/'))
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+        // Unblock the accept loop
+        let _ = TcpStream::connect(self.addr);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+fn read_request(stream: &TcpStream) -> Option<TestRequest> {
+    let mut reader = BufReader::new(stream.try_clone().ok()?);
+    let mut line = String::new();
+    reader.read_line(&mut line).ok()?;
+
+    let mut parts = line.split_whitespace();
+    let _method = parts.next()?;
+    let path = parts.next()?.to_string();
+
+    let mut headers = Vec::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line).ok()? == 0 {
+            break;
+        }
+        let trimmed = line.trim_end();
+        if trimmed.is_empty() {
+            break;
+        }
+        if let Some((key, value)) = trimmed.split_once(':') {
+            headers.push((key.trim().to_string(), value.trim().to_string()));
+        }
+    }
+
+    Some(TestRequest { path, headers })
+}
+
+/// Write a full or partial (206) response for `bytes`
+pub fn serve_bytes(request: &TestRequest, stream: &mut TcpStream, bytes: &[u8], content_type: &str) {
+    let total = bytes.len() as u64;
+    let response = match request.range() {
+        Some((start, end)) if start < total => {
+            let end = end.unwrap_or(total - 1).min(total - 1);
+            let body = &bytes[start as usize..=end as usize];
+            let mut head = format!(
+                "HTTP/1.0 206 Partial Content\r\nContent-Type: {}\r\nContent-Length: {}\r\nContent-Range: bytes {}-{}/{}\r\nAccept-Ranges: bytes\r\n\r\n",
+                content_type,
+                body.len(),
+                start,
+                end,
+                total
+            )
+            .into_bytes();
+            head.extend_from_slice(body);
+            head
+        }
+        Some(_) => format!("HTTP/1.0 416 Range Not Satisfiable\r\nContent-Range: bytes */{}\r\n\r\n", total).into_bytes(),
+        None => {
+            let mut head = format!(
+                "HTTP/1.0 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\nAccept-Ranges: bytes\r\n\r\n",
+                content_type, total
+            )
+            .into_bytes();
+            head.extend_from_slice(bytes);
+            head
+        }
+    };
+    let _ = stream.write_all(&response);
+}
+
+/// Create an empty, uniquely named directory under the system temp dir
+pub fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("gemini-audio-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).expect("Failed to create temp dir");
+    dir
+}
+
+/// Rescan `root` the way `library_scan_folder` does: plan, probe on the
+/// scanner's worker pool, then apply
+pub fn rescan(library: &mut Library, file_manager: &FileManager, root: &Path) -> Result<ScanSummary, LibraryError> {
+    let plan = library.plan_rescan(file_manager, root)?;
+    let paths: Vec<PathBuf> = plan.to_probe.iter().map(|(path, _)| path.clone()).collect();
+    let results = scanner::extract_all(file_manager, &paths, &ScanHandle::default(), |_| {});
+    let probed = results.into_iter()
+        .zip(plan.to_probe.iter())
+        .filter_map(|(track, (_, stamp))| track.map(|track| (track, *stamp)))
+        .collect();
+    library.apply_rescan(plan, probed)
+}
+
+/// 16-bit mono PCM WAV containing a sine tone
+pub fn sine_wav(seconds: f32, sample_rate: u32, frequency: f32) -> Vec<u8> {
+    wav_from_fn(seconds, sample_rate, |t| (t * frequency * std::f32::consts::TAU).sin() * 0.5)
+}
+
+/// 16-bit mono PCM WAV with samples in -1.0..=1.0 given by `signal(seconds)`
+pub fn wav_from_fn(seconds: f32, sample_rate: u32, signal: impl Fn(f32) -> f32) -> Vec<u8> {
+    let samples = (seconds * sample_rate as f32) as u32;
+    let data_len = samples * 2;
+
+    let mut wav = Vec::with_capacity(44 + data_len as usize);
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    wav.extend_from_slice(&1u16.to_le_bytes()); // mono
+    wav.extend_from_slice(&sample_rate.to_le_bytes());
+    wav.extend_from_slice(&(sample_rate * 2).to_le_bytes());
+    wav.extend_from_slice(&2u16.to_le_bytes());
+    wav.extend_from_slice(&16u16.to_le_bytes());
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_len.to_le_bytes());
+
+    for i in 0..samples {
+        let value = signal(i as f32 / sample_rate as f32) * i16::MAX as f32;
+        wav.extend_from_slice(&(value as i16).to_le_bytes());
+    }
+
+    wav
+}
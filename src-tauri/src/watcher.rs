@@ -0,0 +1,166 @@
+// Watcher Module
+// Watches registered library folders and applies filesystem changes to the library
+
+use crate::file_manager::FileManager;
+use crate::library::{Library, LibraryChanges};
+use notify_debouncer_mini::notify::{RecommendedWatcher, RecursiveMode};
+use notify_debouncer_mini::{new_debouncer, DebounceEventResult, Debouncer};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+
+/// Quiet period before a burst of events (e.g. a download or a copied album) is applied
+const DEBOUNCE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Filesystem watcher over every library root
+pub struct LibraryWatcher {
+    debouncer: Debouncer<RecommendedWatcher>,
+}
+
+impl LibraryWatcher {
+    /// Start watching; debounced batches of changed paths are applied to
+    /// `library` and reported through `on_change`
+    pub fn new<F>(library: Arc<Mutex<Library>>, on_change: F) -> Result<Self, String>
+    where
+        F: Fn(LibraryChanges) + Send + 'static,
+    {
+        let file_manager = FileManager::new();
+        let debouncer = new_debouncer(DEBOUNCE_TIMEOUT, move |result: DebounceEventResult| {
+            let events = match result {
+                Ok(events) => events,
+                Err(e) => {
+                    log::warn!("Library watcher error: {}", e);
+                    return;
+                }
+            };
+
+            let paths: Vec<PathBuf> = events.into_iter().map(|event| event.path).collect();
+            match apply(&library, &file_manager, &paths) {
+                Ok(changes) if !changes.is_empty() => on_change(changes),
+                Ok(_) => {}
+                Err(e) => log::warn!("Failed to apply library changes: {}", e),
+            }
+        })
+        .map_err(|e| format!("Failed to start library watcher: {}", e))?;
+
+        Ok(Self { debouncer })
+    }
+
+    pub fn watch(&mut self, path: &Path) -> Result<(), String> {
+        self.debouncer
+            .watcher()
+            .watch(path, RecursiveMode::Recursive)
+            .map_err(|e| format!("Failed to watch {}: {}", path.display(), e))
+    }
+
+    pub fn unwatch(&mut self, path: &Path) -> Result<(), String> {
+        self.debouncer
+            .watcher()
+            .unwatch(path)
+            .map_err(|e| format!("Failed to unwatch {}: {}", path.display(), e))
+    }
+}
+
+/// Plan under the library lock, probe the changed files without it, then
+/// apply the results in one short transaction
+fn apply(library: &Mutex<Library>, file_manager: &FileManager, paths: &[PathBuf]) -> Result<LibraryChanges, String> {
+    let plan = library.lock()
+        .map_err(|e| format!("Failed to lock library: {}", e))?
+        .plan_changes(file_manager, paths)
+        .map_err(|e| e.to_string())?;
+
+    let probed = plan.to_probe.iter()
+        .map(|(path, stamp)| (file_manager.metadata_or_fallback(path), *stamp))
+        .collect();
+
+    library.lock()
+        .map_err(|e| format!("Failed to lock library: {}", e))?
+        .apply_changes(plan, probed)
+        .map_err(|e| e.to_string())
+}
+
+/// Watcher shared with the commands that add and remove library folders
+pub struct WatcherState {
+    pub watcher: Mutex<Option<LibraryWatcher>>,
+}
+
+impl WatcherState {
+    /// Start watching a newly registered folder; failures are logged, not fatal
+    pub fn watch(&self, path: &Path) {
+        let result = self.watcher.lock()
+            .map_err(|e| e.to_string())
+            .and_then(|mut watcher| match watcher.as_mut() {
+                Some(watcher) => watcher.watch(path),
+                None => Ok(()),
+            });
+        if let Err(e) = result {
+            log::warn!("{}", e);
+        }
+    }
+
+    pub fn unwatch(&self, path: &Path) {
+        let result = self.watcher.lock()
+            .map_err(|e| e.to_string())
+            .and_then(|mut watcher| match watcher.as_mut() {
+                Some(watcher) => watcher.unwatch(path),
+                None => Ok(()),
+            });
+        if let Err(e) = result {
+            log::warn!("{}", e);
+        }
+    }
+}
+
+/// Create the watcher for every registered folder and emit `library:changed`
+/// whenever it updates the library
+pub fn start(app: &AppHandle, library: Arc<Mutex<Library>>) -> WatcherState {
+    let folders = library.lock()
+        .map_err(|e| e.to_string())
+        .and_then(|library| library.folders().map_err(|e| e.to_string()))
+        .unwrap_or_else(|e| {
+            log::warn!("Failed to list library folders: {}", e);
+            Vec::new()
+        });
+
+    let emitter = app.clone();
+    let watcher = LibraryWatcher::new(library, move |changes| {
+        if let Err(e) = emitter.emit("library:changed", &changes) {
+            log::warn!("Failed to emit library change: {}", e);
+        }
+        crate::smart_playlists::library_changed(&emitter);
+    });
+
+    let watcher = match watcher {
+        Ok(mut watcher) => {
+            for folder in &folders {
+                if let Err(e) = watcher.watch(Path::new(&folder.path)) {
+                    log::warn!("{}", e);
+                }
+            }
+            Some(watcher)
+        }
+        Err(e) => {
+            // The library still works, it just will not notice changes until a rescan
+            log::warn!("{}", e);
+            None
+        }
+    };
+
+    WatcherState {
+        watcher: Mutex::new(watcher),
+    }
+}
+
+/// Watch `path` if the watcher is running; used after a folder is registered
+pub fn watch_folder(app: &AppHandle, path: &Path) {
+    if let Some(watcher) = app.try_state::<WatcherState>() {
+        watcher.watch(path);
+    }
+}
+
+pub fn unwatch_folder(app: &AppHandle, path: &Path) {
+    if let Some(watcher) = app.try_state::<WatcherState>() {
+        watcher.unwatch(path);
+    }
+}